@@ -0,0 +1,4 @@
+-- 不同来源可在同一时刻报出同一币种对，主键加入 source
+ALTER TABLE exchange_rate_history DROP CONSTRAINT IF EXISTS exchange_rate_history_pkey;
+ALTER TABLE exchange_rate_history
+    ADD CONSTRAINT exchange_rate_history_pkey PRIMARY KEY (base_currency, target_currency, source, time);
@@ -0,0 +1,8 @@
+-- 账户ID是 MongoDB ObjectId 的十六进制字符串，不是 UUID
+DO $$
+BEGIN
+    IF (SELECT data_type FROM information_schema.columns
+        WHERE table_name = 'account_balance_history' AND column_name = 'account_id') = 'uuid' THEN
+        ALTER TABLE account_balance_history ALTER COLUMN account_id TYPE VARCHAR(24) USING account_id::text;
+    END IF;
+END $$;
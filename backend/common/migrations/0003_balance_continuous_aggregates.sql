@@ -0,0 +1,36 @@
+-- 按日/按月聚合的余额 (连续聚合，开启实时聚合以包含尚未物化的最新数据)
+CREATE MATERIALIZED VIEW IF NOT EXISTS account_balance_daily
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT account_id,
+       time_bucket(INTERVAL '1 day', time) AS bucket,
+       first(balance, time) AS open_balance,
+       last(balance, time) AS close_balance,
+       min(balance) AS min_balance,
+       max(balance) AS max_balance
+FROM account_balance_history
+GROUP BY account_id, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('account_balance_daily',
+    start_offset => INTERVAL '3 days',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS account_balance_monthly
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT account_id,
+       time_bucket(INTERVAL '1 month', time) AS bucket,
+       first(balance, time) AS open_balance,
+       last(balance, time) AS close_balance,
+       min(balance) AS min_balance,
+       max(balance) AS max_balance
+FROM account_balance_history
+GROUP BY account_id, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('account_balance_monthly',
+    start_offset => INTERVAL '3 months',
+    end_offset => INTERVAL '1 day',
+    schedule_interval => INTERVAL '1 day',
+    if_not_exists => TRUE);
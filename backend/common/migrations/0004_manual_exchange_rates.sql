@@ -0,0 +1,8 @@
+-- 管理员手动维护的汇率，每个币种对保留当前值及设置时间；历史表有保留期，不能作为手动汇率的来源
+CREATE TABLE IF NOT EXISTS manual_exchange_rates (
+    base_currency VARCHAR(3) NOT NULL,
+    target_currency VARCHAR(3) NOT NULL,
+    rate DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (base_currency, target_currency)
+);
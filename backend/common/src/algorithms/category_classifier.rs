@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::dedup::normalize_text;
+
+/// 拉普拉斯平滑系数
+const ALPHA: f64 = 1.0;
+
+/// 分类建议
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub category_id: String,
+    /// 在候选分类上归一化后的后验概率
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClassStats {
+    documents: u32,
+    total_tokens: u32,
+    tokens: HashMap<String, u32>,
+}
+
+/// 多项式朴素贝叶斯分类器：特征为描述和收款方的词、收款方全称以及金额数量级；
+/// 支持逐笔学习和撤销，用户修改交易分类时可以增量更新
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "ClassifierData", into = "ClassifierData")]
+pub struct CategoryClassifier {
+    classes: HashMap<String, ClassStats>,
+    /// 特征 -> 在所有分类中出现的总次数，键数即词表大小
+    vocabulary: HashMap<String, u32>,
+    documents: u32,
+}
+
+/// 持久化格式：特征以 [词, 次数] 数组保存，避免把任意文本用作 BSON 字段名
+#[derive(Serialize, Deserialize)]
+struct ClassifierData {
+    classes: Vec<ClassData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClassData {
+    category_id: String,
+    documents: u32,
+    tokens: Vec<(String, u32)>,
+}
+
+impl From<ClassifierData> for CategoryClassifier {
+    fn from(data: ClassifierData) -> Self {
+        let mut classifier = Self::default();
+        for class in data.classes {
+            let mut stats = ClassStats {
+                documents: class.documents,
+                ..Default::default()
+            };
+            for (token, count) in class.tokens {
+                stats.total_tokens += count;
+                *classifier.vocabulary.entry(token.clone()).or_insert(0) += count;
+                stats.tokens.insert(token, count);
+            }
+            classifier.documents += stats.documents;
+            classifier.classes.insert(class.category_id, stats);
+        }
+        classifier
+    }
+}
+
+impl From<CategoryClassifier> for ClassifierData {
+    fn from(classifier: CategoryClassifier) -> Self {
+        Self {
+            classes: classifier
+                .classes
+                .into_iter()
+                .map(|(category_id, stats)| ClassData {
+                    category_id,
+                    documents: stats.documents,
+                    tokens: stats.tokens.into_iter().collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{ac00}'..='\u{d7af}')
+}
+
+/// 切词：按空白和标点切分；中日韩文本没有空格，按相邻两字切分；纯数字 (流水号、卡号) 忽略
+fn push_words(features: &mut Vec<String>, text: &str) {
+    for word in text.split_whitespace() {
+        if word.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let chars: Vec<char> = word.chars().collect();
+        if chars.iter().any(|c| is_cjk(*c)) {
+            if chars.len() == 1 {
+                features.push(word.to_string());
+            }
+            features.extend(chars.windows(2).map(|pair| pair.iter().collect::<String>()));
+        } else if chars.len() >= 2 {
+            features.push(word.to_string());
+        }
+    }
+}
+
+impl CategoryClassifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 提取交易的特征
+    pub fn features(description: &str, payee: Option<&str>, amount: f64) -> Vec<String> {
+        let mut features = Vec::new();
+        push_words(&mut features, &normalize_text(description));
+        if let Some(payee) = payee.map(normalize_text).filter(|p| !p.is_empty()) {
+            push_words(&mut features, &payee);
+            features.push(format!("payee:{}", payee));
+        }
+        // 金额按2的幂分档，区分小额消费和大额支出
+        let bucket = amount.abs().max(1.0).log2().floor() as i32;
+        features.push(format!("amount:{}", bucket));
+        features
+    }
+
+    /// 已学习的交易数
+    pub fn documents(&self) -> u32 {
+        self.documents
+    }
+
+    pub fn learn(&mut self, category_id: &str, features: &[String]) {
+        let stats = self.classes.entry(category_id.to_string()).or_default();
+        stats.documents += 1;
+        self.documents += 1;
+        for token in features {
+            *stats.tokens.entry(token.clone()).or_insert(0) += 1;
+            stats.total_tokens += 1;
+            *self.vocabulary.entry(token.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// 撤销一次 learn，用于交易被修改或删除
+    pub fn unlearn(&mut self, category_id: &str, features: &[String]) {
+        let Some(stats) = self.classes.get_mut(category_id) else {
+            return;
+        };
+        if stats.documents == 0 {
+            return;
+        }
+        stats.documents -= 1;
+        self.documents -= 1;
+
+        for token in features {
+            let Some(count) = stats.tokens.get_mut(token) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                stats.tokens.remove(token);
+            }
+            stats.total_tokens -= 1;
+            if let Some(total) = self.vocabulary.get_mut(token) {
+                *total -= 1;
+                if *total == 0 {
+                    self.vocabulary.remove(token);
+                }
+            }
+        }
+
+        if stats.documents == 0 {
+            self.classes.remove(category_id);
+        }
+    }
+
+    /// 在 allowed 接受的分类中按后验概率排序返回前 limit 个建议；未见过的特征不参与计算
+    pub fn suggest<F>(&self, features: &[String], allowed: F, limit: usize) -> Vec<CategorySuggestion>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let vocabulary_size = self.vocabulary.len() as f64;
+        let known: Vec<&String> = features.iter().filter(|t| self.vocabulary.contains_key(*t)).collect();
+
+        let scores: Vec<(&String, f64)> = self
+            .classes
+            .iter()
+            .filter(|(category_id, stats)| stats.documents > 0 && allowed(category_id))
+            .map(|(category_id, stats)| {
+                let denominator = stats.total_tokens as f64 + ALPHA * vocabulary_size;
+                let likelihood: f64 = known
+                    .iter()
+                    .map(|token| {
+                        let count = stats.tokens.get(*token).copied().unwrap_or(0) as f64;
+                        ((count + ALPHA) / denominator).ln()
+                    })
+                    .sum();
+                let prior = (stats.documents as f64 / self.documents as f64).ln();
+                (category_id, prior + likelihood)
+            })
+            .collect();
+
+        // softmax，减去最大值防止下溢
+        let Some(max) = scores.iter().map(|(_, score)| *score).reduce(f64::max) else {
+            return Vec::new();
+        };
+        let total: f64 = scores.iter().map(|(_, score)| (score - max).exp()).sum();
+        let mut suggestions: Vec<CategorySuggestion> = scores
+            .into_iter()
+            .map(|(category_id, score)| CategorySuggestion {
+                category_id: category_id.clone(),
+                confidence: (score - max).exp() / total,
+            })
+            .collect();
+
+        suggestions.sort_by(|a, b| {
+            b.confidence
+                .total_cmp(&a.confidence)
+                .then_with(|| a.category_id.cmp(&b.category_id))
+        });
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn train(samples: &[(&str, &str, Option<&str>, f64)]) -> CategoryClassifier {
+        let mut classifier = CategoryClassifier::new();
+        for (category, description, payee, amount) in samples {
+            classifier.learn(category, &CategoryClassifier::features(description, *payee, *amount));
+        }
+        classifier
+    }
+
+    fn history() -> CategoryClassifier {
+        train(&[
+            ("dining", "Starbucks latte", Some("Starbucks"), 32.0),
+            ("dining", "Starbucks americano", Some("Starbucks"), 28.0),
+            ("dining", "午餐 麦当劳", Some("麦当劳"), 45.0),
+            ("transport", "Uber trip downtown", Some("Uber"), 60.0),
+            ("transport", "地铁充值", None, 100.0),
+            ("housing", "Monthly rent", Some("Landlord"), 4500.0),
+        ])
+    }
+
+    #[test]
+    fn test_features() {
+        let features = CategoryClassifier::features("午餐 McDonald's #20240301", Some("麦当劳"), 45.0);
+        assert!(features.contains(&"午餐".to_string()));
+        assert!(features.contains(&"mcdonald".to_string()));
+        assert!(features.contains(&"麦当".to_string()));
+        assert!(features.contains(&"当劳".to_string()));
+        assert!(features.contains(&"payee:麦当劳".to_string()));
+        assert!(features.contains(&"amount:5".to_string()));
+        assert!(!features.iter().any(|f| f == "20240301"));
+    }
+
+    #[test]
+    fn test_suggest_ranks_by_history() {
+        let classifier = history();
+
+        let suggestions = classifier.suggest(
+            &CategoryClassifier::features("STARBUCKS mocha", None, 35.0),
+            |_| true,
+            3,
+        );
+        assert_eq!(suggestions[0].category_id, "dining");
+        assert!(suggestions[0].confidence > 0.5);
+        assert!(suggestions.windows(2).all(|w| w[0].confidence >= w[1].confidence));
+
+        let suggestions = classifier.suggest(&CategoryClassifier::features("地铁 出行", None, 5.0), |_| true, 3);
+        assert_eq!(suggestions[0].category_id, "transport");
+
+        let all = classifier.suggest(&CategoryClassifier::features("rent", None, 4500.0), |_| true, 10);
+        assert_eq!(all[0].category_id, "housing");
+        let total: f64 = all.iter().map(|s| s.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_suggest_respects_allowed_categories() {
+        let classifier = history();
+        let suggestions = classifier.suggest(
+            &CategoryClassifier::features("Starbucks", None, 30.0),
+            |category| category != "dining",
+            3,
+        );
+        assert!(suggestions.iter().all(|s| s.category_id != "dining"));
+        assert!(CategoryClassifier::new()
+            .suggest(&CategoryClassifier::features("Starbucks", None, 30.0), |_| true, 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_correction_moves_prediction() {
+        let mut classifier = history();
+        let features = CategoryClassifier::features("Uber Eats order", Some("Uber"), 55.0);
+        assert_eq!(classifier.suggest(&features, |_| true, 1)[0].category_id, "transport");
+
+        // 用户多次把 Uber Eats 改为餐饮
+        for _ in 0..3 {
+            classifier.learn("transport", &features);
+            classifier.unlearn("transport", &features);
+            classifier.learn("dining", &features);
+        }
+        assert_eq!(classifier.suggest(&features, |_| true, 1)[0].category_id, "dining");
+    }
+
+    #[test]
+    fn test_unlearn_reverses_learn_and_roundtrips() {
+        let mut classifier = history();
+        let before = classifier.documents();
+        let features = CategoryClassifier::features("Gym membership", None, 300.0);
+        classifier.learn("healthcare", &features);
+        classifier.unlearn("healthcare", &features);
+        assert_eq!(classifier.documents(), before);
+        assert!(!classifier.classes.contains_key("healthcare"));
+        assert!(!classifier.vocabulary.contains_key("gym"));
+
+        // 撤销从未学习过的分类不影响模型
+        classifier.unlearn("missing", &features);
+        assert_eq!(classifier.documents(), before);
+
+        let json = serde_json::to_string(&classifier).unwrap();
+        let restored: CategoryClassifier = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.documents(), classifier.documents());
+        assert_eq!(restored.vocabulary, classifier.vocabulary);
+        let features = CategoryClassifier::features("Starbucks", None, 30.0);
+        assert_eq!(
+            restored.suggest(&features, |_| true, 1)[0].category_id,
+            classifier.suggest(&features, |_| true, 1)[0].category_id
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use crate::models::Transaction;
+use sha2::{Digest, Sha256};
+
+/// 智能去重器：精确哈希匹配 + 模糊匹配
+pub struct DuplicateDetector {
+    amount_tolerance: f64,
+    date_window_days: i64,
+    similarity_threshold: f64,
+}
+
+/// 模糊匹配结果
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DuplicateMatch {
+    pub transaction_id: String,
+    pub score: f64,
+}
+
+impl DuplicateDetector {
+    /// 创建去重器
+    pub fn new(amount_tolerance: f64, date_window_days: i64, similarity_threshold: f64) -> Self {
+        Self {
+            amount_tolerance,
+            date_window_days,
+            similarity_threshold,
+        }
+    }
+
+    pub fn amount_tolerance(&self) -> f64 {
+        self.amount_tolerance
+    }
+
+    pub fn date_window_days(&self) -> i64 {
+        self.date_window_days
+    }
+
+    /// 计算规范化哈希：账户 + 类型 + 金额(分) + 转入账户/金额 + 日期(按天) + 规范化的收款方/描述
+    pub fn canonical_hash(tx: &Transaction) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(tx.account_id.as_bytes());
+        hasher.update(b"|");
+        hasher.update(tx.transaction_type.as_bytes());
+        hasher.update(b"|");
+        hasher.update(to_cents(tx.amount).to_string());
+        hasher.update(b"|");
+        // 仅转账计入转入方，非转账交易的哈希与已存储的值保持一致
+        if let Some(to_account_id) = &tx.to_account_id {
+            hasher.update(to_account_id.as_bytes());
+            hasher.update(b"|");
+            hasher.update(tx.to_amount.map(|amount| to_cents(amount).to_string()).unwrap_or_default());
+            hasher.update(b"|");
+        }
+        hasher.update(tx.transaction_date.format("%Y-%m-%d").to_string());
+        hasher.update(b"|");
+        hasher.update(Self::match_text(tx));
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// 判断两笔交易是否疑似重复，返回0~1的匹配分数
+    pub fn match_score(&self, candidate: &Transaction, existing: &Transaction) -> Option<f64> {
+        if candidate.account_id != existing.account_id
+            || candidate.transaction_type != existing.transaction_type
+            || candidate.to_account_id != existing.to_account_id
+        {
+            return None;
+        }
+
+        let amount_diff = (candidate.amount - existing.amount).abs();
+        if amount_diff > self.amount_tolerance + f64::EPSILON {
+            return None;
+        }
+
+        let days_apart = (candidate.transaction_date - existing.transaction_date)
+            .num_days()
+            .abs();
+        if days_apart > self.date_window_days {
+            return None;
+        }
+
+        let similarity = string_similarity(&Self::match_text(candidate), &Self::match_text(existing));
+        if similarity < self.similarity_threshold {
+            return None;
+        }
+
+        let amount_score = if self.amount_tolerance > 0.0 {
+            1.0 - amount_diff / self.amount_tolerance
+        } else {
+            1.0
+        };
+        let date_score = 1.0 - days_apart as f64 / (self.date_window_days + 1) as f64;
+
+        Some(0.5 * similarity + 0.25 * amount_score.max(0.0) + 0.25 * date_score)
+    }
+
+    /// 在已有交易中查找疑似重复项，按分数从高到低排序
+    pub fn find_matches(&self, candidate: &Transaction, existing: &[Transaction]) -> Vec<DuplicateMatch> {
+        let mut matches: Vec<DuplicateMatch> = existing
+            .iter()
+            .filter(|tx| tx.id.is_some() && tx.id != candidate.id)
+            .filter_map(|tx| {
+                self.match_score(candidate, tx).map(|score| DuplicateMatch {
+                    transaction_id: tx.id.clone().unwrap(),
+                    score,
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        matches
+    }
+
+    fn match_text(tx: &Transaction) -> String {
+        let payee = tx.payee.as_deref().unwrap_or_default();
+        normalize_text(&format!("{} {}", payee, tx.description))
+    }
+}
+
+impl Default for DuplicateDetector {
+    /// 金额误差1分、日期±3天、描述相似度>80%
+    fn default() -> Self {
+        Self::new(0.01, 3, 0.8)
+    }
+}
+
+fn to_cents(amount: f64) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+/// 规范化文本：转小写、去除标点、合并空白
+pub fn normalize_text(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 计算字符串相似度 (基于Levenshtein距离，按字符计算)。任一方为空时不构成相似证据，返回0
+pub fn string_similarity(s1: &str, s2: &str) -> f64 {
+    let a: Vec<char> = s1.chars().collect();
+    let b: Vec<char> = s2.chars().collect();
+
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let distance = levenshtein_distance(&a, &b);
+    1.0 - distance as f64 / a.len().max(b.len()) as f64
+}
+
+fn levenshtein_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j + 1] + 1).min(curr[j] + 1).min(prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TransactionBuilder;
+    use chrono::{TimeZone, Utc};
+
+    fn transaction(id: &str, amount: f64, day: u32, description: &str) -> Transaction {
+        TransactionBuilder::new("expense", amount)
+            .id(id)
+            .category("dining")
+            .description(description)
+            .date(Utc.with_ymd_and_hms(2024, 3, day, 10, 0, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn test_canonical_hash_ignores_formatting() {
+        let a = transaction("1", 38.5, 5, "Starbucks Coffee #12");
+        let mut b = transaction("2", 38.5, 5, "  starbucks   coffee 12 ");
+        b.transaction_date += chrono::Duration::hours(3);
+
+        assert_eq!(DuplicateDetector::canonical_hash(&a), DuplicateDetector::canonical_hash(&b));
+
+        let c = transaction("3", 38.51, 5, "Starbucks Coffee #12");
+        assert_ne!(DuplicateDetector::canonical_hash(&a), DuplicateDetector::canonical_hash(&c));
+    }
+
+    #[test]
+    fn test_canonical_hash_distinguishes_transfers() {
+        let mut a = transaction("1", 500.0, 5, "");
+        a.transaction_type = "transfer".to_string();
+        a.to_account_id = Some("savings".to_string());
+        let mut b = a.clone();
+        b.to_account_id = Some("broker".to_string());
+        assert_ne!(DuplicateDetector::canonical_hash(&a), DuplicateDetector::canonical_hash(&b));
+
+        let mut c = a.clone();
+        c.to_amount = Some(70.0);
+        assert_ne!(DuplicateDetector::canonical_hash(&a), DuplicateDetector::canonical_hash(&c));
+    }
+
+    #[test]
+    fn test_empty_text_is_not_fuzzy_evidence() {
+        let detector = DuplicateDetector::default();
+        let candidate = transaction("new", 20.0, 10, "");
+        let existing = vec![transaction("old", 20.0, 11, "")];
+        assert!(detector.find_matches(&candidate, &existing).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        let detector = DuplicateDetector::default();
+        let candidate = transaction("new", 120.0, 10, "海底捞火锅 朝阳店");
+        let existing = vec![
+            transaction("near", 120.0, 11, "海底捞火锅朝阳店"),
+            transaction("far", 120.0, 20, "海底捞火锅 朝阳店"),
+            transaction("other", 120.0, 10, "地铁充值"),
+            transaction("amount", 125.0, 10, "海底捞火锅 朝阳店"),
+        ];
+
+        let matches = detector.find_matches(&candidate, &existing);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].transaction_id, "near");
+        assert!(matches[0].score > 0.8 && matches[0].score <= 1.0);
+    }
+
+    #[test]
+    fn test_string_similarity() {
+        assert_eq!(string_similarity("abc", "abc"), 1.0);
+        assert_eq!(string_similarity("", "abc"), 0.0);
+        assert_eq!(string_similarity("", ""), 0.0);
+        assert!((string_similarity("kitten", "sitting") - (1.0 - 3.0 / 7.0)).abs() < 1e-9);
+    }
+}
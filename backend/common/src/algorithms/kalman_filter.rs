@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -8,11 +10,14 @@ pub struct RateSource {
     pub noise_variance: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KalmanFilter {
     x: f64,  // 状态估计
     p: f64,  // 估计误差协方差
-    q: f64,  // 过程噪声协方差
+    q: f64,  // 过程噪声协方差 (按时间预测时为每小时的方差)
     r: f64,  // 观测噪声协方差
+    #[serde(default)]
+    last_update: Option<DateTime<Utc>>,  // 最近一次预测到的时间
 }
 
 impl KalmanFilter {
@@ -22,12 +27,44 @@ impl KalmanFilter {
             p: initial_variance,
             q: 0.0001,
             r: 0.01,
+            last_update: None,
+        }
+    }
+
+    /// 指定过程噪声 (每小时方差) 创建滤波器，配合 predict_to 使用
+    pub fn with_process_noise(initial_rate: f64, initial_variance: f64, process_noise: f64) -> Self {
+        Self {
+            q: process_noise,
+            ..Self::new(initial_rate, initial_variance)
         }
     }
     
     pub fn predict(&mut self) {
         self.p += self.q;
     }
+
+    /// 按距上次预测经过的时间预测到 at，过程噪声随时间线性累积；早于上次的时间点不回退
+    pub fn predict_to(&mut self, at: DateTime<Utc>) {
+        self.p = self.variance_at(at);
+        if self.last_update.is_none_or(|last| at > last) {
+            self.last_update = Some(at);
+        }
+    }
+
+    /// 在 at 时刻的估计方差 (不修改状态)
+    pub fn variance_at(&self, at: DateTime<Utc>) -> f64 {
+        match self.last_update {
+            Some(last) if at > last => {
+                let hours = (at - last).num_milliseconds() as f64 / 3_600_000.0;
+                self.p + self.q * hours
+            }
+            _ => self.p,
+        }
+    }
+
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        self.last_update
+    }
     
     pub fn update(&mut self, measurement: f64, measurement_noise: f64) {
         self.r = measurement_noise;
@@ -122,6 +159,28 @@ mod tests {
         let final_estimate = filter.get_estimate();
         assert!(final_estimate > 6.45 && final_estimate < 6.55);
     }
+
+    #[test]
+    fn test_predict_to_scales_with_elapsed_time() {
+        use chrono::TimeZone;
+
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut filter = KalmanFilter::with_process_noise(7.2, 0.001, 0.0002);
+
+        // 首次预测只记录时间
+        filter.predict_to(start);
+        assert_eq!(filter.get_variance(), 0.001);
+
+        filter.predict_to(start + chrono::Duration::hours(5));
+        assert!((filter.get_variance() - 0.002).abs() < 1e-12);
+
+        // 时间倒退时不改变状态
+        filter.predict_to(start);
+        assert!((filter.get_variance() - 0.002).abs() < 1e-12);
+        assert_eq!(filter.last_update(), Some(start + chrono::Duration::hours(5)));
+
+        assert!((filter.variance_at(start + chrono::Duration::hours(15)) - 0.004).abs() < 1e-12);
+    }
     
     #[test]
     fn test_rate_fusion() {
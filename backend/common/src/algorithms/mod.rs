@@ -1,5 +1,11 @@
 pub mod budget_predictor;
+pub mod category_classifier;
+pub mod dedup;
 pub mod kalman_filter;
+pub mod rules;
 
 pub use budget_predictor::{BudgetPredictor, PredictionResult};
+pub use category_classifier::{CategoryClassifier, CategorySuggestion};
+pub use dedup::{string_similarity, DuplicateDetector, DuplicateMatch};
 pub use kalman_filter::{ExchangeRateFusion, KalmanFilter, RateSource};
+pub use rules::{rule_matches, validate_rule, RuleEngine, TEXT_OPERATORS};
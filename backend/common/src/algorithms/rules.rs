@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+
+use crate::constants::category_matches;
+use crate::error::{Error, Result};
+use crate::models::{Category, CategoryRule, RuleActions, RuleConditions, TextMatch, Transaction};
+
+/// 文本条件支持的操作符
+pub const TEXT_OPERATORS: [&str; 4] = ["contains", "equals", "starts_with", "ends_with"];
+
+/// 自动分类规则引擎：按优先级依次匹配，分类和收款方由第一条命中的规则决定，标签累加
+pub struct RuleEngine {
+    rules: Vec<CategoryRule>,
+    /// 未归档分类的引用标识 -> 分类类型
+    category_types: HashMap<String, String>,
+}
+
+impl RuleEngine {
+    /// 只保留启用的规则；动作引用的分类不存在、已归档或与交易类型不符时跳过该动作
+    pub fn new(mut rules: Vec<CategoryRule>, categories: &[Category]) -> Self {
+        rules.retain(|rule| rule.is_active);
+        rules.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.created_at.cmp(&b.created_at)));
+        let category_types = categories
+            .iter()
+            .filter(|c| !c.is_archived)
+            .map(|c| (c.reference_id().to_string(), c.category_type.clone()))
+            .collect();
+        Self { rules, category_types }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[CategoryRule] {
+        &self.rules
+    }
+
+    /// 对交易执行规则，返回实际修改了交易的规则ID；overwrite 为 false 时只填充为空的分类和收款方。
+    /// 拆分交易的分类由拆分行决定，规则不修改
+    pub fn apply(&self, tx: &mut Transaction, overwrite: bool) -> Vec<String> {
+        let split = tx.splits.as_ref().is_some_and(|splits| !splits.is_empty());
+        let mut category_decided = split || (!overwrite && !tx.category_id.is_empty());
+        let mut payee_decided = !overwrite && tx.payee.as_deref().is_some_and(|p| !p.is_empty());
+        let mut touched = Vec::new();
+
+        for rule in &self.rules {
+            if !rule_matches(&rule.conditions, tx) {
+                continue;
+            }
+            let actions = &rule.actions;
+            let mut changed = false;
+
+            if let Some(category_id) = &actions.category_id {
+                if !category_decided && self.usable(category_id, &tx.transaction_type) {
+                    category_decided = true;
+                    let subcategory_id = actions
+                        .subcategory_id
+                        .clone()
+                        .filter(|id| self.usable(id, &tx.transaction_type));
+                    if tx.category_id != *category_id {
+                        tx.category_id = category_id.clone();
+                        tx.subcategory_id = subcategory_id;
+                        changed = true;
+                    } else if subcategory_id.is_some() && tx.subcategory_id != subcategory_id {
+                        tx.subcategory_id = subcategory_id;
+                        changed = true;
+                    }
+                }
+            }
+
+            if let Some(payee) = &actions.payee {
+                if !payee_decided {
+                    payee_decided = true;
+                    if tx.payee.as_deref() != Some(payee.as_str()) {
+                        tx.payee = Some(payee.clone());
+                        changed = true;
+                    }
+                }
+            }
+
+            for tag in actions.tags.iter().flatten() {
+                let tags = tx.tags.get_or_insert_with(Vec::new);
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    tags.push(tag.clone());
+                    changed = true;
+                }
+            }
+
+            if changed {
+                touched.push(rule.id.clone());
+            }
+        }
+        touched
+    }
+
+    fn usable(&self, category_id: &str, transaction_type: &str) -> bool {
+        self.category_types
+            .get(category_id)
+            .is_some_and(|category_type| category_matches(category_type, transaction_type))
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.trim().to_lowercase()
+}
+
+fn text_matches(condition: &TextMatch, text: &str) -> bool {
+    let (text, value) = (normalize(text), normalize(&condition.value));
+    match condition.operator.as_str() {
+        "contains" => text.contains(&value),
+        "equals" => text == value,
+        "starts_with" => text.starts_with(&value),
+        "ends_with" => text.ends_with(&value),
+        _ => false,
+    }
+}
+
+/// 判断交易是否满足规则的全部条件
+pub fn rule_matches(conditions: &RuleConditions, tx: &Transaction) -> bool {
+    if conditions.transaction_type.as_deref().is_some_and(|t| t != tx.transaction_type) {
+        return false;
+    }
+    if conditions.description.as_ref().is_some_and(|m| !text_matches(m, &tx.description)) {
+        return false;
+    }
+    if let Some(condition) = &conditions.payee {
+        if !tx.payee.as_deref().is_some_and(|payee| text_matches(condition, payee)) {
+            return false;
+        }
+    }
+    if conditions.amount_min.is_some_and(|min| tx.amount < min)
+        || conditions.amount_max.is_some_and(|max| tx.amount > max)
+    {
+        return false;
+    }
+    if conditions.account_ids.as_ref().is_some_and(|ids| !ids.contains(&tx.account_id)) {
+        return false;
+    }
+    if let Some(tags) = &conditions.tags {
+        let has_tag = tx
+            .tags
+            .iter()
+            .flatten()
+            .any(|t| tags.iter().any(|tag| t.eq_ignore_ascii_case(tag)));
+        if !has_tag {
+            return false;
+        }
+    }
+    true
+}
+
+/// 校验规则定义：至少一个条件和一个动作，文本条件的操作符和取值合法，金额区间有效
+pub fn validate_rule(conditions: &RuleConditions, actions: &RuleActions) -> Result<()> {
+    let has_condition = conditions.transaction_type.is_some()
+        || conditions.description.is_some()
+        || conditions.payee.is_some()
+        || conditions.amount_min.is_some()
+        || conditions.amount_max.is_some()
+        || conditions.account_ids.as_ref().is_some_and(|ids| !ids.is_empty())
+        || conditions.tags.as_ref().is_some_and(|tags| !tags.is_empty());
+    if !has_condition {
+        return Err(Error::Validation("Rule needs at least one condition".to_string()));
+    }
+
+    if let Some(transaction_type) = &conditions.transaction_type {
+        if !["expense", "income", "transfer"].contains(&transaction_type.as_str()) {
+            return Err(Error::Validation(format!("Unknown transaction_type: {}", transaction_type)));
+        }
+    }
+    for condition in [&conditions.description, &conditions.payee].into_iter().flatten() {
+        if !TEXT_OPERATORS.contains(&condition.operator.as_str()) {
+            return Err(Error::Validation(format!(
+                "operator must be one of: {}",
+                TEXT_OPERATORS.join(", ")
+            )));
+        }
+        if condition.value.trim().is_empty() {
+            return Err(Error::Validation("Text condition value cannot be empty".to_string()));
+        }
+    }
+    if let (Some(min), Some(max)) = (conditions.amount_min, conditions.amount_max) {
+        if min > max {
+            return Err(Error::Validation("amount_min cannot exceed amount_max".to_string()));
+        }
+    }
+
+    let has_action = actions.category_id.is_some()
+        || actions.subcategory_id.is_some()
+        || actions.tags.as_ref().is_some_and(|tags| !tags.is_empty())
+        || actions.payee.as_deref().is_some_and(|p| !p.trim().is_empty());
+    if !has_action {
+        return Err(Error::Validation("Rule needs at least one action".to_string()));
+    }
+    if actions.subcategory_id.is_some() && actions.category_id.is_none() {
+        return Err(Error::Validation("subcategory_id requires category_id".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TransactionSplit;
+    use crate::test_util::TransactionBuilder;
+    use chrono::{TimeZone, Utc};
+
+    fn transaction(transaction_type: &str, amount: f64, description: &str) -> Transaction {
+        TransactionBuilder::new(transaction_type, amount)
+            .id("tx")
+            .category("")
+            .description(description)
+            .date(Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap())
+            .build()
+    }
+
+    fn category(key: &str, category_type: &str, archived: bool) -> Category {
+        let now = Utc::now();
+        Category {
+            id: Some(format!("id-{}", key)),
+            user_id: Some("user".to_string()),
+            key: Some(key.to_string()),
+            name: key.to_string(),
+            category_type: category_type.to_string(),
+            icon: "tag".to_string(),
+            color: "#1890ff".to_string(),
+            parent_id: None,
+            order: 0,
+            is_system: true,
+            is_archived: archived,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn rule(id: &str, priority: i32, conditions: RuleConditions, actions: RuleActions) -> CategoryRule {
+        let now = Utc::now();
+        CategoryRule {
+            id: id.to_string(),
+            user_id: "user".to_string(),
+            name: id.to_string(),
+            priority,
+            is_active: true,
+            conditions,
+            actions,
+            match_count: 0,
+            last_matched_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn contains(value: &str) -> Option<TextMatch> {
+        Some(TextMatch {
+            operator: "contains".to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    fn set_category(category_id: &str) -> RuleActions {
+        RuleActions {
+            category_id: Some(category_id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn categories() -> Vec<Category> {
+        vec![
+            category("dining", "expense", false),
+            category("transport", "expense", false),
+            category("salary", "income", false),
+            category("housing", "expense", true),
+        ]
+    }
+
+    #[test]
+    fn test_conditions_must_all_match() {
+        let conditions = RuleConditions {
+            description: contains("STARBUCKS"),
+            amount_min: Some(10.0),
+            amount_max: Some(100.0),
+            account_ids: Some(vec!["acc".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(rule_matches(&conditions, &transaction("expense", 35.0, "Starbucks Coffee #123")));
+        assert!(rule_matches(&conditions, &transaction("expense", 100.0, "starbucks")));
+        assert!(!rule_matches(&conditions, &transaction("expense", 120.0, "Starbucks")));
+        assert!(!rule_matches(&conditions, &transaction("expense", 35.0, "Costa")));
+
+        let mut other_account = transaction("expense", 35.0, "Starbucks");
+        other_account.account_id = "card".to_string();
+        assert!(!rule_matches(&conditions, &other_account));
+
+        let payee = RuleConditions {
+            payee: Some(TextMatch {
+                operator: "starts_with".to_string(),
+                value: "didi".to_string(),
+            }),
+            ..Default::default()
+        };
+        let mut ride = transaction("expense", 20.0, "");
+        assert!(!rule_matches(&payee, &ride));
+        ride.payee = Some("DiDi Chuxing".to_string());
+        assert!(rule_matches(&payee, &ride));
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides_category_and_tags_accumulate() {
+        let engine = RuleEngine::new(
+            vec![
+                rule(
+                    "late",
+                    20,
+                    RuleConditions { description: contains("taxi"), ..Default::default() },
+                    RuleActions {
+                        category_id: Some("dining".to_string()),
+                        tags: Some(vec!["commute".to_string()]),
+                        ..Default::default()
+                    },
+                ),
+                rule(
+                    "early",
+                    10,
+                    RuleConditions { description: contains("taxi"), ..Default::default() },
+                    RuleActions {
+                        category_id: Some("transport".to_string()),
+                        tags: Some(vec!["work".to_string()]),
+                        payee: Some("Taxi Co".to_string()),
+                        ..Default::default()
+                    },
+                ),
+            ],
+            &categories(),
+        );
+
+        let mut tx = transaction("expense", 30.0, "Taxi to airport");
+        let touched = engine.apply(&mut tx, false);
+
+        assert_eq!(tx.category_id, "transport");
+        assert_eq!(tx.payee.as_deref(), Some("Taxi Co"));
+        assert_eq!(tx.tags, Some(vec!["work".to_string(), "commute".to_string()]));
+        assert_eq!(touched, vec!["early".to_string(), "late".to_string()]);
+    }
+
+    #[test]
+    fn test_explicit_values_kept_unless_overwriting() {
+        let engine = RuleEngine::new(
+            vec![rule(
+                "coffee",
+                0,
+                RuleConditions { description: contains("coffee"), ..Default::default() },
+                RuleActions {
+                    category_id: Some("dining".to_string()),
+                    payee: Some("Cafe".to_string()),
+                    ..Default::default()
+                },
+            )],
+            &categories(),
+        );
+
+        let mut tx = transaction("expense", 8.0, "coffee beans");
+        tx.category_id = "transport".to_string();
+        tx.payee = Some("Market".to_string());
+        assert!(engine.apply(&mut tx, false).is_empty());
+        assert_eq!(tx.category_id, "transport");
+
+        assert_eq!(engine.apply(&mut tx, true), vec!["coffee".to_string()]);
+        assert_eq!(tx.category_id, "dining");
+        assert_eq!(tx.payee.as_deref(), Some("Cafe"));
+
+        // 已经符合规则时不计为修改
+        assert!(engine.apply(&mut tx, true).is_empty());
+
+        let mut split = transaction("expense", 8.0, "coffee and snacks");
+        split.category_id = "shopping".to_string();
+        split.splits = Some(vec![
+            TransactionSplit { category_id: "shopping".to_string(), amount: 5.0, note: None },
+            TransactionSplit { category_id: "dining".to_string(), amount: 3.0, note: None },
+        ]);
+        assert_eq!(engine.apply(&mut split, true), vec!["coffee".to_string()]);
+        assert_eq!(split.category_id, "shopping");
+        assert_eq!(split.payee.as_deref(), Some("Cafe"));
+    }
+
+    #[test]
+    fn test_unusable_categories_are_skipped() {
+        let engine = RuleEngine::new(
+            vec![
+                rule(
+                    "archived",
+                    0,
+                    RuleConditions { amount_min: Some(0.0), ..Default::default() },
+                    set_category("housing"),
+                ),
+                rule(
+                    "wrong_type",
+                    1,
+                    RuleConditions { amount_min: Some(0.0), ..Default::default() },
+                    set_category("dining"),
+                ),
+                rule(
+                    "income",
+                    2,
+                    RuleConditions { amount_min: Some(0.0), ..Default::default() },
+                    set_category("salary"),
+                ),
+            ],
+            &categories(),
+        );
+
+        let mut tx = transaction("income", 5000.0, "payroll");
+        assert_eq!(engine.apply(&mut tx, false), vec!["income".to_string()]);
+        assert_eq!(tx.category_id, "salary");
+
+        let mut inactive = rule("off", 0, RuleConditions::default(), set_category("dining"));
+        inactive.is_active = false;
+        assert!(RuleEngine::new(vec![inactive], &categories()).is_empty());
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        let conditions = RuleConditions { description: contains("coffee"), ..Default::default() };
+        assert!(validate_rule(&conditions, &set_category("dining")).is_ok());
+        assert!(validate_rule(&RuleConditions::default(), &set_category("dining")).is_err());
+        assert!(validate_rule(&conditions, &RuleActions::default()).is_err());
+
+        let bad_operator = RuleConditions {
+            description: Some(TextMatch {
+                operator: "regex".to_string(),
+                value: ".*".to_string(),
+            }),
+            ..Default::default()
+        };
+        assert!(validate_rule(&bad_operator, &set_category("dining")).is_err());
+
+        let bad_range = RuleConditions {
+            amount_min: Some(50.0),
+            amount_max: Some(10.0),
+            ..Default::default()
+        };
+        assert!(validate_rule(&bad_range, &set_category("dining")).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+// JWT 密钥环 - 启动时加载一次签名密钥和所有可用于验签的公钥，按 kid 选择密钥
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+/// HS256 共享密钥的 kid；不带 kid 的旧令牌也按此密钥验签
+pub const SHARED_SECRET_KID: &str = "shared";
+
+/// Ed25519 SubjectPublicKeyInfo 的固定 DER 前缀，其后为 32 字节公钥
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// 公钥参数，均为 base64url 编码
+#[derive(Debug, Clone, PartialEq)]
+enum PublicKey {
+    Rsa { n: String, e: String },
+    Ed25519 { x: String },
+}
+
+impl PublicKey {
+    fn from_rsa(key: &RsaPublicKey) -> Self {
+        Self::Rsa {
+            n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+        }
+    }
+
+    fn from_ed25519(public: &[u8]) -> Self {
+        Self::Ed25519 {
+            x: URL_SAFE_NO_PAD.encode(public),
+        }
+    }
+
+    /// 从 PEM 中取出公钥部分，私钥 (PKCS#8 / PKCS#1) 和公钥 (SPKI / PKCS#1) 均可
+    fn from_pem(pem: &[u8]) -> Result<Self> {
+        let parsed = pem::parse(pem).map_err(|e| invalid_key(format!("invalid PEM: {}", e)))?;
+        let der = parsed.contents();
+
+        match parsed.tag() {
+            "PRIVATE KEY" => match Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                Ok(pair) => Ok(Self::from_ed25519(pair.public_key().as_ref())),
+                Err(_) => RsaPrivateKey::from_pkcs8_der(der)
+                    .map(|key| Self::from_rsa(&key.to_public_key()))
+                    .map_err(|e| invalid_key(format!("unsupported private key: {}", e))),
+            },
+            "RSA PRIVATE KEY" => RsaPrivateKey::from_pkcs1_der(der)
+                .map(|key| Self::from_rsa(&key.to_public_key()))
+                .map_err(|e| invalid_key(format!("invalid RSA private key: {}", e))),
+            "PUBLIC KEY" => match der.strip_prefix(&ED25519_SPKI_PREFIX[..]) {
+                Some(public) if public.len() == 32 => Ok(Self::from_ed25519(public)),
+                _ => RsaPublicKey::from_public_key_der(der)
+                    .map(|key| Self::from_rsa(&key))
+                    .map_err(|e| invalid_key(format!("unsupported public key: {}", e))),
+            },
+            "RSA PUBLIC KEY" => RsaPublicKey::from_pkcs1_der(der)
+                .map(|key| Self::from_rsa(&key))
+                .map_err(|e| invalid_key(format!("invalid RSA public key: {}", e))),
+            tag => Err(invalid_key(format!("unsupported PEM block: {}", tag))),
+        }
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            Self::Rsa { .. } => Algorithm::RS256,
+            Self::Ed25519 { .. } => Algorithm::EdDSA,
+        }
+    }
+
+    fn decoding_key(&self) -> Result<DecodingKey> {
+        match self {
+            Self::Rsa { n, e } => DecodingKey::from_rsa_components(n, e),
+            Self::Ed25519 { x } => DecodingKey::from_ed_components(x),
+        }
+        .map_err(|e| invalid_key(e.to_string()))
+    }
+
+    fn to_jwk(&self, kid: &str) -> Jwk {
+        let (key_algorithm, algorithm) = match self {
+            Self::Rsa { n, e } => (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: n.clone(),
+                    e: e.clone(),
+                }),
+            ),
+            Self::Ed25519 { x } => (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: x.clone(),
+                }),
+            ),
+        };
+
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+}
+
+fn invalid_key(message: String) -> Error {
+    Error::InternalServer(format!("Invalid JWT key: {}", message))
+}
+
+pub(crate) struct SigningKey {
+    pub(crate) kid: String,
+    pub(crate) algorithm: Algorithm,
+    pub(crate) key: EncodingKey,
+}
+
+pub(crate) struct VerificationKey {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) key: DecodingKey,
+    public: Option<PublicKey>,
+}
+
+/// 一个签名密钥 (仅签发令牌的服务需要) 加上若干验签密钥。
+/// 轮换密钥时先把新公钥加入所有服务的验签列表，再切换签名密钥，旧公钥待旧令牌过期后移除
+#[derive(Default)]
+pub struct KeyRing {
+    signing: Option<SigningKey>,
+    verification: HashMap<String, VerificationKey>,
+}
+
+impl KeyRing {
+    /// 从环境变量加载：
+    /// - `JWT_SIGNING_KEY` / `JWT_SIGNING_KEY_ID`: 签名私钥 PEM 文件 (RSA 或 Ed25519) 及其 kid
+    /// - `JWT_VERIFY_KEYS`: 额外的验签公钥，格式为 `kid=path,kid=path`
+    /// - `JWT_SECRET`: 可选的 HS256 共享密钥，未配置非对称签名密钥时用于签发
+    ///
+    /// 没有任何可用密钥时返回错误，服务应拒绝启动
+    pub fn from_env() -> Result<Self> {
+        let mut ring = Self::default();
+
+        if let Some(path) = env_value("JWT_SIGNING_KEY") {
+            let kid = env_value("JWT_SIGNING_KEY_ID")
+                .ok_or_else(|| invalid_key("JWT_SIGNING_KEY_ID is required with JWT_SIGNING_KEY".to_string()))?;
+            ring.set_signing_pem(&kid, &read_key_file(&path)?)?;
+        }
+
+        if let Some(entries) = env_value("JWT_VERIFY_KEYS") {
+            for entry in entries.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+                let (kid, path) = entry
+                    .split_once('=')
+                    .ok_or_else(|| invalid_key(format!("JWT_VERIFY_KEYS entry must be kid=path: {}", entry)))?;
+                ring.add_verification_pem(kid.trim(), &read_key_file(path.trim())?)?;
+            }
+        }
+
+        if let Some(secret) = env_value("JWT_SECRET") {
+            ring.add_shared_secret(&secret)?;
+        }
+
+        if ring.verification.is_empty() {
+            return Err(invalid_key(
+                "no JWT key configured, set JWT_SIGNING_KEY, JWT_VERIFY_KEYS or JWT_SECRET".to_string(),
+            ));
+        }
+        Ok(ring)
+    }
+
+    /// 仅含 HS256 共享密钥的密钥环
+    pub fn from_secret(secret: &str) -> Result<Self> {
+        let mut ring = Self::default();
+        ring.add_shared_secret(secret)?;
+        Ok(ring)
+    }
+
+    /// 设置签名私钥，其公钥同时加入验签列表
+    pub fn set_signing_pem(&mut self, kid: &str, pem: &[u8]) -> Result<()> {
+        let public = PublicKey::from_pem(pem)?;
+        let algorithm = public.algorithm();
+        let key = match algorithm {
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(pem),
+            _ => EncodingKey::from_rsa_pem(pem),
+        }
+        .map_err(|e| invalid_key(e.to_string()))?;
+
+        self.insert_public(kid, public)?;
+        self.signing = Some(SigningKey {
+            kid: kid.to_string(),
+            algorithm,
+            key,
+        });
+        Ok(())
+    }
+
+    /// 加入一个仅用于验签的公钥
+    pub fn add_verification_pem(&mut self, kid: &str, pem: &[u8]) -> Result<()> {
+        let public = PublicKey::from_pem(pem)?;
+        self.insert_public(kid, public)
+    }
+
+    /// 加入 HS256 共享密钥；没有非对称签名密钥时也用它签发
+    pub fn add_shared_secret(&mut self, secret: &str) -> Result<()> {
+        if secret.is_empty() {
+            return Err(invalid_key("JWT_SECRET must not be empty".to_string()));
+        }
+
+        self.verification.insert(
+            SHARED_SECRET_KID.to_string(),
+            VerificationKey {
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+                public: None,
+            },
+        );
+        if self.signing.is_none() {
+            self.signing = Some(SigningKey {
+                kid: SHARED_SECRET_KID.to_string(),
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(secret.as_bytes()),
+            });
+        }
+        Ok(())
+    }
+
+    fn insert_public(&mut self, kid: &str, public: PublicKey) -> Result<()> {
+        if kid.is_empty() || kid == SHARED_SECRET_KID {
+            return Err(invalid_key(format!("invalid key id: {:?}", kid)));
+        }
+        if let Some(existing) = self.verification.get(kid) {
+            if existing.public.as_ref() != Some(&public) {
+                return Err(invalid_key(format!("key id {} is configured with different keys", kid)));
+            }
+        }
+
+        self.verification.insert(
+            kid.to_string(),
+            VerificationKey {
+                algorithm: public.algorithm(),
+                key: public.decoding_key()?,
+                public: Some(public),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn can_sign(&self) -> bool {
+        self.signing.is_some()
+    }
+
+    pub(crate) fn signing_key(&self) -> Option<&SigningKey> {
+        self.signing.as_ref()
+    }
+
+    /// 按 kid 查找验签密钥，没有 kid 的令牌使用共享密钥
+    pub(crate) fn verification_key(&self, kid: Option<&str>) -> Option<&VerificationKey> {
+        self.verification.get(kid.unwrap_or(SHARED_SECRET_KID))
+    }
+
+    /// 公开的验签密钥集合 (JWKS)，不含 HS256 共享密钥
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self
+            .verification
+            .iter()
+            .filter_map(|(kid, key)| key.public.as_ref().map(|public| public.to_jwk(kid)))
+            .collect();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+        JwkSet { keys }
+    }
+}
+
+fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn read_key_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| invalid_key(format!("failed to read {}: {}", path, e)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+
+    /// 生成 Ed25519 私钥 PEM 及对应的 SPKI 公钥 PEM
+    pub(crate) fn ed25519_pems() -> (Vec<u8>, Vec<u8>) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+        let mut spki = ED25519_SPKI_PREFIX.to_vec();
+        spki.extend_from_slice(pair.public_key().as_ref());
+
+        (
+            pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref().to_vec())).into_bytes(),
+            pem::encode(&pem::Pem::new("PUBLIC KEY", spki)).into_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_private_and_public_pem_yield_same_key() {
+        let (private, public) = ed25519_pems();
+        assert_eq!(
+            PublicKey::from_pem(&private).unwrap(),
+            PublicKey::from_pem(&public).unwrap()
+        );
+
+        let mut ring = KeyRing::default();
+        ring.set_signing_pem("2024-01", &private).unwrap();
+        ring.add_verification_pem("2024-01", &public).unwrap();
+        assert!(ring.add_verification_pem("2024-01", &ed25519_pems().1).is_err());
+    }
+
+    #[test]
+    fn test_jwks_excludes_shared_secret() {
+        let (private, _) = ed25519_pems();
+        let (_, previous) = ed25519_pems();
+
+        let mut ring = KeyRing::from_secret("legacy").unwrap();
+        ring.set_signing_pem("current", &private).unwrap();
+        ring.add_verification_pem("previous", &previous).unwrap();
+
+        let kids: Vec<_> = ring
+            .jwks()
+            .keys
+            .iter()
+            .map(|jwk| jwk.common.key_id.clone().unwrap())
+            .collect();
+        assert_eq!(kids, vec!["current", "previous"]);
+        assert_eq!(ring.signing_key().unwrap().algorithm, Algorithm::EdDSA);
+    }
+}
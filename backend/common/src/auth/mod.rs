@@ -0,0 +1,356 @@
+pub mod keyring;
+
+pub use keyring::*;
+
+use crate::constants::ROLE_ADMIN;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const TOKEN_TYPE_ACCESS: &str = "access";
+pub const TOKEN_TYPE_REFRESH: &str = "refresh";
+pub const TOKEN_TYPE_API: &str = "api";
+
+/// 管理员权限，只授予 admin 角色，API 令牌不能申请
+pub const SCOPE_ADMIN: &str = "admin";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,      // 用户ID
+    pub user_id: String,  // 用户ID
+    pub username: String,
+    pub role: String,
+    pub iat: i64,         // 签发时间
+    pub exp: i64,         // 过期时间
+    /// 没有默认值：旧版本签发的令牌 (含 role 为 refresh 的刷新令牌) 没有 type，解码失败需重新登录
+    #[serde(rename = "type")]
+    pub token_type: String, // access / refresh / api
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>, // 刷新令牌或 API 令牌ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>, // 刷新令牌族ID，同一次登录轮换出的令牌共享
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>, // 为空表示登录会话，拥有该角色的全部权限
+}
+
+impl Claims {
+    /// 是否拥有某项权限；`xxx:write` 隐含 `xxx:read`，admin 权限还要求 admin 角色
+    pub fn has_scope(&self, scope: &str) -> bool {
+        if scope == SCOPE_ADMIN && self.role != ROLE_ADMIN {
+            return false;
+        }
+        if self.scopes.is_empty() {
+            return true;
+        }
+
+        let implied_write = scope
+            .strip_suffix(":read")
+            .map(|resource| format!("{}:write", resource));
+        self.scopes
+            .iter()
+            .any(|granted| granted == scope || Some(granted) == implied_write.as_ref())
+    }
+}
+
+/// 新签发的刷新令牌及其服务端跟踪所需的信息
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub token: String,
+    pub jti: String,
+    pub family: String,
+    pub expires_at: i64,
+}
+
+pub struct JwtManager {
+    keys: KeyRing,
+    access_token_expiry: Duration,
+    refresh_token_expiry: Duration,
+}
+
+impl JwtManager {
+    pub fn new(keys: KeyRing) -> Self {
+        Self {
+            keys,
+            access_token_expiry: Duration::hours(2),
+            refresh_token_expiry: Duration::days(7),
+        }
+    }
+
+    pub fn generate_access_token(
+        &self,
+        user_id: &str,
+        username: &str,
+        role: &str,
+    ) -> Result<String> {
+        let now = Utc::now();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            role: role.to_string(),
+            iat: now.timestamp(),
+            exp: (now + self.access_token_expiry).timestamp(),
+            token_type: TOKEN_TYPE_ACCESS.to_string(),
+            jti: None,
+            family: None,
+            scopes: Vec::new(),
+        };
+
+        self.sign(&claims)
+            .map_err(|e| Error::InternalServer(format!("Failed to generate token: {}", e)))
+    }
+
+    pub fn access_token_expiry(&self) -> Duration {
+        self.access_token_expiry
+    }
+
+    pub fn refresh_token_expiry(&self) -> Duration {
+        self.refresh_token_expiry
+    }
+
+    /// 签发刷新令牌；family 为 None 时开启新的令牌族 (即一次新的登录)
+    pub fn generate_refresh_token(&self, user_id: &str, family: Option<&str>) -> Result<RefreshToken> {
+        let now = Utc::now();
+        let jti = Uuid::new_v4().to_string();
+        let family = family
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let claims = Claims {
+            sub: user_id.to_string(),
+            user_id: user_id.to_string(),
+            username: String::new(),
+            role: String::new(),
+            iat: now.timestamp(),
+            exp: (now + self.refresh_token_expiry).timestamp(),
+            token_type: TOKEN_TYPE_REFRESH.to_string(),
+            jti: Some(jti.clone()),
+            family: Some(family.clone()),
+            scopes: Vec::new(),
+        };
+
+        let token = self
+            .sign(&claims)
+            .map_err(|e| Error::InternalServer(format!("Failed to generate refresh token: {}", e)))?;
+
+        Ok(RefreshToken {
+            token,
+            jti,
+            family,
+            expires_at: claims.exp,
+        })
+    }
+
+    /// 签发只拥有指定权限的 API 令牌，jti 用于服务端吊销
+    pub fn generate_api_token(
+        &self,
+        user_id: &str,
+        username: &str,
+        jti: &str,
+        scopes: &[String],
+        expires_at: DateTime<Utc>,
+    ) -> Result<String> {
+        if scopes.is_empty() || scopes.iter().any(|scope| scope == SCOPE_ADMIN) {
+            return Err(Error::InvalidInput("API tokens require non-admin scopes".to_string()));
+        }
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            user_id: user_id.to_string(),
+            username: username.to_string(),
+            role: crate::constants::ROLE_USER.to_string(),
+            iat: Utc::now().timestamp(),
+            exp: expires_at.timestamp(),
+            token_type: TOKEN_TYPE_API.to_string(),
+            jti: Some(jti.to_string()),
+            family: None,
+            scopes: scopes.to_vec(),
+        };
+
+        self.sign(&claims)
+            .map_err(|e| Error::InternalServer(format!("Failed to generate API token: {}", e)))
+    }
+
+    pub fn keys(&self) -> &KeyRing {
+        &self.keys
+    }
+
+    /// 用签名密钥签发，并在头部写入 kid
+    fn sign(&self, claims: &Claims) -> Result<String> {
+        let signing = self
+            .keys
+            .signing_key()
+            .ok_or_else(|| Error::InternalServer("No JWT signing key configured".to_string()))?;
+
+        let mut header = Header::new(signing.algorithm);
+        header.kid = Some(signing.kid.clone());
+        encode(&header, claims, &signing.key)
+            .map_err(|e| Error::InternalServer(e.to_string()))
+    }
+
+    pub fn verify_token(&self, token: &str) -> Result<Claims> {
+        let header = decode_header(token)
+            .map_err(|e| Error::Unauthorized(format!("Invalid token: {}", e)))?;
+        let key = self
+            .keys
+            .verification_key(header.kid.as_deref())
+            .ok_or_else(|| Error::Unauthorized("Invalid token: unknown signing key".to_string()))?;
+        // 只接受该密钥对应的算法，防止算法混淆
+        if header.alg != key.algorithm {
+            return Err(Error::Unauthorized("Invalid token: algorithm mismatch".to_string()));
+        }
+
+        decode::<Claims>(token, &key.key, &Validation::new(key.algorithm))
+            .map(|data| data.claims)
+            .map_err(|e| Error::Unauthorized(format!("Invalid token: {}", e)))
+    }
+
+    /// 校验访问令牌或 API 令牌，拒绝刷新令牌
+    pub fn verify_access_token(&self, token: &str) -> Result<Claims> {
+        let claims = self.verify_token(token)?;
+        let is_api_token = claims.token_type == TOKEN_TYPE_API && claims.jti.is_some() && !claims.scopes.is_empty();
+        if (claims.token_type != TOKEN_TYPE_ACCESS && !is_api_token) || claims.role == TOKEN_TYPE_REFRESH {
+            return Err(Error::Unauthorized("Not an access token".to_string()));
+        }
+        Ok(claims)
+    }
+
+    /// 校验刷新令牌，拒绝访问令牌及缺少 jti/family 的旧格式令牌
+    pub fn verify_refresh_token(&self, token: &str) -> Result<Claims> {
+        let claims = self.verify_token(token)?;
+        if claims.token_type != TOKEN_TYPE_REFRESH || claims.jti.is_none() || claims.family.is_none() {
+            return Err(Error::Unauthorized("Not a refresh token".to_string()));
+        }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_and_verify_token() {
+        let manager = JwtManager::new(KeyRing::from_secret("test_secret").unwrap());
+        let token = manager
+            .generate_access_token("user123", "testuser", "user")
+            .unwrap();
+
+        let claims = manager.verify_token(&token).unwrap();
+        assert_eq!(claims.sub, "user123");
+        assert_eq!(claims.username, "testuser");
+        assert_eq!(claims.role, "user");
+    }
+
+    #[test]
+    fn test_token_types_are_not_interchangeable() {
+        let manager = JwtManager::new(KeyRing::from_secret("test_secret").unwrap());
+        let access = manager
+            .generate_access_token("user123", "testuser", "user")
+            .unwrap();
+        let refresh = manager.generate_refresh_token("user123", None).unwrap();
+
+        assert!(manager.verify_refresh_token(&access).is_err());
+        assert!(manager.verify_access_token(&refresh.token).is_err());
+
+        let claims = manager.verify_refresh_token(&refresh.token).unwrap();
+        assert_eq!(claims.jti.as_deref(), Some(refresh.jti.as_str()));
+
+        // 轮换出的令牌沿用同一令牌族
+        let rotated = manager
+            .generate_refresh_token("user123", Some(&refresh.family))
+            .unwrap();
+        assert_eq!(rotated.family, refresh.family);
+        assert_ne!(rotated.jti, refresh.jti);
+    }
+
+    #[test]
+    fn test_legacy_refresh_token_is_rejected() {
+        // 旧版本的刷新令牌：没有 type/jti，role 为 refresh，有效期 7 天
+        #[derive(Serialize)]
+        struct LegacyClaims<'a> {
+            sub: &'a str,
+            user_id: &'a str,
+            username: &'a str,
+            role: &'a str,
+            iat: i64,
+            exp: i64,
+        }
+        let now = Utc::now();
+        let legacy = encode(
+            &Header::default(),
+            &LegacyClaims {
+                sub: "user123",
+                user_id: "user123",
+                username: "",
+                role: "refresh",
+                iat: now.timestamp(),
+                exp: (now + Duration::days(7)).timestamp(),
+            },
+            &jsonwebtoken::EncodingKey::from_secret(b"test_secret"),
+        )
+        .unwrap();
+
+        let manager = JwtManager::new(KeyRing::from_secret("test_secret").unwrap());
+        assert!(manager.verify_token(&legacy).is_err());
+        assert!(manager.verify_access_token(&legacy).is_err());
+        assert!(manager.verify_refresh_token(&legacy).is_err());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let manager = JwtManager::new(KeyRing::from_secret("test_secret").unwrap());
+        let session = manager
+            .verify_access_token(&manager.generate_access_token("user123", "testuser", "user").unwrap())
+            .unwrap();
+        assert!(session.has_scope("transactions:write"));
+        assert!(!session.has_scope(SCOPE_ADMIN));
+
+        let scopes = vec!["reports:read".to_string(), "budgets:write".to_string()];
+        let token = manager
+            .generate_api_token("user123", "testuser", "token1", &scopes, Utc::now() + Duration::days(30))
+            .unwrap();
+        let api = manager.verify_access_token(&token).unwrap();
+        assert!(api.has_scope("reports:read"));
+        assert!(api.has_scope("budgets:read"));
+        assert!(api.has_scope("budgets:write"));
+        assert!(!api.has_scope("transactions:read"));
+        assert!(!api.has_scope("reports:write"));
+
+        assert!(manager
+            .generate_api_token("user123", "testuser", "token2", &[SCOPE_ADMIN.to_string()], Utc::now())
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_across_key_rotation() {
+        let (old_private, old_public) = keyring::tests::ed25519_pems();
+        let (new_private, _) = keyring::tests::ed25519_pems();
+
+        let mut old_keys = KeyRing::default();
+        old_keys.set_signing_pem("old", &old_private).unwrap();
+        let old_token = JwtManager::new(old_keys)
+            .generate_access_token("user123", "testuser", "user")
+            .unwrap();
+
+        // 切换到新签名密钥后，旧公钥仍在验签列表中
+        let mut keys = KeyRing::default();
+        keys.set_signing_pem("new", &new_private).unwrap();
+        keys.add_verification_pem("old", &old_public).unwrap();
+        let manager = JwtManager::new(keys);
+
+        let new_token = manager
+            .generate_access_token("user123", "testuser", "user")
+            .unwrap();
+        assert_eq!(decode_header(&new_token).unwrap().kid.as_deref(), Some("new"));
+        assert_eq!(manager.verify_access_token(&old_token).unwrap().sub, "user123");
+        assert_eq!(manager.verify_access_token(&new_token).unwrap().sub, "user123");
+
+        // 共享密钥签发的令牌不能被非对称密钥环接受
+        let legacy = JwtManager::new(KeyRing::from_secret("test_secret").unwrap())
+            .generate_access_token("user123", "testuser", "user")
+            .unwrap();
+        assert!(manager.verify_token(&legacy).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+// 日历 - 按用户时区计算日/周/月/年的边界与分桶键，报表和预算周期共用
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use mongodb::Database;
+
+use crate::{user_timezone, Error, Result, Tz};
+
+/// 日历周期；周从周一开始 (ISO 8601)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// 解析报表的 interval 参数
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            _ => Err(Error::InvalidInput("interval must be day, week, month or year".to_string())),
+        }
+    }
+
+    /// 预算的 budget_type (daily/weekly/monthly/yearly)
+    pub fn from_budget_type(value: &str) -> Option<Self> {
+        match value {
+            "daily" => Some(Self::Day),
+            "weekly" => Some(Self::Week),
+            "monthly" => Some(Self::Month),
+            "yearly" => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    /// 分桶键的格式，同时用于 MongoDB 的 $dateToString
+    pub fn key_format(&self) -> &'static str {
+        match self {
+            Self::Day => "%Y-%m-%d",
+            Self::Week => "%G-W%V",
+            Self::Month => "%Y-%m",
+            Self::Year => "%Y",
+        }
+    }
+
+    /// date 所在周期的第一天
+    pub fn first_day(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month => date.with_day(1).unwrap(),
+            Self::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+        }
+    }
+
+    /// 下一个周期的第一天
+    pub fn next_first_day(&self, date: NaiveDate) -> NaiveDate {
+        let first = self.first_day(date);
+        match self {
+            Self::Day => first + Duration::days(1),
+            Self::Week => first + Duration::days(7),
+            Self::Month => first.checked_add_months(chrono::Months::new(1)).unwrap(),
+            Self::Year => NaiveDate::from_ymd_opt(first.year() + 1, 1, 1).unwrap(),
+        }
+    }
+}
+
+/// 某个时区下的日历
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    tz: Tz,
+}
+
+impl Calendar {
+    pub fn new(tz: Tz) -> Self {
+        Self { tz }
+    }
+
+    pub fn utc() -> Self {
+        Self::new(Tz::UTC)
+    }
+
+    /// 用户设置中时区的日历
+    pub async fn for_user(db: &Database, user_id: &str) -> Result<Self> {
+        Ok(Self::new(user_timezone(db, user_id).await?))
+    }
+
+    pub fn tz(&self) -> Tz {
+        self.tz
+    }
+
+    /// 该时刻在本地的日期
+    pub fn local_date(&self, time: DateTime<Utc>) -> NaiveDate {
+        time.with_timezone(&self.tz).date_naive()
+    }
+
+    /// 本地日期的零点；零点恰逢夏令时开始而不存在时取当天最早的时刻
+    pub fn start_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        (0..24 * 60)
+            .find_map(|minute| self.tz.from_local_datetime(&(midnight + Duration::minutes(minute))).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+    }
+
+    /// 本地挂钟时间对应的时刻；回拨重复的时间取较早的一次，夏令时跳过的时间按跳变后的时刻解释
+    pub fn from_local(&self, time: NaiveDateTime) -> DateTime<Utc> {
+        self.tz
+            .from_local_datetime(&time)
+            .earliest()
+            .or_else(|| self.tz.from_local_datetime(&(time + Duration::hours(1))).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&time))
+    }
+
+    /// 包含 time 的周期 [开始, 结束)
+    pub fn period_range(&self, period: Period, time: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let date = self.local_date(time);
+        (
+            self.start_of_day(period.first_day(date)),
+            self.start_of_day(period.next_first_day(date)),
+        )
+    }
+
+    /// 时刻所在周期的分桶键
+    pub fn bucket_key(&self, period: Period, time: DateTime<Utc>) -> String {
+        self.local_date(time).format(period.key_format()).to_string()
+    }
+
+    /// 解析查询参数中的时间：RFC3339 时刻，或 YYYY-MM-DD 表示的本地日期；
+    /// 日期作为区间终点时取次日零点 (开区间)，使该日整天都包含在内
+    pub fn parse_bound(&self, value: &str, is_end: bool) -> Result<DateTime<Utc>> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+            return Ok(time.with_timezone(&Utc));
+        }
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| Error::InvalidInput(format!("Invalid date: {}", value)))?;
+        Ok(self.start_of_day(if is_end { date + Duration::days(1) } else { date }))
+    }
+
+    /// 把时刻换成以 UTC 表示的本地挂钟时间，供只按自然日计算的算法 (如预算预测) 使用
+    pub fn wall_clock(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&time.with_timezone(&self.tz).naive_local())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_york() -> Calendar {
+        Calendar::new(Tz::America__New_York)
+    }
+
+    fn shanghai() -> Calendar {
+        Calendar::new(Tz::Asia__Shanghai)
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_late_night_spending_lands_on_local_day_and_month() {
+        let calendar = shanghai();
+        // 北京时间 2024-02-01 00:30
+        let time = utc(2024, 1, 31, 16, 30);
+        assert_eq!(calendar.bucket_key(Period::Day, time), "2024-02-01");
+        assert_eq!(calendar.bucket_key(Period::Month, time), "2024-02");
+        assert_eq!(calendar.period_range(Period::Month, time), (utc(2024, 1, 31, 16, 0), utc(2024, 2, 29, 16, 0)));
+        assert_eq!(calendar.period_range(Period::Year, time), (utc(2023, 12, 31, 16, 0), utc(2024, 12, 31, 16, 0)));
+    }
+
+    #[test]
+    fn test_days_across_dst_transitions() {
+        let calendar = new_york();
+
+        // 夏令时开始的那天只有 23 小时
+        let (start, end) = calendar.period_range(Period::Day, utc(2024, 3, 10, 12, 0));
+        assert_eq!(start, utc(2024, 3, 10, 5, 0));
+        assert_eq!(end, utc(2024, 3, 11, 4, 0));
+        assert_eq!(end - start, Duration::hours(23));
+
+        // 夏令时结束的那天有 25 小时
+        let (start, end) = calendar.period_range(Period::Day, utc(2024, 11, 3, 12, 0));
+        assert_eq!(start, utc(2024, 11, 3, 4, 0));
+        assert_eq!(end - start, Duration::hours(25));
+
+        // 回拨后重复的 01:30 EST 仍属于 11 月 3 日
+        assert_eq!(calendar.bucket_key(Period::Day, utc(2024, 11, 3, 6, 30)), "2024-11-03");
+    }
+
+    #[test]
+    fn test_from_local_wall_clock() {
+        let calendar = new_york();
+        let local = |d: u32, h: u32, min: u32| NaiveDate::from_ymd_opt(2024, 3, d).unwrap().and_hms_opt(h, min, 0).unwrap();
+        assert_eq!(calendar.from_local(local(1, 9, 0)), utc(2024, 3, 1, 14, 0));
+        // 02:30 在夏令时开始当天不存在，按 03:30 EDT 解释
+        assert_eq!(calendar.from_local(local(10, 2, 30)), utc(2024, 3, 10, 7, 30));
+        assert_eq!(shanghai().from_local(local(1, 0, 0)), utc(2024, 2, 29, 16, 0));
+    }
+
+    #[test]
+    fn test_month_and_week_spanning_dst() {
+        let calendar = new_york();
+        let (start, end) = calendar.period_range(Period::Month, utc(2024, 3, 20, 12, 0));
+        assert_eq!(start, utc(2024, 3, 1, 5, 0));
+        assert_eq!(end, utc(2024, 4, 1, 4, 0));
+
+        // 2024-11-03 是周日，所在 ISO 周从 10-28 (EDT) 到 11-04 (EST)
+        let (start, end) = calendar.period_range(Period::Week, utc(2024, 11, 3, 12, 0));
+        assert_eq!(start, utc(2024, 10, 28, 4, 0));
+        assert_eq!(end, utc(2024, 11, 4, 5, 0));
+        assert_eq!(calendar.bucket_key(Period::Week, utc(2024, 11, 3, 12, 0)), "2024-W44");
+    }
+
+    #[test]
+    fn test_midnight_inside_dst_gap() {
+        // 智利在周六 24:00 (即周日零点) 切换夏令时，当天零点不存在
+        let calendar = Calendar::new(Tz::America__Santiago);
+        let day = NaiveDate::from_ymd_opt(2024, 9, 8).unwrap();
+        assert_eq!(calendar.start_of_day(day), utc(2024, 9, 8, 4, 0));
+        assert_eq!(utc(2024, 9, 8, 4, 0).with_timezone(&calendar.tz()).naive_local(), day.and_hms_opt(1, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        let calendar = shanghai();
+        assert_eq!(calendar.parse_bound("2024-01-30", false).unwrap(), utc(2024, 1, 29, 16, 0));
+        assert_eq!(calendar.parse_bound("2024-02-02", true).unwrap(), utc(2024, 2, 2, 16, 0));
+
+        assert_eq!(calendar.parse_bound("2024-02-01T00:00:00Z", true).unwrap(), utc(2024, 2, 1, 0, 0));
+        assert!(calendar.parse_bound("02/01/2024", false).is_err());
+    }
+
+    #[test]
+    fn test_period_boundaries() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        assert_eq!(Period::Week.first_day(date), NaiveDate::from_ymd_opt(2024, 12, 30).unwrap());
+        assert_eq!(Period::Month.next_first_day(date), NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        assert_eq!(Period::Year.next_first_day(date), NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        assert_eq!(Period::from_budget_type("weekly"), Some(Period::Week));
+        assert_eq!(Period::from_budget_type("custom"), None);
+        assert!(Period::parse("quarter").is_err());
+    }
+}
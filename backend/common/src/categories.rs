@@ -0,0 +1,151 @@
+// 分类 - 为用户预置系统分类文档，以及按引用标识查找分类
+use chrono::Utc;
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Document},
+    options::UpdateOptions,
+    Database,
+};
+
+use crate::{category_matches, is_duplicate_key, Category, Error, Result, Transaction, TransactionSplit, SYSTEM_CATEGORIES};
+
+/// 按引用标识 (系统分类的 key 或自定义分类的 _id) 匹配用户分类的过滤条件
+pub fn reference_filter(user_id: &str, reference: &str) -> Document {
+    doc! {
+        "user_id": user_id,
+        "$or": [{ "key": reference }, { "_id": reference }],
+    }
+}
+
+/// 为用户补齐缺少的系统分类；已存在的 (可能被用户改名或归档) 保持不变，可重复调用
+pub async fn seed_system_categories(db: &Database, user_id: &str) -> Result<()> {
+    let collection = db.collection::<Category>("categories");
+    let existing = collection
+        .count_documents(doc! { "user_id": user_id, "is_system": true }, None)
+        .await?;
+    if existing >= SYSTEM_CATEGORIES.len() as u64 {
+        return Ok(());
+    }
+
+    let now = bson::to_bson(&Utc::now()).unwrap();
+    for (order, system) in SYSTEM_CATEGORIES.iter().enumerate() {
+        let result = collection
+            .update_one(
+                doc! { "user_id": user_id, "key": system.key },
+                doc! { "$setOnInsert": {
+                    "_id": ObjectId::new().to_hex(),
+                    "name": system.name,
+                    "category_type": system.category_type,
+                    "icon": system.icon,
+                    "color": system.color,
+                    "order": order as i32,
+                    "is_system": true,
+                    "is_archived": false,
+                    "created_at": now.clone(),
+                    "updated_at": now.clone(),
+                } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await;
+        // 并发预置时另一方已插入同一分类
+        match result {
+            Err(e) if !is_duplicate_key(&e) => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn find_category(db: &Database, user_id: &str, reference: &str) -> Result<Option<Category>> {
+    Ok(db
+        .collection::<Category>("categories")
+        .find_one(reference_filter(user_id, reference), None)
+        .await?)
+}
+
+/// 校验交易引用的分类存在、未归档且类型与交易类型相符
+pub async fn ensure_transaction_category(
+    db: &Database,
+    user_id: &str,
+    category_id: &str,
+    transaction_type: &str,
+) -> Result<Category> {
+    seed_system_categories(db, user_id).await?;
+    let category = find_category(db, user_id, category_id)
+        .await?
+        .ok_or_else(|| Error::Validation(format!("Unknown category: {}", category_id)))?;
+    if category.is_archived {
+        return Err(Error::Validation(format!("Category {} is archived", category.name)));
+    }
+    if !category_matches(&category.category_type, transaction_type) {
+        return Err(Error::Validation(format!(
+            "Category {} is for {} transactions, not {}",
+            category.name, category.category_type, transaction_type
+        )));
+    }
+    Ok(category)
+}
+
+/// 校验拆分行：至少两行、每行金额为正、合计 (按分计) 等于交易金额；返回金额最大的一行的分类
+pub fn check_splits(amount: f64, splits: &[TransactionSplit]) -> Result<&str> {
+    if splits.len() < 2 {
+        return Err(Error::Validation("A split transaction needs at least two lines".to_string()));
+    }
+    if splits.iter().any(|split| split.amount <= 0.0) {
+        return Err(Error::Validation("Split amounts must be positive".to_string()));
+    }
+    let cents = |amount: f64| (amount * 100.0).round() as i64;
+    let total: i64 = splits.iter().map(|split| cents(split.amount)).sum();
+    if total != cents(amount) {
+        return Err(Error::Validation(format!(
+            "Split amounts add up to {:.2}, expected {:.2}",
+            total as f64 / 100.0,
+            amount
+        )));
+    }
+    let primary = splits
+        .iter()
+        .reduce(|best, split| if split.amount > best.amount { split } else { best })
+        .unwrap();
+    Ok(&primary.category_id)
+}
+
+/// 校验拆分交易：只有收支交易可以拆分，各行的分类须有效；交易的 category_id 设为金额最大的拆分行的分类。
+/// 未拆分的交易不做处理
+pub async fn ensure_transaction_splits(db: &Database, transaction: &mut Transaction) -> Result<()> {
+    let Some(splits) = transaction.splits.as_ref().filter(|splits| !splits.is_empty()) else {
+        transaction.splits = None;
+        return Ok(());
+    };
+    if transaction.transaction_type != "expense" && transaction.transaction_type != "income" {
+        return Err(Error::Validation("Only expense and income transactions can be split".to_string()));
+    }
+    let primary = check_splits(transaction.amount, splits)?.to_string();
+    for split in splits {
+        ensure_transaction_category(db, &transaction.user_id, &split.category_id, &transaction.transaction_type).await?;
+    }
+    transaction.category_id = primary;
+    transaction.subcategory_id = None;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(category_id: &str, amount: f64) -> TransactionSplit {
+        TransactionSplit {
+            category_id: category_id.to_string(),
+            amount,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_check_splits() {
+        let splits = vec![split("dining", 86.4), split("shopping", 33.7)];
+        assert_eq!(check_splits(120.1, &splits).unwrap(), "dining");
+        assert!(check_splits(120.0, &splits).is_err());
+        assert!(check_splits(86.4, &splits[..1]).is_err());
+        assert!(check_splits(86.4, &[split("dining", 90.0), split("shopping", -3.6)]).is_err());
+    }
+}
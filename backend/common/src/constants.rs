@@ -1,27 +1,52 @@
 use std::collections::HashMap;
 use std::sync::OnceLock;
 
+/// 分类类型；adjustment 仅用于对账产生的余额调整
+pub const CATEGORY_TYPE_EXPENSE: &str = "expense";
+pub const CATEGORY_TYPE_INCOME: &str = "income";
+pub const CATEGORY_TYPE_ADJUSTMENT: &str = "adjustment";
+/// 对账产生的余额调整交易使用的系统分类
+pub const ADJUSTMENT_CATEGORY_KEY: &str = "adjustment";
+
+/// 为每个用户预置的系统分类
+pub struct SystemCategory {
+    /// 固定标识，交易和预算以它引用系统分类
+    pub key: &'static str,
+    pub name: &'static str,
+    pub category_type: &'static str,
+    pub icon: &'static str,
+    pub color: &'static str,
+}
+
+pub const SYSTEM_CATEGORIES: &[SystemCategory] = &[
+    SystemCategory { key: "shopping", name: "购物", category_type: "expense", icon: "shopping", color: "#f5222d" },
+    SystemCategory { key: "transport", name: "交通", category_type: "expense", icon: "car", color: "#fa8c16" },
+    SystemCategory { key: "dining", name: "餐饮", category_type: "expense", icon: "coffee", color: "#faad14" },
+    SystemCategory { key: "entertainment", name: "娱乐", category_type: "expense", icon: "smile", color: "#eb2f96" },
+    SystemCategory { key: "housing", name: "居住", category_type: "expense", icon: "home", color: "#722ed1" },
+    SystemCategory { key: "healthcare", name: "医疗", category_type: "expense", icon: "medicine-box", color: "#13c2c2" },
+    SystemCategory { key: "education", name: "教育", category_type: "expense", icon: "book", color: "#2f54eb" },
+    SystemCategory { key: "utilities", name: "水电煤", category_type: "expense", icon: "thunderbolt", color: "#a0d911" },
+    SystemCategory { key: "communication", name: "通讯", category_type: "expense", icon: "phone", color: "#1890ff" },
+    SystemCategory { key: "clothing", name: "服饰", category_type: "expense", icon: "skin", color: "#eb2f96" },
+    SystemCategory { key: "other_expense", name: "其他支出", category_type: "expense", icon: "ellipsis", color: "#8c8c8c" },
+    SystemCategory { key: "salary", name: "工资", category_type: "income", icon: "wallet", color: "#52c41a" },
+    SystemCategory { key: "bonus", name: "奖金", category_type: "income", icon: "gift", color: "#52c41a" },
+    SystemCategory { key: "investment", name: "投资收益", category_type: "income", icon: "stock", color: "#52c41a" },
+    SystemCategory { key: "gift", name: "礼金", category_type: "income", icon: "red-envelope", color: "#52c41a" },
+    SystemCategory { key: "refund", name: "退款", category_type: "income", icon: "rollback", color: "#52c41a" },
+    SystemCategory { key: "other_income", name: "其他收入", category_type: "income", icon: "ellipsis", color: "#8c8c8c" },
+    SystemCategory { key: ADJUSTMENT_CATEGORY_KEY, name: "余额调整", category_type: "adjustment", icon: "swap", color: "#8c8c8c" },
+];
+
 pub fn get_system_categories() -> &'static HashMap<&'static str, &'static str> {
     static CATEGORIES: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
-    CATEGORIES.get_or_init(|| {
-        let mut m = HashMap::new();
-        m.insert("shopping", "购物");
-        m.insert("transport", "交通");
-        m.insert("dining", "餐饮");
-        m.insert("entertainment", "娱乐");
-        m.insert("housing", "居住");
-        m.insert("healthcare", "医疗");
-        m.insert("education", "教育");
-        m.insert("utilities", "水电煤");
-        m.insert("communication", "通讯");
-        m.insert("clothing", "服饰");
-        m.insert("other_expense", "其他支出");
-        m.insert("salary", "工资");
-        m.insert("bonus", "奖金");
-        m.insert("investment", "投资收益");
-        m.insert("other_income", "其他收入");
-        m
-    })
+    CATEGORIES.get_or_init(|| SYSTEM_CATEGORIES.iter().map(|c| (c.key, c.name)).collect())
+}
+
+/// 交易类型可使用的分类类型：收支需类型一致，转账不限，余额调整的收支两个方向都可使用
+pub fn category_matches(category_type: &str, transaction_type: &str) -> bool {
+    category_type == transaction_type || transaction_type == "transfer" || category_type == CATEGORY_TYPE_ADJUSTMENT
 }
 
 pub fn get_category_name(id: &str) -> String {
@@ -30,3 +55,78 @@ pub fn get_category_name(id: &str) -> String {
         .map(|&s| s.to_string())
         .unwrap_or_else(|| id.to_string())
 }
+
+/// 负债类账户 (余额为负表示欠款)
+pub const LIABILITY_ACCOUNT_TYPES: [&str; 2] = ["credit_card", "loan"];
+
+pub fn is_liability_account(account_type: &str) -> bool {
+    LIABILITY_ACCOUNT_TYPES.contains(&account_type)
+}
+
+/// 账户状态；归档的账户保留历史交易，但不计入总资产，也不能再记账
+pub const ACCOUNT_STATUS_ACTIVE: &str = "active";
+pub const ACCOUNT_STATUS_ARCHIVED: &str = "archived";
+
+/// 交易状态；possible_duplicate 由去重检查设置，只能通过复核接口改回
+pub const TRANSACTION_STATUS_CONFIRMED: &str = "confirmed";
+pub const TRANSACTION_STATUS_PENDING: &str = "pending";
+pub const TRANSACTION_STATUS_COMPLETED: &str = "completed";
+pub const TRANSACTION_STATUS_CANCELLED: &str = "cancelled";
+pub const TRANSACTION_STATUS_POSSIBLE_DUPLICATE: &str = "possible_duplicate";
+
+/// 用户可以直接设置的交易状态
+pub fn is_editable_transaction_status(status: &str) -> bool {
+    [
+        TRANSACTION_STATUS_CONFIRMED,
+        TRANSACTION_STATUS_PENDING,
+        TRANSACTION_STATUS_COMPLETED,
+        TRANSACTION_STATUS_CANCELLED,
+    ]
+    .contains(&status)
+}
+
+/// 用户角色
+pub const ROLE_USER: &str = "user";
+pub const ROLE_ADMIN: &str = "admin";
+
+/// 用户状态
+pub const USER_STATUS_ACTIVE: &str = "active";
+pub const USER_STATUS_PENDING: &str = "pending_verification";
+pub const USER_STATUS_DISABLED: &str = "disabled";
+
+/// API 令牌可申请的权限范围；`xxx:write` 隐含 `xxx:read`
+pub const API_TOKEN_SCOPES: [&str; 8] = [
+    "profile:read",
+    "accounts:read",
+    "accounts:write",
+    "transactions:read",
+    "transactions:write",
+    "budgets:read",
+    "budgets:write",
+    "reports:read",
+];
+
+/// 界面支持的语言
+pub const SUPPORTED_LANGUAGES: [&str; 2] = ["zh-CN", "en-US"];
+
+/// 界面主题
+pub const THEMES: [&str; 2] = ["light", "dark"];
+
+/// ISO 4217 现行币种代码
+pub const CURRENCY_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BHD",
+    "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY",
+    "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP",
+    "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT",
+    "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR",
+    "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK",
+    "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES", "VND", "VUV", "WST", "XAF", "XCD", "XOF",
+    "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+pub fn is_currency_code(code: &str) -> bool {
+    CURRENCY_CODES.contains(&code)
+}
@@ -0,0 +1,102 @@
+// 账户余额历史 - 每次余额变动后向 TimescaleDB 的 account_balance_history 超表追加一条快照
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use crate::{Account, Result};
+
+/// 由交易自动产生的快照
+pub const SNAPSHOT_AUTO: &str = "auto";
+/// 由账户创建、手动调整等操作产生的快照
+pub const SNAPSHOT_MANUAL: &str = "manual";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceSnapshot {
+    pub account_id: String,
+    pub time: DateTime<Utc>,
+    pub balance: f64,
+    pub currency: String,
+    pub snapshot_type: String,
+}
+
+impl BalanceSnapshot {
+    /// 以账户当前余额生成快照，时间为余额变动的时刻而不是交易日期：补记的历史交易
+    /// 只影响记账之后的历史余额，之前各时刻的快照不会回溯修改
+    pub fn of(account: &Account, snapshot_type: &str) -> Self {
+        Self {
+            account_id: account.id.clone().unwrap_or_default(),
+            time: account.updated_at,
+            balance: account.current_balance,
+            currency: account.currency.clone(),
+            snapshot_type: snapshot_type.to_string(),
+        }
+    }
+}
+
+/// 批量写入余额快照；同一账户只保留最后一条 (如修改交易时先冲销再应用产生的中间状态)
+pub async fn record_balance_snapshots(pool: &PgPool, snapshots: &[BalanceSnapshot]) -> Result<()> {
+    let mut latest: HashMap<&str, &BalanceSnapshot> = HashMap::new();
+    for snapshot in snapshots {
+        latest.insert(snapshot.account_id.as_str(), snapshot);
+    }
+    if latest.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<&BalanceSnapshot> = latest.into_values().collect();
+    let times: Vec<DateTime<Utc>> = rows.iter().map(|s| s.time).collect();
+    let account_ids: Vec<String> = rows.iter().map(|s| s.account_id.clone()).collect();
+    let balances: Vec<f64> = rows.iter().map(|s| s.balance).collect();
+    let currencies: Vec<String> = rows.iter().map(|s| s.currency.clone()).collect();
+    let types: Vec<String> = rows.iter().map(|s| s.snapshot_type.clone()).collect();
+
+    sqlx::query(
+        "INSERT INTO account_balance_history (time, account_id, balance, currency, snapshot_type)
+         SELECT * FROM UNNEST($1::timestamptz[], $2::varchar[], $3::float8[], $4::varchar[], $5::varchar[])
+         ON CONFLICT (account_id, time) DO UPDATE
+         SET balance = EXCLUDED.balance, snapshot_type = EXCLUDED.snapshot_type",
+    )
+    .bind(&times)
+    .bind(&account_ids)
+    .bind(&balances)
+    .bind(&currencies)
+    .bind(&types)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// 按时间顺序读取若干账户的全部余额快照
+pub async fn load_balance_snapshots(pool: &PgPool, account_ids: &[String]) -> Result<Vec<BalanceSnapshot>> {
+    let rows: Vec<(String, DateTime<Utc>, f64, String, String)> = sqlx::query_as(
+        "SELECT account_id, time, balance::float8, currency, snapshot_type FROM account_balance_history
+         WHERE account_id = ANY($1)
+         ORDER BY account_id, time",
+    )
+    .bind(account_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(account_id, time, balance, currency, snapshot_type)| BalanceSnapshot {
+            account_id,
+            time,
+            balance,
+            currency,
+            snapshot_type,
+        })
+        .collect())
+}
+
+/// 删除若干账户的全部余额快照，返回删除的行数；连续聚合视图在下次刷新时同步
+pub async fn delete_balance_snapshots(pool: &PgPool, account_ids: &[String]) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM account_balance_history WHERE account_id = ANY($1)")
+        .bind(account_ids)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
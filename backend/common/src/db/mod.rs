@@ -1,4 +1,9 @@
-use mongodb::{Client, Database};
+pub mod balance_history;
+
+pub use balance_history::*;
+
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::{Client, ClientSession, Database};
 use redis::aio::ConnectionManager;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
@@ -8,6 +13,7 @@ use tracing::warn;
 
 #[derive(Clone)]
 pub struct DatabaseConnection {
+    pub mongo_client: Client,
     pub mongo: Arc<Database>,
     pub redis: Arc<ConnectionManager>,
     pub pg: Option<PgPool>,
@@ -17,9 +23,9 @@ impl DatabaseConnection {
     pub async fn new(mongo_uri: &str, redis_uri: &str, pg_uri: Option<&str>, db_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
         // Try to connect to Mongo with retries
         let mut attempts = 0u32;
-        let mongo = loop {
+        let mongo_client = loop {
             match Client::with_uri_str(mongo_uri).await {
-                Ok(client) => break Arc::new(client.database(db_name)),
+                Ok(client) => break client,
                 Err(e) => {
                     attempts += 1;
                     if attempts >= 10 {
@@ -30,6 +36,7 @@ impl DatabaseConnection {
                 }
             }
         };
+        let mongo = Arc::new(mongo_client.database(db_name));
 
         // Redis connection with retries
         attempts = 0;
@@ -77,6 +84,69 @@ impl DatabaseConnection {
             None
         };
 
-        Ok(Self { mongo, redis, pg })
+        // TimescaleDB 表结构的版本化迁移，多个服务同时启动时由 sqlx 的咨询锁保证只执行一次
+        if let Some(pool) = &pg {
+            sqlx::migrate!("./migrations").run(pool).await?;
+        }
+
+        Ok(Self { mongo_client, mongo, redis, pg })
+    }
+
+    /// Start a session with an open multi-document transaction.
+    ///
+    /// Requires MongoDB to run as a replica set (or sharded cluster); the
+    /// caller is responsible for committing or aborting the transaction.
+    pub async fn start_transaction(&self) -> mongodb::error::Result<ClientSession> {
+        let mut session = self.mongo_client.start_session(None).await?;
+        session.start_transaction(None).await?;
+        Ok(session)
+    }
+
+    /// Append balance snapshots to the TimescaleDB history.
+    ///
+    /// Skipped when no Postgres pool is configured. Failures are only logged,
+    /// since the balances themselves are already committed in MongoDB.
+    pub async fn record_balance_history(&self, snapshots: &[BalanceSnapshot]) {
+        if let Some(pool) = &self.pg {
+            if let Err(e) = record_balance_snapshots(pool, snapshots).await {
+                warn!("Failed to record balance history: {}", e);
+            }
+        }
+    }
+}
+
+/// Finish a transaction opened by [`DatabaseConnection::start_transaction`].
+///
+/// Commits when `result` is `Ok`, otherwise aborts (logging abort failures)
+/// and hands the original error back to the caller.
+pub async fn commit_or_abort<T>(session: &mut ClientSession, result: crate::Result<T>) -> crate::Result<T> {
+    match result {
+        Ok(value) => {
+            session.commit_transaction().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            if let Err(abort_err) = session.abort_transaction().await {
+                warn!("Failed to abort transaction: {}", abort_err);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// MongoDB 唯一索引冲突的错误码
+const DUPLICATE_KEY: i32 = 11000;
+
+/// 写入是否因唯一索引冲突失败 (E11000)
+pub fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY,
+        ErrorKind::BulkWrite(failure) => failure
+            .write_errors
+            .iter()
+            .flatten()
+            .any(|e| e.code == DUPLICATE_KEY),
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY,
+        _ => false,
     }
 }
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -19,6 +19,8 @@ pub enum Error {
     Database(String),
     Validation(String),
     InvalidInput(String),
+    /// 触发限流，参数为建议的重试等待秒数
+    TooManyRequests(u64),
 }
 
 impl fmt::Display for Error {
@@ -33,6 +35,7 @@ impl fmt::Display for Error {
             Error::Database(msg) => write!(f, "Database error: {}", msg),
             Error::Validation(msg) => write!(f, "Validation error: {}", msg),
             Error::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            Error::TooManyRequests(secs) => write!(f, "Too many requests: retry after {}s", secs),
         }
     }
 }
@@ -50,10 +53,16 @@ struct ErrorResponse {
 struct ErrorDetail {
     code: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            Error::TooManyRequests(secs) => Some(*secs),
+            _ => None,
+        };
         let (status, code, message) = match self {
             Error::NotFound(msg) => (StatusCode::NOT_FOUND, "NOT_FOUND", msg),
             Error::BadRequest(msg) => (StatusCode::BAD_REQUEST, "BAD_REQUEST", msg),
@@ -68,6 +77,11 @@ impl IntoResponse for Error {
             }
             Error::Validation(msg) => (StatusCode::BAD_REQUEST, "VALIDATION_ERROR", msg),
             Error::InvalidInput(msg) => (StatusCode::BAD_REQUEST, "INVALID_INPUT", msg),
+            Error::TooManyRequests(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMIT_EXCEEDED",
+                "请求频率超限，请稍后再试".to_string(),
+            ),
         };
 
         let error_response = ErrorResponse {
@@ -75,11 +89,16 @@ impl IntoResponse for Error {
             error: ErrorDetail {
                 code: code.to_string(),
                 message,
+                retry_after,
             },
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
 
-        (status, Json(error_response)).into_response()
+        let mut response = (status, Json(error_response)).into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
@@ -95,3 +114,15 @@ impl From<jsonwebtoken::errors::Error> for Error {
         Error::Unauthorized(err.to_string())
     }
 }
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Error::Database(err.to_string())
+    }
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(err: redis::RedisError) -> Self {
+        Error::Database(err.to_string())
+    }
+}
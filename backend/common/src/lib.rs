@@ -6,6 +6,12 @@ pub mod algorithms;
 pub mod db;
 pub mod middleware;
 pub mod constants;
+pub mod timezone;
+pub mod calendar;
+pub mod categories;
+pub mod recurrence;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 pub use error::{Error, Result};
 pub use models::*;
@@ -15,3 +21,10 @@ pub use algorithms::*;
 pub use db::*;
 pub use middleware::*;
 pub use constants::*;
+pub use timezone::{is_valid_timezone, user_timezone, Tz};
+pub use calendar::{Calendar, Period};
+pub use categories::{
+    check_splits, ensure_transaction_category, ensure_transaction_splits, find_category, reference_filter,
+    seed_system_categories,
+};
+pub use recurrence::{Frequency, RecurrenceRule};
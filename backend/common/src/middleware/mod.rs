@@ -0,0 +1,215 @@
+pub mod rate_limit;
+
+pub use rate_limit::{client_ip, Lockout, RateLimit, RateLimitLayer};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::bson::{self, doc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::{ApiToken, Claims, DatabaseConnection, Error, JwtManager, TOKEN_TYPE_API};
+
+/// API 令牌的 last_used_at 最多每隔这么久写一次
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+fn revoked_key(user_id: &str) -> String {
+    format!("auth:revoked:{}", user_id)
+}
+
+/// 作废用户此前签发的全部令牌 (用户被禁用或降级时)；标记保留到这些访问令牌都过期为止。
+/// 令牌签发时间精确到秒，同一秒内稍早签发的令牌不受影响
+pub async fn revoke_issued_tokens(redis: &ConnectionManager, user_id: &str, ttl: Duration) -> crate::Result<()> {
+    let mut conn = redis.clone();
+    conn.set_ex::<_, _, ()>(revoked_key(user_id), Utc::now().timestamp(), ttl.num_seconds().max(1) as u64)
+        .await?;
+    Ok(())
+}
+
+/// 令牌是否签发于吊销标记之前
+fn issued_before(claims: &Claims, revoked_at: Option<i64>) -> bool {
+    revoked_at.is_some_and(|revoked_at| claims.iat < revoked_at)
+}
+
+/// 上次记录的使用时间是否已经过时，需要重新写入
+fn last_used_stale(last_used_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    last_used_at.is_none_or(|last| now - last >= Duration::seconds(LAST_USED_RESOLUTION_SECS))
+}
+
+/// 认证中间件的状态：验签密钥环，以及用于检查 API 令牌是否已吊销的数据库连接
+#[derive(Clone)]
+pub struct AuthState {
+    pub jwt: Arc<JwtManager>,
+    pub db: DatabaseConnection,
+}
+
+impl AuthState {
+    pub fn new(jwt: Arc<JwtManager>, db: DatabaseConnection) -> Self {
+        Self { jwt, db }
+    }
+}
+
+pub async fn auth_middleware(
+    State(auth): State<AuthState>,
+    headers: HeaderMap,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let auth_header = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    
+    if !auth_header.starts_with("Bearer ") {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    
+    let token = &auth_header[7..];
+    let claims = auth
+        .jwt
+        .verify_access_token(token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    // 管理员禁用或降级用户后，之前签发的令牌不再有效；Redis 不可用时放行，与限流一致
+    let mut conn = (*auth.db.redis).clone();
+    match conn.get::<_, Option<i64>>(revoked_key(&claims.user_id)).await {
+        Ok(revoked_at) if issued_before(&claims, revoked_at) => return Err(StatusCode::UNAUTHORIZED),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Token revocation check unavailable, allowing request: {}", e),
+    }
+
+    // API 令牌在库中删除即视为吊销
+    if claims.token_type == TOKEN_TYPE_API {
+        let collection = auth.db.mongo.collection::<ApiToken>("api_tokens");
+        let id = claims.jti.as_deref().unwrap_or_default();
+        let token = collection
+            .find_one(doc! { "_id": id, "user_id": &claims.user_id }, None)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let now = Utc::now();
+        if last_used_stale(token.last_used_at, now) {
+            let result = collection
+                .update_one(doc! { "_id": id }, doc! { "$set": { "last_used_at": bson::to_bson(&now).unwrap() } }, None)
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to record API token usage: {}", e);
+            }
+        }
+    }
+    
+    request.extensions_mut().insert(claims);
+    Ok(next.run(request).await)
+}
+
+/// 路由所需的权限
+pub trait Scope {
+    const NAME: &'static str;
+}
+
+macro_rules! scopes {
+    ($($marker:ident => $name:literal),* $(,)?) => {
+        $(
+            pub struct $marker;
+
+            impl Scope for $marker {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+/// 各路由使用的权限标记类型
+pub mod scope {
+    use super::Scope;
+
+    scopes! {
+        ProfileRead => "profile:read",
+        ProfileWrite => "profile:write",
+        AccountsRead => "accounts:read",
+        AccountsWrite => "accounts:write",
+        TransactionsRead => "transactions:read",
+        TransactionsWrite => "transactions:write",
+        BudgetsRead => "budgets:read",
+        BudgetsWrite => "budgets:write",
+        ReportsRead => "reports:read",
+        Admin => "admin",
+    }
+}
+
+/// 要求请求拥有权限 S 的提取器，取代直接提取 `Extension<Claims>`；需位于 auth_middleware 之后
+pub struct Authorized<S: Scope> {
+    pub claims: Claims,
+    scope: PhantomData<S>,
+}
+
+#[async_trait]
+impl<S, St> FromRequestParts<St> for Authorized<S>
+where
+    S: Scope,
+    St: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &St) -> Result<Self, Self::Rejection> {
+        let claims = parts
+            .extensions
+            .get::<Claims>()
+            .cloned()
+            .ok_or_else(|| Error::Unauthorized("Missing credentials".to_string()))?;
+
+        if !claims.has_scope(S::NAME) {
+            return Err(Error::Forbidden(format!("Missing required scope: {}", S::NAME)));
+        }
+        Ok(Self {
+            claims,
+            scope: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TOKEN_TYPE_ACCESS;
+
+    fn claims(iat: i64) -> Claims {
+        Claims {
+            sub: "user".to_string(),
+            user_id: "user".to_string(),
+            username: "user".to_string(),
+            role: "admin".to_string(),
+            iat,
+            exp: iat + 7200,
+            token_type: TOKEN_TYPE_ACCESS.to_string(),
+            jti: None,
+            family: None,
+            scopes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_tokens_issued_before_revocation_are_rejected() {
+        assert!(!issued_before(&claims(1_000), None));
+        assert!(issued_before(&claims(999), Some(1_000)));
+        // 吊销之后重新登录或刷新得到的令牌可以使用
+        assert!(!issued_before(&claims(1_000), Some(1_000)));
+        assert!(!issued_before(&claims(1_001), Some(1_000)));
+    }
+
+    #[test]
+    fn test_last_used_written_at_most_once_per_minute() {
+        let now = Utc::now();
+        assert!(last_used_stale(None, now));
+        assert!(!last_used_stale(Some(now - Duration::seconds(30)), now));
+        assert!(last_used_stale(Some(now - Duration::seconds(60)), now));
+    }
+}
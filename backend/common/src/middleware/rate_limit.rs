@@ -0,0 +1,432 @@
+// 限流 - 基于 Redis 有序集合的滑动窗口限流 tower 层，以及按连续违规次数递增的渐进式锁定
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::{Claims, Error, Result};
+
+const HEADER_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const HEADER_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const HEADER_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// 滑动窗口：清理窗口外的请求记录，未超限则记录本次请求
+/// 返回 {是否放行, 窗口内请求数, 窗口内最早请求的时间戳(毫秒)}
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+local count = redis.call('ZCARD', KEYS[1])
+local allowed = 0
+if count < limit then
+    redis.call('ZADD', KEYS[1], now, ARGV[4])
+    count = count + 1
+    allowed = 1
+end
+redis.call('PEXPIRE', KEYS[1], window)
+local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+local oldest_at = now
+if oldest[2] then
+    oldest_at = tonumber(oldest[2])
+end
+return {allowed, count, oldest_at}
+"#;
+
+/// 渐进式锁定：在 memory 时间内累计 threshold 次违规后开始锁定，
+/// 之后每多一次违规锁定时长翻倍，最长 max
+#[derive(Debug, Clone, Copy)]
+pub struct Lockout {
+    pub threshold: u64,
+    pub base: Duration,
+    pub max: Duration,
+    pub memory: Duration,
+}
+
+impl Default for Lockout {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            base: Duration::from_secs(60),
+            max: Duration::from_secs(3600),
+            memory: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+impl Lockout {
+    /// 第 strikes 次违规后的锁定时长，未达到阈值时不锁定
+    pub fn duration(&self, strikes: u64) -> Option<Duration> {
+        if strikes < self.threshold {
+            return None;
+        }
+        let doublings = (strikes - self.threshold).min(31) as u32;
+        Some(self.base.saturating_mul(2u32.saturating_pow(doublings)).min(self.max))
+    }
+
+    /// 仍处于锁定期时返回 TooManyRequests
+    pub async fn check(&self, redis: &ConnectionManager, key: &str) -> Result<()> {
+        let mut conn = redis.clone();
+        let ttl: i64 = conn.ttl(format!("{}:lock", key)).await?;
+        if ttl > 0 {
+            return Err(Error::TooManyRequests(ttl as u64));
+        }
+        Ok(())
+    }
+
+    /// 记录一次违规，返回由此触发的锁定秒数
+    pub async fn record_failure(&self, redis: &ConnectionManager, key: &str) -> Result<Option<u64>> {
+        let mut conn = redis.clone();
+        let strikes_key = format!("{}:strikes", key);
+        let (strikes,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&strikes_key, 1)
+            .expire(&strikes_key, self.memory.as_secs() as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        let Some(duration) = self.duration(strikes) else {
+            return Ok(None);
+        };
+        let secs = duration.as_secs().max(1);
+        let _: () = conn.set_ex(format!("{}:lock", key), strikes, secs).await?;
+        Ok(Some(secs))
+    }
+
+    /// 成功后清除违规记录
+    pub async fn reset(&self, redis: &ConnectionManager, key: &str) -> Result<()> {
+        let mut conn = redis.clone();
+        let _: () = conn.del(&[format!("{}:strikes", key), format!("{}:lock", key)]).await?;
+        Ok(())
+    }
+}
+
+/// 限流规则：window 时间内最多 limit 次请求
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub name: &'static str,
+    pub limit: u64,
+    pub window: Duration,
+    /// 超限后的渐进式锁定，None 表示仅按窗口限流
+    pub lockout: Option<Lockout>,
+}
+
+impl RateLimit {
+    pub fn new(name: &'static str, limit: u64, window: Duration) -> Self {
+        Self {
+            name,
+            limit,
+            window,
+            lockout: None,
+        }
+    }
+
+    pub fn per_second(name: &'static str, limit: u64) -> Self {
+        Self::new(name, limit, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(name: &'static str, limit: u64) -> Self {
+        Self::new(name, limit, Duration::from_secs(60))
+    }
+
+    pub fn per_hour(name: &'static str, limit: u64) -> Self {
+        Self::new(name, limit, Duration::from_secs(3600))
+    }
+
+    /// 允许通过环境变量覆盖上限
+    pub fn limit_from_env(mut self, var: &str) -> Self {
+        if let Some(limit) = std::env::var(var).ok().and_then(|v| v.parse().ok()) {
+            self.limit = limit;
+        }
+        self
+    }
+
+    pub fn with_lockout(mut self, lockout: Lockout) -> Self {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    /// 全局单 IP 限流，默认 100 req/s
+    pub fn global_ip() -> Self {
+        Self::per_second("ip", 100).limit_from_env("RATE_LIMIT_IP_PER_SECOND")
+    }
+
+    /// 全局单用户限流，默认 50 req/s
+    pub fn global_user() -> Self {
+        Self::per_second("user", 50).limit_from_env("RATE_LIMIT_USER_PER_SECOND")
+    }
+}
+
+/// 一次限流检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// reset_at 为窗口内最早一次请求移出窗口的 Unix 时间戳(秒)
+    Allowed { limit: u64, remaining: u64, reset_at: u64 },
+    Rejected { retry_after: u64 },
+}
+
+impl Decision {
+    /// 由滑动窗口脚本的返回值计算结果，时间单位均为毫秒
+    fn from_window(limit: u64, window_ms: u64, now_ms: u64, allowed: bool, count: u64, oldest_ms: u64) -> Self {
+        let frees_at = oldest_ms.saturating_add(window_ms);
+        if allowed {
+            Decision::Allowed {
+                limit,
+                remaining: limit.saturating_sub(count),
+                reset_at: frees_at.div_ceil(1000),
+            }
+        } else {
+            Decision::Rejected {
+                retry_after: frees_at.saturating_sub(now_ms).div_ceil(1000).max(1),
+            }
+        }
+    }
+}
+
+/// 按什么维度区分调用方
+#[derive(Debug, Clone, Copy)]
+enum KeyBy {
+    Ip,
+    User,
+}
+
+struct Limiter {
+    redis: ConnectionManager,
+    rule: RateLimit,
+    key_by: KeyBy,
+    /// 信任 nginx 传入的 X-Real-IP / X-Forwarded-For
+    trust_proxy: bool,
+    enabled: bool,
+    script: Script,
+}
+
+impl Limiter {
+    fn identity(&self, request: &Request) -> Option<String> {
+        match self.key_by {
+            KeyBy::Ip => client_ip(
+                request.headers(),
+                request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0),
+                self.trust_proxy,
+            ),
+            KeyBy::User => request.extensions().get::<Claims>().map(|claims| claims.user_id.clone()),
+        }
+    }
+
+    async fn check(&self, identity: &str) -> Result<Decision> {
+        let key = format!("ratelimit:{}:{}", self.rule.name, identity);
+        if let Some(lockout) = &self.rule.lockout {
+            match lockout.check(&self.redis, &key).await {
+                Err(Error::TooManyRequests(retry_after)) => return Ok(Decision::Rejected { retry_after }),
+                Err(e) => return Err(e),
+                Ok(()) => {}
+            }
+        }
+
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let window_ms = self.rule.window.as_millis() as u64;
+        let mut conn = self.redis.clone();
+        let (allowed, count, oldest_ms): (u64, u64, u64) = self
+            .script
+            .key(&key)
+            .arg(now_ms)
+            .arg(window_ms)
+            .arg(self.rule.limit)
+            .arg(uuid::Uuid::new_v4().to_string())
+            .invoke_async(&mut conn)
+            .await?;
+
+        let decision = Decision::from_window(self.rule.limit, window_ms, now_ms, allowed == 1, count, oldest_ms);
+        if let (Decision::Rejected { retry_after }, Some(lockout)) = (decision, &self.rule.lockout) {
+            if let Some(locked) = lockout.record_failure(&self.redis, &key).await? {
+                return Ok(Decision::Rejected {
+                    retry_after: retry_after.max(locked),
+                });
+            }
+        }
+        Ok(decision)
+    }
+}
+
+/// 取客户端 IP：经 nginx 转发时优先取 X-Real-IP，其次 X-Forwarded-For 的第一跳，最后取连接地址
+pub fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>, trust_proxy: bool) -> Option<String> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get("x-forwarded-for")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.split(',').next())
+            })
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+    peer.map(|addr| addr.ip().to_string())
+}
+
+/// Redis 滑动窗口限流层。按 IP 限流需在 serve 时启用
+/// `into_make_service_with_connect_info::<SocketAddr>()`；按用户限流需位于 auth_middleware 之内，
+/// 未认证的请求直接放行。Redis 不可用时放行并记录告警
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    pub fn per_ip(redis: &ConnectionManager, rule: RateLimit) -> Self {
+        Self::new(redis, rule, KeyBy::Ip)
+    }
+
+    pub fn per_user(redis: &ConnectionManager, rule: RateLimit) -> Self {
+        Self::new(redis, rule, KeyBy::User)
+    }
+
+    fn new(redis: &ConnectionManager, rule: RateLimit, key_by: KeyBy) -> Self {
+        let flag = |var: &str, default: bool| {
+            std::env::var(var).map(|v| v != "false" && v != "0").unwrap_or(default)
+        };
+        Self {
+            limiter: Arc::new(Limiter {
+                redis: redis.clone(),
+                rule,
+                key_by,
+                // 转发头可被客户端伪造，仅在服务端口只能经 nginx 访问时显式开启
+                trust_proxy: flag("RATE_LIMIT_TRUST_PROXY", false),
+                enabled: flag("RATE_LIMIT_ENABLED", true),
+                script: Script::new(SLIDING_WINDOW_SCRIPT),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // 取出已 poll_ready 的内层服务，留下克隆供下次使用
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let identity = match limiter.identity(&request) {
+                Some(identity) if limiter.enabled => identity,
+                _ => return inner.call(request).await,
+            };
+
+            match limiter.check(&identity).await {
+                Ok(Decision::Rejected { retry_after }) => Ok(Error::TooManyRequests(retry_after).into_response()),
+                Ok(Decision::Allowed { limit, remaining, reset_at }) => {
+                    let mut response = inner.call(request).await?;
+                    // 多层限流叠加时保留最内层 (最具体) 规则的额度
+                    let headers = response.headers_mut();
+                    if !headers.contains_key(HEADER_LIMIT) {
+                        headers.insert(HEADER_LIMIT, HeaderValue::from(limit));
+                        headers.insert(HEADER_REMAINING, HeaderValue::from(remaining));
+                        headers.insert(HEADER_RESET, HeaderValue::from(reset_at));
+                    }
+                    Ok(response)
+                }
+                Err(e) => {
+                    warn!("Rate limiter {} unavailable, allowing request: {}", limiter.rule.name, e);
+                    inner.call(request).await
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_doubles_until_max() {
+        let lockout = Lockout::default();
+        assert_eq!(lockout.duration(4), None);
+        assert_eq!(lockout.duration(5), Some(Duration::from_secs(60)));
+        assert_eq!(lockout.duration(6), Some(Duration::from_secs(120)));
+        assert_eq!(lockout.duration(8), Some(Duration::from_secs(480)));
+        assert_eq!(lockout.duration(11), Some(Duration::from_secs(3600)));
+        assert_eq!(lockout.duration(u64::MAX), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_decision_from_window() {
+        // 窗口 60s，最早请求在 10s 前
+        let allowed = Decision::from_window(5, 60_000, 100_000, true, 3, 90_000);
+        assert_eq!(
+            allowed,
+            Decision::Allowed {
+                limit: 5,
+                remaining: 2,
+                reset_at: 150
+            }
+        );
+
+        let rejected = Decision::from_window(5, 60_000, 100_000, false, 5, 90_000);
+        assert_eq!(rejected, Decision::Rejected { retry_after: 50 });
+
+        // 即将移出窗口时至少等待 1 秒
+        let rejected = Decision::from_window(5, 60_000, 100_000, false, 5, 40_001);
+        assert_eq!(rejected, Decision::Rejected { retry_after: 1 });
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let peer: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9, 10.0.0.1"));
+        assert_eq!(client_ip(&headers, Some(peer), true).as_deref(), Some("203.0.113.9"));
+
+        headers.insert("x-real-ip", HeaderValue::from_static("198.51.100.7"));
+        assert_eq!(client_ip(&headers, Some(peer), true).as_deref(), Some("198.51.100.7"));
+
+        // 不信任代理头时只看连接地址
+        assert_eq!(client_ip(&headers, Some(peer), false).as_deref(), Some("10.0.0.2"));
+        assert_eq!(client_ip(&HeaderMap::new(), None, true), None);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,12 +15,23 @@ pub struct User {
     pub phone: Option<String>,
     pub settings: UserSettings,
     pub status: String,
+    #[serde(default = "default_user_role")]
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified_at: Option<DateTime<Utc>>,
+    /// 登录需二次验证 (TOTP)，密钥与恢复码保存在 user_mfa 集合
+    #[serde(default)]
+    pub mfa_enabled: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_login_at: Option<DateTime<Utc>>,
 }
 
+fn default_user_role() -> String {
+    crate::constants::ROLE_USER.to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
     pub default_currency: String,
@@ -88,9 +99,17 @@ pub struct Transaction {
     pub account_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub to_account_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>,
+    /// 拆分交易为金额最大的拆分行的分类
     pub category_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subcategory_id: Option<String>,
+    /// 按分类拆分的明细行，金额之和等于交易金额
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub splits: Option<Vec<TransactionSplit>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
     pub description: String,
@@ -104,6 +123,8 @@ pub struct Transaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dedup_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub external_id: Option<String>,
     pub status: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -113,6 +134,53 @@ pub struct Transaction {
     pub created_by: String,
 }
 
+impl Transaction {
+    /// 交易对各账户余额的影响 (账户ID, 变动额)：收入增加、支出减少，转账从转出账户转入目标账户
+    pub fn balance_effects(&self) -> Vec<(&str, f64)> {
+        match self.transaction_type.as_str() {
+            "income" => vec![(self.account_id.as_str(), self.amount)],
+            "expense" => vec![(self.account_id.as_str(), -self.amount)],
+            "transfer" => {
+                let mut effects = vec![(self.account_id.as_str(), -self.amount)];
+                if let Some(to_account_id) = &self.to_account_id {
+                    effects.push((to_account_id.as_str(), self.to_amount.unwrap_or(self.amount)));
+                }
+                effects
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// 交易金额在各分类上的归属 (分类ID, 金额)：拆分交易按拆分行，否则整笔计入交易分类
+    pub fn category_amounts(&self) -> Vec<(&str, f64)> {
+        match &self.splits {
+            Some(splits) if !splits.is_empty() => splits
+                .iter()
+                .map(|split| (split.category_id.as_str(), split.amount))
+                .collect(),
+            _ => vec![(self.category_id.as_str(), self.amount)],
+        }
+    }
+
+    /// 计入给定分类集合 (如一个预算的分类) 的金额
+    pub fn amount_in_categories(&self, category_ids: &[String]) -> f64 {
+        self.category_amounts()
+            .into_iter()
+            .filter(|(category_id, _)| category_ids.iter().any(|id| id == category_id))
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+}
+
+/// 拆分行：一张小票同时包含食品和日用品时，按分类拆成多行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSplit {
+    pub category_id: String,
+    pub amount: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Location {
     pub latitude: f64,
@@ -135,6 +203,9 @@ pub struct Category {
     pub id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_id: Option<String>,
+    /// 系统分类的固定标识 (见 SYSTEM_CATEGORIES)，自定义分类为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
     pub name: String,
     pub category_type: String,
     pub icon: String,
@@ -148,6 +219,143 @@ pub struct Category {
     pub updated_at: DateTime<Utc>,
 }
 
+impl Category {
+    /// 交易、预算和子分类引用该分类时使用的标识：系统分类为 key，自定义分类为 _id
+    pub fn reference_id(&self) -> &str {
+        self.key.as_deref().or(self.id.as_deref()).unwrap_or_default()
+    }
+}
+
+/// 周期性交易 (定期账单、工资等)：按 RRULE 在每个发生日生成一笔交易；日期均为用户时区的本地日期
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringTransaction {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub template: TransactionTemplate,
+    /// RRULE 子集，见 RecurrenceRule
+    pub rrule: String,
+    pub start_date: NaiveDate,
+    pub status: String, // active/paused/completed
+    /// 对单次发生的跳过或修改
+    #[serde(default)]
+    pub exceptions: Vec<OccurrenceException>,
+    /// 已处理 (生成或跳过) 的最后一个发生日
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_occurrence: Option<NaiveDate>,
+    /// 下一次入账的日期 (首个待处理发生日，改期时为改期后的日期)，规则结束后为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_occurrence: Option<NaiveDate>,
+    pub generated_count: u32,
+    /// 最近一次生成失败的原因 (如账户已归档)，成功后清除
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// 调度器处理期间的租约，防止多个实例重复生成
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 周期性交易每次生成的交易内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionTemplate {
+    pub transaction_type: String,
+    pub amount: f64,
+    pub currency: String,
+    pub account_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_account_id: Option<String>,
+    pub category_id: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// 单次发生的例外：跳过，或修改金额、描述、分类、收款方、日期
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OccurrenceException {
+    /// 按规则原本的发生日
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub skip: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    /// 改到另一天入账
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<NaiveDate>,
+}
+
+/// 自动分类规则：条件全部满足时执行动作；多条规则按 priority 从小到大匹配
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryRule {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub priority: i32,
+    pub is_active: bool,
+    pub conditions: RuleConditions,
+    pub actions: RuleActions,
+    /// 规则累计修改过的交易数
+    pub match_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_matched_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 规则条件，未设置的条件不参与匹配
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<TextMatch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payee: Option<TextMatch>,
+    /// 金额区间 (含端点)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_max: Option<f64>,
+    /// 交易账户为其中之一
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_ids: Option<Vec<String>>,
+    /// 交易带有其中任一标签
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// 文本匹配，忽略大小写和首尾空白
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextMatch {
+    pub operator: String, // contains/equals/starts_with/ends_with
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleActions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subcategory_id: Option<String>,
+    /// 追加到交易已有标签
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Budget {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -188,3 +396,87 @@ pub struct BudgetPrediction {
     pub algorithm: String,
     pub updated_at: DateTime<Utc>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_id: String,
+    pub account_id: String,
+    pub file_name: String,
+    pub format: String,
+    pub status: String,
+    pub total_rows: u32,
+    pub processed_rows: u32,
+    pub inserted: u32,
+    /// 已写入但疑似重复、等待复核的行数 (包含在 inserted 中)
+    #[serde(default)]
+    pub flagged: u32,
+    pub skipped: u32,
+    pub failed: u32,
+    pub errors: Vec<ImportRowError>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRowError {
+    pub row: u32,
+    pub message: String,
+}
+
+/// 账户对账结果，每个账户保留最近一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// 与账户ID相同
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_id: String,
+    pub account_id: String,
+    pub recorded_balance: f64,
+    pub ledger_balance: f64,
+    pub difference: f64,
+    pub status: String, // consistent/discrepancy/corrected/adjusted
+    pub transaction_count: u64,
+    /// 差异产生的区间：上次对账一致的时间 (或账户创建时间) 至本次对账时间
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_consistent_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjustment_transaction_id: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// 用户为脚本签发的长期 API 令牌；令牌本身只在创建时返回一次，这里只保存元数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    #[serde(rename = "_id")]
+    pub id: String, // 令牌 jti
+    pub user_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// 用户的 TOTP 二次验证配置，_id 即用户 id；确认绑定前 enabled 为 false
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserMfa {
+    #[serde(rename = "_id")]
+    pub user_id: String,
+    /// Base32 编码的 TOTP 密钥
+    pub secret: String,
+    pub enabled: bool,
+    /// 未使用的恢复码的 SHA-256 摘要
+    pub recovery_codes: Vec<String>,
+    /// 最近一次通过校验的时间步，同一口令不能重复使用
+    pub last_used_step: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,490 @@
+// 重复规则 - 解析 RFC 5545 RRULE 的常用子集，并按本地日期展开周期性交易的发生日
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// 连续这么多个周期没有发生日时认为规则不会再产生日期 (如隔年的 2 月 30 日)
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// BYDAY 的一项，如 MO、-1FR (当月最后一个周五)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub ordinal: Option<i32>,
+    pub weekday: Weekday,
+}
+
+/// 支持 FREQ (DAILY/WEEKLY/MONTHLY/YEARLY)、INTERVAL、BYDAY、BYMONTHDAY、BYSETPOS、COUNT、UNTIL；
+/// 例如每月最后一个工作日为 FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<ByDay>,
+    /// 负数从月末倒数，-1 为当月最后一天；当月没有的日期 (如 31 日) 跳过
+    pub by_month_day: Vec<i32>,
+    /// 在每个周期的候选日期中取第几个，负数从后往前数
+    pub by_set_pos: Vec<i32>,
+    pub count: Option<u32>,
+    /// 最后可能的发生日 (含)
+    pub until: Option<NaiveDate>,
+}
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Validation(format!("Invalid rrule: {}", message.into()))
+}
+
+fn parse_int<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("{} must be an integer", key)))
+}
+
+fn parse_by_day(value: &str) -> Result<ByDay> {
+    let split = value.len().saturating_sub(2);
+    let (ordinal, code) = value.split_at(split);
+    let weekday = WEEKDAYS
+        .iter()
+        .find(|(name, _)| *name == code)
+        .map(|(_, weekday)| *weekday)
+        .ok_or_else(|| invalid(format!("unknown weekday {}", value)))?;
+    let ordinal = match ordinal {
+        "" => None,
+        ordinal => {
+            let n: i32 = parse_int("BYDAY", ordinal.trim_start_matches('+'))?;
+            if n == 0 || n.abs() > 5 {
+                return Err(invalid("BYDAY ordinal must be between -5 and 5"));
+            }
+            Some(n)
+        }
+    };
+    Ok(ByDay { ordinal, weekday })
+}
+
+fn parse_until(value: &str) -> Result<NaiveDate> {
+    let date = value.split('T').next().unwrap_or_default();
+    NaiveDate::parse_from_str(date, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .map_err(|_| invalid("UNTIL must be a date like 20261231"))
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+    let next = first.checked_add_months(Months::new(1)).unwrap();
+    (next - first).num_days() as u32
+}
+
+impl RecurrenceRule {
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix("RRULE:").unwrap_or(value);
+
+        let mut frequency = None;
+        let mut rule = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_set_pos: Vec::new(),
+            count: None,
+            until: None,
+        };
+
+        for part in value.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected KEY=VALUE, got {}", part)))?;
+            let key = key.trim().to_ascii_uppercase();
+            let value = value.trim().to_ascii_uppercase();
+            match key.as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid("FREQ must be DAILY, WEEKLY, MONTHLY or YEARLY")),
+                    })
+                }
+                "INTERVAL" => rule.interval = parse_int(&key, &value)?,
+                "COUNT" => rule.count = Some(parse_int(&key, &value)?),
+                "UNTIL" => rule.until = Some(parse_until(&value)?),
+                "BYDAY" => {
+                    rule.by_day = value.split(',').map(parse_by_day).collect::<Result<_>>()?;
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = value.split(',').map(|v| parse_int(&key, v)).collect::<Result<_>>()?;
+                }
+                "BYSETPOS" => {
+                    rule.by_set_pos = value.split(',').map(|v| parse_int(&key, v)).collect::<Result<_>>()?;
+                }
+                // 起始日期由 start_date 单独给出
+                "WKST" if value == "MO" => {}
+                _ => return Err(invalid(format!("{} is not supported", key))),
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(|| invalid("FREQ is required"))?;
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.interval == 0 || self.interval > 1000 {
+            return Err(invalid("INTERVAL must be between 1 and 1000"));
+        }
+        if self.count == Some(0) {
+            return Err(invalid("COUNT must be positive"));
+        }
+        if self.count.is_some() && self.until.is_some() {
+            return Err(invalid("COUNT and UNTIL cannot be combined"));
+        }
+        match self.frequency {
+            Frequency::Daily | Frequency::Yearly => {
+                if !self.by_day.is_empty() || !self.by_month_day.is_empty() || !self.by_set_pos.is_empty() {
+                    return Err(invalid("BYDAY, BYMONTHDAY and BYSETPOS require FREQ=WEEKLY or MONTHLY"));
+                }
+            }
+            Frequency::Weekly => {
+                if !self.by_month_day.is_empty() {
+                    return Err(invalid("BYMONTHDAY requires FREQ=MONTHLY"));
+                }
+                if self.by_day.iter().any(|d| d.ordinal.is_some()) {
+                    return Err(invalid("BYDAY ordinals require FREQ=MONTHLY"));
+                }
+            }
+            Frequency::Monthly => {}
+        }
+        if self.by_month_day.iter().any(|d| *d == 0 || d.abs() > 31) {
+            return Err(invalid("BYMONTHDAY must be between -31 and 31"));
+        }
+        if self.by_set_pos.iter().any(|p| *p == 0 || p.abs() > 31) {
+            return Err(invalid("BYSETPOS must be between -31 and 31"));
+        }
+        if !self.by_set_pos.is_empty() && self.by_day.is_empty() && self.by_month_day.is_empty() {
+            return Err(invalid("BYSETPOS requires BYDAY or BYMONTHDAY"));
+        }
+        Ok(())
+    }
+
+    /// 从 start 开始 (含) 按时间顺序展开发生日；start 本身不满足规则时不算发生日
+    pub fn occurrences(&self, start: NaiveDate) -> Occurrences {
+        Occurrences {
+            rule: self.clone(),
+            start,
+            period: 0,
+            buffer: VecDeque::new(),
+            emitted: 0,
+            empty_periods: 0,
+            done: false,
+        }
+    }
+
+    /// date 是否为从 start 开始的发生日
+    pub fn is_occurrence(&self, start: NaiveDate, date: NaiveDate) -> bool {
+        self.occurrences(start).take_while(|d| *d <= date).any(|d| d == date)
+    }
+
+    fn matches_by_day(&self, date: NaiveDate) -> bool {
+        let day = date.day() as i32;
+        let last = days_in_month(date.year(), date.month()) as i32;
+        self.by_day.iter().any(|by_day| {
+            by_day.weekday == date.weekday()
+                && match by_day.ordinal {
+                    None => true,
+                    Some(n) if n > 0 => (day - 1) / 7 + 1 == n,
+                    Some(n) => (last - day) / 7 + 1 == -n,
+                }
+        })
+    }
+
+    /// 第 index 个周期的候选日期 (未按 start 过滤)；第二个返回值为周期的第一天
+    fn period_dates(&self, start: NaiveDate, index: u32) -> (Vec<NaiveDate>, NaiveDate) {
+        let step = index.saturating_mul(self.interval);
+        let (mut dates, first_day) = match self.frequency {
+            Frequency::Daily => {
+                let date = start + Duration::days(step as i64);
+                (vec![date], date)
+            }
+            Frequency::Weekly => {
+                let monday = start - Duration::days(start.weekday().num_days_from_monday() as i64)
+                    + Duration::weeks(step as i64);
+                let dates = if self.by_day.is_empty() {
+                    vec![monday + Duration::days(start.weekday().num_days_from_monday() as i64)]
+                } else {
+                    (0..7)
+                        .map(|offset| monday + Duration::days(offset))
+                        .filter(|date| self.matches_by_day(*date))
+                        .collect()
+                };
+                (dates, monday)
+            }
+            Frequency::Monthly => {
+                let first = start.with_day(1).unwrap().checked_add_months(Months::new(step)).unwrap();
+                let last = days_in_month(first.year(), first.month()) as i32;
+                let month_day = |day: i32| first.with_day(day as u32);
+                let dates: Vec<NaiveDate> = if !self.by_month_day.is_empty() {
+                    self.by_month_day
+                        .iter()
+                        .map(|d| if *d > 0 { *d } else { last + d + 1 })
+                        .filter(|d| (1..=last).contains(d))
+                        .filter_map(month_day)
+                        .filter(|date| self.by_day.is_empty() || self.matches_by_day(*date))
+                        .collect()
+                } else if !self.by_day.is_empty() {
+                    (1..=last)
+                        .filter_map(month_day)
+                        .filter(|date| self.matches_by_day(*date))
+                        .collect()
+                } else {
+                    month_day(start.day() as i32).into_iter().collect()
+                };
+                (dates, first)
+            }
+            Frequency::Yearly => {
+                let year = start.year() + step as i32;
+                let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+                (NaiveDate::from_ymd_opt(year, start.month(), start.day()).into_iter().collect(), first)
+            }
+        };
+
+        dates.sort();
+        dates.dedup();
+        if !self.by_set_pos.is_empty() {
+            let len = dates.len() as i32;
+            let mut selected: Vec<NaiveDate> = self
+                .by_set_pos
+                .iter()
+                .map(|pos| if *pos > 0 { pos - 1 } else { len + pos })
+                .filter(|i| (0..len).contains(i))
+                .map(|i| dates[i as usize])
+                .collect();
+            selected.sort();
+            selected.dedup();
+            dates = selected;
+        }
+        (dates, first_day)
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        let join = |values: Vec<String>| values.join(",");
+        if !self.by_day.is_empty() {
+            let days = self
+                .by_day
+                .iter()
+                .map(|d| {
+                    let name = WEEKDAYS.iter().find(|(_, w)| *w == d.weekday).unwrap().0;
+                    match d.ordinal {
+                        Some(n) => format!("{}{}", n, name),
+                        None => name.to_string(),
+                    }
+                })
+                .collect();
+            write!(f, ";BYDAY={}", join(days))?;
+        }
+        if !self.by_month_day.is_empty() {
+            write!(f, ";BYMONTHDAY={}", join(self.by_month_day.iter().map(|d| d.to_string()).collect()))?;
+        }
+        if !self.by_set_pos.is_empty() {
+            write!(f, ";BYSETPOS={}", join(self.by_set_pos.iter().map(|p| p.to_string()).collect()))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+        Ok(())
+    }
+}
+
+/// RecurrenceRule::occurrences 返回的迭代器
+pub struct Occurrences {
+    rule: RecurrenceRule,
+    start: NaiveDate,
+    period: u32,
+    buffer: VecDeque<NaiveDate>,
+    emitted: u32,
+    empty_periods: u32,
+    done: bool,
+}
+
+impl Iterator for Occurrences {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<NaiveDate> {
+        while !self.done {
+            if let Some(date) = self.buffer.pop_front() {
+                if self.rule.until.is_some_and(|until| date > until)
+                    || self.rule.count.is_some_and(|count| self.emitted >= count)
+                {
+                    self.done = true;
+                    return None;
+                }
+                self.emitted += 1;
+                return Some(date);
+            }
+
+            let (dates, first_day) = self.rule.period_dates(self.start, self.period);
+            self.period += 1;
+            if self.rule.until.is_some_and(|until| first_day > until) || first_day.year() > 9000 {
+                self.done = true;
+                break;
+            }
+            self.buffer.extend(dates.into_iter().filter(|date| *date >= self.start));
+            if self.buffer.is_empty() {
+                self.empty_periods += 1;
+                self.done = self.empty_periods >= MAX_EMPTY_PERIODS;
+            } else {
+                self.empty_periods = 0;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn expand(rule: &str, start: NaiveDate, n: usize) -> Vec<NaiveDate> {
+        RecurrenceRule::parse(rule).unwrap().occurrences(start).take(n).collect()
+    }
+
+    #[test]
+    fn test_daily_and_weekly() {
+        assert_eq!(
+            expand("FREQ=DAILY;INTERVAL=3", date(2026, 2, 26), 3),
+            vec![date(2026, 2, 26), date(2026, 3, 1), date(2026, 3, 4)]
+        );
+        // 2026-10-14 是周三：本周的周一已经过去，从周五开始
+        assert_eq!(
+            expand("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR", date(2026, 10, 14), 4),
+            vec![date(2026, 10, 16), date(2026, 10, 26), date(2026, 10, 30), date(2026, 11, 9)]
+        );
+        assert_eq!(
+            expand("FREQ=WEEKLY", date(2026, 10, 14), 2),
+            vec![date(2026, 10, 14), date(2026, 10, 21)]
+        );
+    }
+
+    #[test]
+    fn test_monthly_month_end_and_last_business_day() {
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=-1", date(2026, 1, 15), 3),
+            vec![date(2026, 1, 31), date(2026, 2, 28), date(2026, 3, 31)]
+        );
+        // 没有 31 日的月份跳过
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=31", date(2026, 1, 1), 3),
+            vec![date(2026, 1, 31), date(2026, 3, 31), date(2026, 5, 31)]
+        );
+        // 2026-05-31 是周日、2026-10-31 是周六
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1", date(2026, 5, 1), 3),
+            vec![date(2026, 5, 29), date(2026, 6, 30), date(2026, 7, 31)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1", date(2026, 10, 1), 1),
+            vec![date(2026, 10, 30)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=-1FR", date(2026, 10, 1), 2),
+            vec![date(2026, 10, 30), date(2026, 11, 27)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=2MO", date(2026, 10, 1), 1),
+            vec![date(2026, 10, 12)]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY", date(2026, 1, 31), 3),
+            vec![date(2026, 1, 31), date(2026, 3, 31), date(2026, 5, 31)]
+        );
+    }
+
+    #[test]
+    fn test_count_until_and_yearly() {
+        assert_eq!(expand("FREQ=MONTHLY;BYMONTHDAY=1;COUNT=2", date(2026, 1, 1), 10).len(), 2);
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=15;UNTIL=20260415", date(2026, 1, 20), 10),
+            vec![date(2026, 2, 15), date(2026, 3, 15), date(2026, 4, 15)]
+        );
+        assert_eq!(
+            expand("FREQ=YEARLY", date(2024, 2, 29), 2),
+            vec![date(2024, 2, 29), date(2028, 2, 29)]
+        );
+        assert_eq!(expand("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=30", date(2026, 2, 1), 1), vec![]);
+    }
+
+    #[test]
+    fn test_parse_validation_and_display() {
+        for rule in [
+            "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;COUNT=10",
+            "FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20271231",
+        ] {
+            assert_eq!(RecurrenceRule::parse(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            RecurrenceRule::parse("RRULE:freq=monthly;until=20261231T235959Z").unwrap().until,
+            Some(date(2026, 12, 31))
+        );
+
+        for rule in [
+            "",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=MONTHLY;BYMONTHDAY=0",
+            "FREQ=MONTHLY;BYSETPOS=-1",
+            "FREQ=MONTHLY;COUNT=3;UNTIL=20261231",
+            "FREQ=MONTHLY;BYMONTH=1",
+            "FREQ=DAILY;INTERVAL=0",
+        ] {
+            assert!(RecurrenceRule::parse(rule).is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_is_occurrence() {
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY;BYMONTHDAY=-1").unwrap();
+        assert!(rule.is_occurrence(date(2026, 1, 1), date(2026, 2, 28)));
+        assert!(!rule.is_occurrence(date(2026, 1, 1), date(2026, 2, 27)));
+    }
+}
@@ -54,7 +54,7 @@ pub struct PaginationMeta {
 
 impl PaginationMeta {
     pub fn new(total: u64, page: u64, page_size: u64) -> Self {
-        let total_pages = (total + page_size - 1) / page_size;
+        let total_pages = total.div_ceil(page_size);
         Self {
             total,
             page,
@@ -0,0 +1,100 @@
+// 测试工具 - 各服务单元测试共用的数据构造器，通过 test-util 特性对其他 crate 开放
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::models::{Transaction, TransactionSplit};
+
+/// 测试用交易构造器：默认是用户 user 在账户 acc 上 2024-03-01 12:00 (UTC) 的一笔已确认人民币交易
+pub struct TransactionBuilder {
+    tx: Transaction,
+}
+
+impl TransactionBuilder {
+    pub fn new(transaction_type: &str, amount: f64) -> Self {
+        let date = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        Self {
+            tx: Transaction {
+                id: None,
+                user_id: "user".to_string(),
+                transaction_type: transaction_type.to_string(),
+                amount,
+                currency: "CNY".to_string(),
+                account_id: "acc".to_string(),
+                to_account_id: None,
+                to_amount: None,
+                exchange_rate: None,
+                category_id: "other_expense".to_string(),
+                subcategory_id: None,
+                splits: None,
+                tags: None,
+                description: String::new(),
+                payee: None,
+                transaction_date: date,
+                location: None,
+                attachments: None,
+                dedup_hash: None,
+                duplicate_of: None,
+                external_id: None,
+                status: "confirmed".to_string(),
+                notes: None,
+                created_at: date,
+                updated_at: date,
+                created_by: "user".to_string(),
+            },
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.tx.id = Some(id.to_string());
+        self
+    }
+
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.tx.user_id = user_id.to_string();
+        self.tx.created_by = user_id.to_string();
+        self
+    }
+
+    pub fn account(mut self, account_id: &str) -> Self {
+        self.tx.account_id = account_id.to_string();
+        self
+    }
+
+    /// 转入账户；to_amount 为空表示同币种转账
+    pub fn transfer_to(mut self, to_account_id: &str, to_amount: Option<f64>) -> Self {
+        self.tx.to_account_id = Some(to_account_id.to_string());
+        self.tx.to_amount = to_amount;
+        self
+    }
+
+    pub fn category(mut self, category_id: &str) -> Self {
+        self.tx.category_id = category_id.to_string();
+        self
+    }
+
+    pub fn splits(mut self, splits: Vec<TransactionSplit>) -> Self {
+        self.tx.splits = Some(splits);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.tx.description = description.to_string();
+        self
+    }
+
+    /// 交易日期，创建和更新时间随之设置
+    pub fn date(mut self, date: DateTime<Utc>) -> Self {
+        self.tx.transaction_date = date;
+        self.tx.created_at = date;
+        self.tx.updated_at = date;
+        self
+    }
+
+    pub fn status(mut self, status: &str) -> Self {
+        self.tx.status = status.to_string();
+        self
+    }
+
+    pub fn build(self) -> Transaction {
+        self.tx
+    }
+}
@@ -0,0 +1,56 @@
+// 时区 - 使用 chrono-tz 内置的 IANA 时区库，读取用户设置中的时区
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOneOptions,
+    Database,
+};
+
+use crate::Result;
+
+/// IANA 时区，例如 Asia/Shanghai、America/New_York
+pub use chrono_tz::Tz;
+
+/// 时区名是否可用
+pub fn is_valid_timezone(name: &str) -> bool {
+    name.parse::<Tz>().is_ok()
+}
+
+/// 读取用户设置中的时区；用户不存在或时区无法识别时回退到 UTC
+pub async fn user_timezone(db: &Database, user_id: &str) -> Result<Tz> {
+    let options = FindOneOptions::builder()
+        .projection(doc! { "settings.timezone": 1 })
+        .build();
+    let timezone = db
+        .collection::<Document>("users")
+        .find_one(doc! { "_id": user_id }, options)
+        .await?
+        .and_then(|user| {
+            user.get_document("settings")
+                .ok()
+                .and_then(|settings| settings.get_str("timezone").ok())
+                .map(str::to_string)
+        });
+
+    Ok(match timezone {
+        Some(name) => name.parse().unwrap_or_else(|e| {
+            tracing::warn!("Falling back to UTC for user {}: {}", user_id, e);
+            Tz::UTC
+        }),
+        None => Tz::UTC,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validates_iana_names() {
+        assert!(!is_valid_timezone(""));
+        assert!(!is_valid_timezone("../etc/passwd"));
+        assert!(!is_valid_timezone("Mars/Olympus_Mons"));
+        assert!(is_valid_timezone("UTC"));
+        assert!(is_valid_timezone("Asia/Shanghai"));
+        assert!(is_valid_timezone("America/New_York"));
+    }
+}
@@ -0,0 +1,365 @@
+// 账户删除 - 删除前把交易转移到另一个账户或级联删除，并同步相关账户余额、预算与对账记录
+use common::{
+    commit_or_abort, delete_balance_snapshots, Account, BalanceSnapshot, Budget, DatabaseConnection, Error,
+    Reconciliation, RecurringTransaction, Result, Transaction, ACCOUNT_STATUS_ARCHIVED, SNAPSHOT_AUTO,
+};
+use mongodb::{
+    bson::{self, doc},
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    ClientSession, Database,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 账户仍有交易时的处理方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeleteMode {
+    /// 交易转移到同币种的目标账户
+    Reassign(String),
+    /// 交易随账户一起删除
+    Cascade,
+}
+
+impl DeleteMode {
+    pub fn parse(mode: &str, target_account_id: Option<String>) -> Result<Self> {
+        match (mode, target_account_id) {
+            ("reassign", Some(target)) => Ok(Self::Reassign(target)),
+            ("reassign", None) => Err(Error::InvalidInput("target_account_id is required for reassign".to_string())),
+            ("cascade", _) => Ok(Self::Cascade),
+            _ => Err(Error::InvalidInput("mode must be reassign or cascade".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountDeletion {
+    pub account_id: String,
+    pub transactions_removed: u64,
+    pub transactions_reassigned: u64,
+    pub budgets_updated: u64,
+}
+
+/// 删除计划：要删除的交易，以及其他账户余额的变动
+#[derive(Debug, Default)]
+struct DeletionPlan {
+    removed: Vec<Transaction>,
+    reassigned: u64,
+    balance_deltas: HashMap<String, f64>,
+}
+
+/// 级联时冲销被删交易在其他账户上的影响；转移时把交易在被删账户上的影响记到目标账户，
+/// 两者之间的转账在转移后会变成自己转给自己，直接删除并冲销其在目标账户上的影响
+fn plan(account_id: &str, transactions: &[Transaction], mode: &DeleteMode) -> DeletionPlan {
+    let mut plan = DeletionPlan::default();
+    for tx in transactions {
+        let effects = tx.balance_effects();
+        let target = match mode {
+            DeleteMode::Reassign(target) if !effects.iter().any(|(id, _)| id == target) => target,
+            _ => {
+                for (id, delta) in effects.iter().filter(|(id, _)| *id != account_id) {
+                    *plan.balance_deltas.entry(id.to_string()).or_insert(0.0) -= delta;
+                }
+                plan.removed.push(tx.clone());
+                continue;
+            }
+        };
+        for (_, delta) in effects.iter().filter(|(id, _)| *id == account_id) {
+            *plan.balance_deltas.entry(target.clone()).or_insert(0.0) += delta;
+        }
+        plan.reassigned += 1;
+    }
+    plan.balance_deltas.retain(|_, delta| delta.abs() > f64::EPSILON);
+    plan
+}
+
+async fn load_transactions(
+    db: &Database,
+    session: &mut ClientSession,
+    account: &Account,
+) -> Result<Vec<Transaction>> {
+    let account_id = account.id.as_deref().unwrap_or_default();
+    let mut cursor = db
+        .collection::<Transaction>("transactions")
+        .find_with_session(
+            doc! {
+                "user_id": &account.user_id,
+                "$or": [{ "account_id": account_id }, { "to_account_id": account_id }],
+            },
+            None,
+            session,
+        )
+        .await?;
+
+    let mut transactions = Vec::new();
+    while cursor.advance(session).await? {
+        transactions.push(cursor.deserialize_current()?);
+    }
+    Ok(transactions)
+}
+
+/// 转移的目标账户必须属于同一用户、币种相同且未归档
+async fn check_target(db: &Database, session: &mut ClientSession, account: &Account, target_id: &str) -> Result<()> {
+    if account.id.as_deref() == Some(target_id) {
+        return Err(Error::InvalidInput("Cannot reassign transactions to the account being deleted".to_string()));
+    }
+    let target = db
+        .collection::<Account>("accounts")
+        .find_one_with_session(doc! { "_id": target_id, "user_id": &account.user_id }, None, session)
+        .await?
+        .ok_or_else(|| Error::NotFound("Target account not found".to_string()))?;
+    if target.status == ACCOUNT_STATUS_ARCHIVED {
+        return Err(Error::InvalidInput("Cannot reassign transactions to an archived account".to_string()));
+    }
+    if target.currency != account.currency {
+        return Err(Error::InvalidInput("Target account must use the same currency".to_string()));
+    }
+    Ok(())
+}
+
+/// 删除交易后冲减对应预算的已用金额，拆分交易只冲减属于预算分类的拆分行
+async fn revert_budgets(db: &Database, session: &mut ClientSession, tx: &Transaction) -> Result<()> {
+    let collection = db.collection::<Budget>("budgets");
+    let category_ids: Vec<&str> = tx.category_amounts().into_iter().map(|(category_id, _)| category_id).collect();
+    let filter = doc! {
+        "user_id": &tx.user_id,
+        "category_ids": { "$in": category_ids },
+        "start_date": { "$lte": bson::to_bson(&tx.transaction_date).unwrap() },
+        "end_date": { "$gte": bson::to_bson(&tx.transaction_date).unwrap() },
+        "status": "active"
+    };
+
+    let mut cursor = collection.find_with_session(filter, None, session).await?;
+    let mut budgets: Vec<Budget> = Vec::new();
+    while cursor.advance(session).await? {
+        budgets.push(cursor.deserialize_current()?);
+    }
+
+    for mut budget in budgets {
+        budget.spent -= tx.amount_in_categories(&budget.category_ids);
+        budget.remaining = budget.amount - budget.spent;
+        budget.progress = (budget.spent / budget.amount) * 100.0;
+        budget.updated_at = chrono::Utc::now();
+
+        collection
+            .replace_one_with_session(doc! { "_id": budget.id.as_ref().unwrap() }, &budget, None, session)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 在删除事务内读取交易、确定处理方式并生成计划后执行，计划与写入基于同一快照，
+/// 期间并发写入的交易会使事务冲突失败，而不是被漏掉
+async fn execute(
+    db: &Database,
+    session: &mut ClientSession,
+    account: &Account,
+    mode: Option<DeleteMode>,
+    snapshots: &mut Vec<BalanceSnapshot>,
+) -> Result<AccountDeletion> {
+    let account_id = account.id.as_deref().unwrap_or_default();
+    let existing = load_transactions(db, session, account).await?;
+    let mode = match mode {
+        Some(mode) => mode,
+        None if existing.is_empty() => DeleteMode::Cascade,
+        None => {
+            return Err(Error::Conflict(format!(
+                "Account has {} transactions; archive it, or delete with mode=reassign or mode=cascade",
+                existing.len()
+            )))
+        }
+    };
+    if let DeleteMode::Reassign(target) = &mode {
+        check_target(db, session, account, target).await?;
+    }
+    let plan = plan(account_id, &existing, &mode);
+
+    let transactions = db.collection::<Transaction>("transactions");
+    let accounts = db.collection::<Account>("accounts");
+    let budgets = db.collection::<Budget>("budgets");
+    let recurring = db.collection::<RecurringTransaction>("recurring_transactions");
+
+    if !plan.removed.is_empty() {
+        let ids: Vec<&str> = plan.removed.iter().filter_map(|tx| tx.id.as_deref()).collect();
+        transactions
+            .delete_many_with_session(doc! { "user_id": &account.user_id, "_id": { "$in": ids } }, None, session)
+            .await?;
+        for tx in plan.removed.iter().filter(|tx| tx.transaction_type == "expense") {
+            revert_budgets(db, session, tx).await?;
+        }
+    }
+
+    // 剩下的交易都转移到目标账户
+    if let DeleteMode::Reassign(target) = &mode {
+        transactions
+            .update_many_with_session(
+                doc! { "user_id": &account.user_id, "account_id": account_id },
+                doc! { "$set": { "account_id": target } },
+                None,
+                session,
+            )
+            .await?;
+        transactions
+            .update_many_with_session(
+                doc! { "user_id": &account.user_id, "to_account_id": account_id },
+                doc! { "$set": { "to_account_id": target } },
+                None,
+                session,
+            )
+            .await?;
+        budgets
+            .update_many_with_session(
+                doc! { "user_id": &account.user_id, "account_ids": account_id },
+                doc! { "$addToSet": { "account_ids": target } },
+                None,
+                session,
+            )
+            .await?;
+        for field in ["template.account_id", "template.to_account_id"] {
+            recurring
+                .update_many_with_session(
+                    doc! { "user_id": &account.user_id, field: account_id },
+                    doc! { "$set": { field: target } },
+                    None,
+                    session,
+                )
+                .await?;
+        }
+    }
+
+    // 级联删除时引用该账户的周期性交易一并删除，已生成的交易按上面的计划处理
+    recurring
+        .delete_many_with_session(
+            doc! {
+                "user_id": &account.user_id,
+                "$or": [{ "template.account_id": account_id }, { "template.to_account_id": account_id }],
+            },
+            None,
+            session,
+        )
+        .await?;
+
+    let budgets_updated = budgets
+        .update_many_with_session(
+            doc! { "user_id": &account.user_id, "account_ids": account_id },
+            doc! { "$pull": { "account_ids": account_id } },
+            None,
+            session,
+        )
+        .await?
+        .modified_count;
+
+    let now = bson::to_bson(&chrono::Utc::now()).unwrap();
+    for (id, delta) in &plan.balance_deltas {
+        let updated = accounts
+            .find_one_and_update_with_session(
+                doc! { "_id": id, "user_id": &account.user_id },
+                doc! { "$inc": { "current_balance": delta }, "$set": { "updated_at": now.clone() } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+                session,
+            )
+            .await?;
+        // 转账的另一方可能已被删除，此时无余额可调整
+        if let Some(updated) = updated {
+            snapshots.push(BalanceSnapshot::of(&updated, SNAPSHOT_AUTO));
+        }
+    }
+
+    db.collection::<Reconciliation>("reconciliations")
+        .delete_one_with_session(doc! { "_id": account_id }, None, session)
+        .await?;
+    let deleted = accounts
+        .delete_one_with_session(doc! { "_id": account_id, "user_id": &account.user_id }, None, session)
+        .await?;
+    if deleted.deleted_count == 0 {
+        return Err(Error::NotFound("Account not found".to_string()));
+    }
+
+    Ok(AccountDeletion {
+        account_id: account_id.to_string(),
+        transactions_removed: plan.removed.len() as u64,
+        transactions_reassigned: plan.reassigned,
+        budgets_updated,
+    })
+}
+
+/// 删除账户；账户仍有交易且未指定处理方式时拒绝删除，建议改为归档
+pub async fn delete_account(
+    db: &DatabaseConnection,
+    account: &Account,
+    mode: Option<DeleteMode>,
+) -> Result<AccountDeletion> {
+    let account_id = account.id.clone().unwrap_or_default();
+    let mut snapshots = Vec::new();
+    let mut session = db.start_transaction().await?;
+    let result = execute(&db.mongo, &mut session, account, mode, &mut snapshots).await;
+    let deletion = commit_or_abort(&mut session, result).await?;
+
+    db.record_balance_history(&snapshots).await;
+    if let Some(pool) = &db.pg {
+        if let Err(e) = delete_balance_snapshots(pool, std::slice::from_ref(&account_id)).await {
+            tracing::warn!("Failed to delete balance history of account {}: {}", account_id, e);
+        }
+    }
+
+    Ok(deletion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_util::TransactionBuilder;
+
+    fn transaction(transaction_type: &str, account_id: &str, to_account_id: Option<&str>, amount: f64) -> Transaction {
+        let builder = TransactionBuilder::new(transaction_type, amount)
+            .id(&format!("{}-{}", transaction_type, amount))
+            .account(account_id);
+        match to_account_id {
+            Some(to_account_id) => builder.transfer_to(to_account_id, None),
+            None => builder,
+        }
+        .build()
+    }
+
+    fn sample() -> Vec<Transaction> {
+        vec![
+            transaction("income", "old", None, 500.0),
+            transaction("expense", "old", None, 120.0),
+            transaction("transfer", "old", Some("savings"), 100.0),
+            transaction("transfer", "wallet", Some("old"), 30.0),
+        ]
+    }
+
+    #[test]
+    fn test_cascade_reverts_counterparty_balances() {
+        let plan = plan("old", &sample(), &DeleteMode::Cascade);
+        assert_eq!(plan.removed.len(), 4);
+        assert_eq!(plan.reassigned, 0);
+        assert_eq!(plan.balance_deltas.len(), 2);
+        assert_eq!(plan.balance_deltas["savings"], -100.0);
+        assert_eq!(plan.balance_deltas["wallet"], 30.0);
+    }
+
+    #[test]
+    fn test_reassign_moves_effects_and_drops_self_transfers() {
+        let plan = plan("old", &sample(), &DeleteMode::Reassign("savings".to_string()));
+        // 转入 savings 的那笔转账被删除，其余三笔转移
+        assert_eq!(plan.removed.len(), 1);
+        assert_eq!(plan.reassigned, 3);
+        // +500 - 120 + 30 记到 savings，再冲销被删转账的 +100
+        assert_eq!(plan.balance_deltas.len(), 1);
+        assert_eq!(plan.balance_deltas["savings"], 310.0);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(DeleteMode::parse("cascade", None).unwrap(), DeleteMode::Cascade);
+        assert_eq!(
+            DeleteMode::parse("reassign", Some("a".to_string())).unwrap(),
+            DeleteMode::Reassign("a".to_string())
+        );
+        assert!(DeleteMode::parse("reassign", None).is_err());
+        assert!(DeleteMode::parse("purge", None).is_err());
+    }
+}
@@ -1,13 +1,26 @@
 use axum::{
-    extract::{Path, Query, State, Extension},
+    extract::{Path, Query, State},
     Json,
 };
-use common::{Account, ApiResponse, PaginationResponse, PaginationMeta, Claims, Error, Result};
-use mongodb::{bson::{doc, oid::ObjectId}, options::FindOptions};
+use chrono::Utc;
+use common::{
+    Account, ApiResponse, Calendar, BalanceSnapshot, PaginationResponse, PaginationMeta, Error, Reconciliation, Result,
+    ACCOUNT_STATUS_ACTIVE, ACCOUNT_STATUS_ARCHIVED, SNAPSHOT_MANUAL,
+};
+use common::middleware::{scope, Authorized};
+use mongodb::{
+    bson::{doc, oid::ObjectId},
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
+use crate::{
+    deletion::{self, AccountDeletion, DeleteMode},
+    reconcile::{self, StatementMode},
+    service::{self, BalanceInterval, BalancePoint},
+    AppState,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateAccountRequest {
@@ -38,6 +51,63 @@ pub struct ListQuery {
     page: u64,
     #[serde(default = "default_page_size")]
     page_size: u64,
+    /// 按状态过滤，如 active、archived
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountQuery {
+    /// 账户仍有交易时必填：reassign 转移到 target_account_id，cascade 一并删除
+    pub mode: Option<String>,
+    pub target_account_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BalanceQuery {
+    /// 查询该时刻的历史余额 (YYYY-MM-DD 表示用户时区的当天日终，或 RFC3339)
+    pub as_of: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BalanceHistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BalanceHistoryResponse {
+    pub account_id: String,
+    pub currency: String,
+    pub interval: String,
+    pub points: Vec<BalancePoint>,
+}
+
+#[derive(Deserialize)]
+pub struct ReconciliationQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StatementRequest {
+    pub statement_balance: f64,
+    /// 对账单日期：YYYY-MM-DD (包含当天，按用户时区) 或 RFC3339 时刻 (不含)，缺省为当前时间
+    pub statement_date: Option<String>,
+    /// correct: 修改期初余额；adjustment: 生成调整交易
+    #[serde(default = "default_statement_mode")]
+    pub mode: String,
+}
+
+fn default_statement_mode() -> String { "adjustment".to_string() }
+
+async fn find_account(state: &AppState, user_id: &str, id: &str) -> Result<Account> {
+    state
+        .db
+        .mongo
+        .collection::<Account>("accounts")
+        .find_one(doc! { "_id": id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Account not found".to_string()))
 }
 
 fn default_page() -> u64 { 1 }
@@ -45,12 +115,15 @@ fn default_page_size() -> u64 { 10 }
 
 pub async fn list_accounts(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<ApiResponse<PaginationResponse<Account>>>> {
     let collection = state.db.mongo.collection::<Account>("accounts");
     
-    let filter = doc! { "user_id": &claims.user_id };
+    let mut filter = doc! { "user_id": &claims.user_id };
+    if let Some(status) = &query.status {
+        filter.insert("status", status);
+    }
     let total = collection.count_documents(filter.clone(), None).await?;
     
     let skip = (query.page - 1) * query.page_size;
@@ -74,7 +147,7 @@ pub async fn list_accounts(
 
 pub async fn create_account(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
     Json(req): Json<CreateAccountRequest>,
 ) -> Result<Json<ApiResponse<Account>>> {
     let account = Account {
@@ -98,13 +171,17 @@ pub async fn create_account(
     
     let collection = state.db.mongo.collection::<Account>("accounts");
     collection.insert_one(&account, None).await?;
+    state
+        .db
+        .record_balance_history(&[BalanceSnapshot::of(&account, SNAPSHOT_MANUAL)])
+        .await;
     
     Ok(Json(ApiResponse::success(account)))
 }
 
 pub async fn get_account(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<Account>>> {
     let collection = state.db.mongo.collection::<Account>("accounts");
@@ -118,7 +195,7 @@ pub async fn get_account(
 
 pub async fn update_account(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
     Path(id): Path<String>,
     Json(req): Json<UpdateAccountRequest>,
 ) -> Result<Json<ApiResponse<Account>>> {
@@ -155,42 +232,125 @@ pub async fn update_account(
     Ok(Json(ApiResponse::success(updated)))
 }
 
+/// 删除账户；仍有交易时需指定 mode，否则返回 409 并建议归档
 pub async fn delete_account(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
     Path(id): Path<String>,
-) -> Result<Json<ApiResponse<()>>> {
-    let collection = state.db.mongo.collection::<Account>("accounts");
-    let result = collection
-        .delete_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
-        .await?;
-    
-    if result.deleted_count == 0 {
-        return Err(Error::NotFound("Account not found".to_string()));
-    }
-    
-    Ok(Json(ApiResponse::success(())))
+    Query(query): Query<DeleteAccountQuery>,
+) -> Result<Json<ApiResponse<AccountDeletion>>> {
+    let account = find_account(&state, &claims.user_id, &id).await?;
+    let mode = query
+        .mode
+        .as_deref()
+        .map(|mode| DeleteMode::parse(mode, query.target_account_id.clone()))
+        .transpose()?;
+
+    let result = deletion::delete_account(&state.db, &account, mode).await?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+async fn set_status(state: &AppState, user_id: &str, id: &str, status: &str) -> Result<Account> {
+    state
+        .db
+        .mongo
+        .collection::<Account>("accounts")
+        .find_one_and_update(
+            doc! { "_id": id, "user_id": user_id },
+            doc! { "$set": {
+                "status": status,
+                "updated_at": mongodb::bson::to_bson(&chrono::Utc::now()).unwrap(),
+            } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await?
+        .ok_or_else(|| Error::NotFound("Account not found".to_string()))
+}
+
+/// 归档账户：保留历史交易，但不再计入总资产，也不能再记账
+pub async fn archive_account(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Account>>> {
+    let account = set_status(&state, &claims.user_id, &id, ACCOUNT_STATUS_ARCHIVED).await?;
+    Ok(Json(ApiResponse::success(account)))
+}
+
+pub async fn unarchive_account(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Account>>> {
+    let account = set_status(&state, &claims.user_id, &id, ACCOUNT_STATUS_ACTIVE).await?;
+    Ok(Json(ApiResponse::success(account)))
 }
 
 pub async fn get_balance(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
     Path(id): Path<String>,
+    Query(query): Query<BalanceQuery>,
 ) -> Result<Json<ApiResponse<f64>>> {
-    let oid = ObjectId::parse_str(&id).map_err(|_| Error::InvalidInput("Invalid account ID".to_string()))?;
-    
-    let collection = state.db.mongo.collection::<Account>("accounts");
-    let account = collection
-        .find_one(doc! { "_id": oid, "user_id": &claims.user_id }, None)
+    let account = find_account(&state, &claims.user_id, &id).await?;
+
+    let Some(as_of) = &query.as_of else {
+        return Ok(Json(ApiResponse::success(account.current_balance)));
+    };
+
+    // 日期参数取用户时区的当天日终，即次日零点之前的最后一条快照
+    let as_of = Calendar::for_user(&state.db.mongo, &claims.user_id).await?.parse_bound(as_of, true)?;
+    let pool = service::history_pool(&state.db.pg)?;
+    let balance = service::balance_as_of(pool, &id, as_of)
         .await?
-        .ok_or_else(|| Error::NotFound("Account not found".to_string()))?;
-    
-    Ok(Json(ApiResponse::success(account.current_balance)))
+        .ok_or_else(|| Error::NotFound("No balance recorded before the requested time".to_string()))?;
+
+    Ok(Json(ApiResponse::success(balance)))
+}
+
+pub async fn get_balance_history(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
+    Path(id): Path<String>,
+    Query(query): Query<BalanceHistoryQuery>,
+) -> Result<Json<ApiResponse<BalanceHistoryResponse>>> {
+    let account = find_account(&state, &claims.user_id, &id).await?;
+
+    let interval = query.interval.unwrap_or_else(|| "day".to_string());
+    let bucket = BalanceInterval::parse(&interval)?;
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let to = match &query.to {
+        Some(to) => calendar.parse_bound(to, true)?,
+        None => Utc::now(),
+    };
+    let from = match &query.from {
+        Some(from) => calendar.parse_bound(from, false)?,
+        None => match bucket {
+            BalanceInterval::Day => to - chrono::Duration::days(30),
+            BalanceInterval::Month => to - chrono::Duration::days(365),
+        },
+    };
+    if from >= to {
+        return Err(Error::InvalidInput("from must be earlier than to".to_string()));
+    }
+
+    let pool = service::history_pool(&state.db.pg)?;
+    let points = service::balance_history(pool, &id, from, to, bucket).await?;
+
+    Ok(Json(ApiResponse::success(BalanceHistoryResponse {
+        account_id: id,
+        currency: account.currency,
+        interval,
+        points,
+    })))
 }
 
 pub async fn get_account_transactions(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
     Path(id): Path<String>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<ApiResponse<PaginationResponse<common::Transaction>>>> {
@@ -227,3 +387,61 @@ pub async fn get_account_transactions(
         pagination: PaginationMeta::new(total, query.page, query.page_size),
     })))
 }
+
+pub async fn list_reconciliations(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
+    Query(query): Query<ReconciliationQuery>,
+) -> Result<Json<ApiResponse<Vec<Reconciliation>>>> {
+    let mut filter = doc! { "user_id": &claims.user_id };
+    if let Some(status) = &query.status {
+        filter.insert("status", status);
+    }
+
+    let options = FindOptions::builder().sort(doc! { "checked_at": -1 }).build();
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<Reconciliation>("reconciliations")
+        .find(filter, options)
+        .await?;
+
+    let mut results = Vec::new();
+    while cursor.advance().await? {
+        results.push(cursor.deserialize_current()?);
+    }
+
+    Ok(Json(ApiResponse::success(results)))
+}
+
+/// 立即对账户执行一次对账
+pub async fn check_reconciliation(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Reconciliation>>> {
+    let account = find_account(&state, &claims.user_id, &id).await?;
+    let result = reconcile::reconcile_account(&state.db, &account).await?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
+
+/// 按银行对账单余额修正账户
+pub async fn reconcile_statement(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<StatementRequest>,
+) -> Result<Json<ApiResponse<Reconciliation>>> {
+    let account = find_account(&state, &claims.user_id, &id).await?;
+    let mode = StatementMode::parse(&req.mode)?;
+    // 日期形式的对账单日期包含当天全部交易，截止到用户时区的次日零点
+    let statement_date = match &req.statement_date {
+        Some(date) => Calendar::for_user(&state.db.mongo, &claims.user_id).await?.parse_bound(date, true)?,
+        None => Utc::now(),
+    };
+
+    let result = reconcile::apply_statement(&state.db, &account, req.statement_balance, statement_date, mode).await?;
+
+    Ok(Json(ApiResponse::success(result)))
+}
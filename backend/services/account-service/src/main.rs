@@ -1,4 +1,6 @@
+mod deletion;
 mod handlers;
+mod reconcile;
 mod service;
 
 use axum::{
@@ -6,14 +8,15 @@ use axum::{
     Router,
     middleware,
 };
-use common::{DatabaseConnection, middleware::auth_middleware};
+use common::{DatabaseConnection, JwtManager, KeyRing, middleware::{auth_middleware, AuthState, RateLimit, RateLimitLayer}};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub struct AppState {
     pub db: DatabaseConnection,
 }
 
-pub fn create_router(db: DatabaseConnection) -> Router {
+pub fn create_router(db: DatabaseConnection, jwt: Arc<JwtManager>) -> Router {
     let state = Arc::new(AppState { db: db.clone() });
     
     Router::new()
@@ -22,9 +25,17 @@ pub fn create_router(db: DatabaseConnection) -> Router {
         .route("/accounts/:id", get(handlers::get_account))
         .route("/accounts/:id", put(handlers::update_account))
         .route("/accounts/:id", delete(handlers::delete_account))
+        .route("/accounts/:id/archive", post(handlers::archive_account))
+        .route("/accounts/:id/unarchive", post(handlers::unarchive_account))
         .route("/accounts/:id/balance", get(handlers::get_balance))
+        .route("/accounts/:id/balance/history", get(handlers::get_balance_history))
         .route("/accounts/:id/transactions", get(handlers::get_account_transactions))
-        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware))
+        .route("/accounts/reconciliations", get(handlers::list_reconciliations))
+        .route("/accounts/:id/reconciliation", get(handlers::check_reconciliation))
+        .route("/accounts/:id/reconcile", post(handlers::reconcile_statement))
+        .layer(RateLimitLayer::per_user(&db.redis, RateLimit::global_user()))
+        .layer(middleware::from_fn_with_state(AuthState::new(jwt.clone(), db.clone()), auth_middleware))
+        .layer(RateLimitLayer::per_ip(&db.redis, RateLimit::global_ip()))
         .with_state(state)
 }
 
@@ -32,6 +43,8 @@ pub fn create_router(db: DatabaseConnection) -> Router {
 async fn main() {
     tracing_subscriber::fmt::init();
     
+    let jwt = Arc::new(JwtManager::new(KeyRing::from_env().expect("Failed to load JWT keys")));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
@@ -40,7 +53,13 @@ async fn main() {
         .await
         .expect("Failed to connect to database");
     
-    let app = create_router(db);
+    let reconcile_interval = std::env::var("RECONCILE_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(24 * 3600);
+    reconcile::spawn_reconciliation_job(db.clone(), std::time::Duration::from_secs(reconcile_interval));
+
+    let app = create_router(db, jwt);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3001")
         .await
@@ -48,7 +67,7 @@ async fn main() {
     
     tracing::info!("Account service listening on port 3001");
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
@@ -0,0 +1,364 @@
+// 余额对账 - 按交易流水重放计算账户应有余额，与记录的 current_balance 比较并修正
+use chrono::{DateTime, Utc};
+use common::{
+    commit_or_abort, Account, BalanceSnapshot, DatabaseConnection, Error, Reconciliation, Result, Transaction,
+    ADJUSTMENT_CATEGORY_KEY, SNAPSHOT_MANUAL,
+};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Document},
+    options::{FindOneAndUpdateOptions, ReplaceOptions, ReturnDocument},
+    ClientSession,
+};
+use std::time::Duration;
+
+/// 余额差异容忍度 (半分)
+const BALANCE_TOLERANCE: f64 = 0.005;
+
+/// 按对账单修正余额的方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementMode {
+    /// 修改期初余额，不产生交易
+    Correct,
+    /// 生成一笔显式的调整交易
+    Adjustment,
+}
+
+impl StatementMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "correct" => Ok(Self::Correct),
+            "adjustment" => Ok(Self::Adjustment),
+            _ => Err(Error::InvalidInput("mode must be correct or adjustment".to_string())),
+        }
+    }
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// 由期初余额与交易流水计算账户余额，until 不为空时只计入该时刻之前的交易 (不含 until)
+pub fn ledger_balance(account: &Account, transactions: &[Transaction], until: Option<DateTime<Utc>>) -> f64 {
+    let account_id = account.id.as_deref().unwrap_or_default();
+    let total: f64 = transactions
+        .iter()
+        .filter(|tx| until.is_none_or(|until| tx.transaction_date < until))
+        .flat_map(|tx| tx.balance_effects())
+        .filter(|(id, _)| *id == account_id)
+        .map(|(_, delta)| delta)
+        .sum();
+
+    round2(account.initial_balance + total)
+}
+
+fn transactions_filter(account: &Account) -> Document {
+    let account_id = account.id.as_deref().unwrap_or_default();
+    doc! {
+        "user_id": &account.user_id,
+        "$or": [{ "account_id": account_id }, { "to_account_id": account_id }],
+    }
+}
+
+async fn load_transactions(db: &DatabaseConnection, account: &Account) -> Result<Vec<Transaction>> {
+    let mut cursor = db
+        .mongo
+        .collection::<Transaction>("transactions")
+        .find(transactions_filter(account), None)
+        .await?;
+
+    let mut transactions = Vec::new();
+    while cursor.advance().await? {
+        transactions.push(cursor.deserialize_current()?);
+    }
+    Ok(transactions)
+}
+
+/// 在事务内读取账户及其交易，保证余额与流水来自同一快照
+async fn load_ledger(
+    db: &DatabaseConnection,
+    session: &mut ClientSession,
+    user_id: &str,
+    account_id: &str,
+) -> Result<(Account, Vec<Transaction>)> {
+    let account = db
+        .mongo
+        .collection::<Account>("accounts")
+        .find_one_with_session(doc! { "_id": account_id, "user_id": user_id }, None, session)
+        .await?
+        .ok_or_else(|| Error::NotFound("Account not found".to_string()))?;
+
+    let mut cursor = db
+        .mongo
+        .collection::<Transaction>("transactions")
+        .find_with_session(transactions_filter(&account), None, session)
+        .await?;
+    let mut transactions = Vec::new();
+    while cursor.advance(session).await? {
+        transactions.push(cursor.deserialize_current()?);
+    }
+    Ok((account, transactions))
+}
+
+async fn previous_result(db: &DatabaseConnection, account_id: &str) -> Result<Option<Reconciliation>> {
+    Ok(db
+        .mongo
+        .collection::<Reconciliation>("reconciliations")
+        .find_one(doc! { "_id": account_id }, None)
+        .await?)
+}
+
+async fn save_result(db: &DatabaseConnection, result: &Reconciliation) -> Result<()> {
+    db.mongo
+        .collection::<Reconciliation>("reconciliations")
+        .replace_one(
+            doc! { "_id": &result.id },
+            result,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// 重放账户的全部交易并与记录的余额比较，保存并返回对账结果
+pub async fn reconcile_account(db: &DatabaseConnection, account: &Account) -> Result<Reconciliation> {
+    let account_id = account.id.clone().unwrap_or_default();
+    let transactions = load_transactions(db, account).await?;
+    let ledger = ledger_balance(account, &transactions, None);
+    let difference = round2(account.current_balance - ledger);
+    let consistent = difference.abs() < BALANCE_TOLERANCE;
+
+    let now = Utc::now();
+    let previous = previous_result(db, &account_id).await?;
+    let previous_consistent_at = previous.and_then(|p| p.last_consistent_at);
+
+    let result = Reconciliation {
+        id: account_id.clone(),
+        user_id: account.user_id.clone(),
+        account_id: account_id.clone(),
+        recorded_balance: account.current_balance,
+        ledger_balance: ledger,
+        difference,
+        status: if consistent { "consistent" } else { "discrepancy" }.to_string(),
+        transaction_count: transactions.len() as u64,
+        period_start: previous_consistent_at.unwrap_or(account.created_at),
+        period_end: now,
+        last_consistent_at: if consistent { Some(now) } else { previous_consistent_at },
+        adjustment_transaction_id: None,
+        checked_at: now,
+    };
+
+    if !consistent {
+        tracing::warn!(
+            "Balance discrepancy on account {}: recorded {}, ledger {}, introduced between {} and {}",
+            account_id,
+            result.recorded_balance,
+            result.ledger_balance,
+            result.period_start,
+            result.period_end
+        );
+    }
+
+    save_result(db, &result).await?;
+    Ok(result)
+}
+
+/// 对所有账户执行对账，返回 (检查数, 差异数)
+pub async fn reconcile_all(db: &DatabaseConnection) -> Result<(u64, u64)> {
+    let mut cursor = db.mongo.collection::<Account>("accounts").find(doc! {}, None).await?;
+    let (mut checked, mut discrepancies) = (0, 0);
+
+    while cursor.advance().await? {
+        let account: Account = cursor.deserialize_current()?;
+        match reconcile_account(db, &account).await {
+            Ok(result) => {
+                checked += 1;
+                if result.status == "discrepancy" {
+                    discrepancies += 1;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to reconcile account {:?}: {}", account.id, e),
+        }
+    }
+    Ok((checked, discrepancies))
+}
+
+/// 启动定期对账任务
+pub fn spawn_reconciliation_job(db: DatabaseConnection, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match reconcile_all(&db).await {
+                Ok((checked, discrepancies)) => {
+                    tracing::info!("Reconciled {} accounts, {} discrepancies", checked, discrepancies)
+                }
+                Err(e) => tracing::warn!("Reconciliation job failed: {}", e),
+            }
+        }
+    });
+}
+
+/// 按对账单余额修正账户：statement_date (不含) 之前的流水余额与对账单的差额通过修改期初余额或调整交易补齐，
+/// 同时把 current_balance 重置为流水重放结果，消除累计误差。账户与流水在同一事务内读取，
+/// 余额用 $inc 写回，期间并发记账的交易不会被覆盖
+pub async fn apply_statement(
+    db: &DatabaseConnection,
+    account: &Account,
+    statement_balance: f64,
+    statement_date: DateTime<Utc>,
+    mode: StatementMode,
+) -> Result<Reconciliation> {
+    let account_id = account.id.clone().unwrap_or_default();
+    let previous_consistent_at = previous_result(db, &account_id).await?.and_then(|p| p.last_consistent_at);
+    let now = Utc::now();
+
+    let mut session = db.start_transaction().await?;
+    let result = async {
+        let (account, transactions) = load_ledger(db, &mut session, &account.user_id, &account_id).await?;
+        let difference = round2(statement_balance - ledger_balance(&account, &transactions, Some(statement_date)));
+        let needs_fix = difference.abs() >= BALANCE_TOLERANCE;
+        let correction = if needs_fix { difference } else { 0.0 };
+        let corrected_balance = round2(ledger_balance(&account, &transactions, None) + correction);
+
+        let adjustment = (needs_fix && mode == StatementMode::Adjustment).then(|| Transaction {
+            id: Some(ObjectId::new().to_hex()),
+            user_id: account.user_id.clone(),
+            transaction_type: if difference > 0.0 { "income" } else { "expense" }.to_string(),
+            amount: difference.abs(),
+            currency: account.currency.clone(),
+            account_id: account_id.clone(),
+            to_account_id: None,
+            to_amount: None,
+            exchange_rate: None,
+            category_id: ADJUSTMENT_CATEGORY_KEY.to_string(),
+            subcategory_id: None,
+            splits: None,
+            tags: None,
+            description: "对账调整".to_string(),
+            payee: None,
+            // 记在截止时刻前一秒，落在对账单的最后一天
+            transaction_date: statement_date - chrono::Duration::seconds(1),
+            location: None,
+            attachments: None,
+            dedup_hash: None,
+            duplicate_of: None,
+            external_id: None,
+            status: "confirmed".to_string(),
+            notes: Some(format!("Statement balance {:.2}", statement_balance)),
+            created_at: now,
+            updated_at: now,
+            created_by: account.user_id.clone(),
+        });
+        if let Some(adjustment) = &adjustment {
+            db.mongo
+                .collection::<Transaction>("transactions")
+                .insert_one_with_session(adjustment, None, &mut session)
+                .await?;
+        }
+
+        let mut inc = doc! { "current_balance": round2(corrected_balance - account.current_balance) };
+        if needs_fix && mode == StatementMode::Correct {
+            inc.insert("initial_balance", difference);
+        }
+        let updated = db
+            .mongo
+            .collection::<Account>("accounts")
+            .find_one_and_update_with_session(
+                doc! { "_id": &account_id, "user_id": &account.user_id },
+                doc! { "$inc": inc, "$set": { "updated_at": bson::to_bson(&now).unwrap() } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+                &mut session,
+            )
+            .await?
+            .ok_or_else(|| Error::NotFound("Account not found".to_string()))?;
+
+        let status = match (needs_fix, mode) {
+            (false, _) => "consistent",
+            (true, StatementMode::Correct) => "corrected",
+            (true, StatementMode::Adjustment) => "adjusted",
+        };
+        let result = Reconciliation {
+            id: account_id.clone(),
+            user_id: account.user_id.clone(),
+            account_id: account_id.clone(),
+            recorded_balance: account.current_balance,
+            ledger_balance: corrected_balance,
+            difference,
+            status: status.to_string(),
+            transaction_count: transactions.len() as u64 + adjustment.is_some() as u64,
+            period_start: previous_consistent_at.unwrap_or(account.created_at),
+            period_end: statement_date,
+            last_consistent_at: Some(now),
+            adjustment_transaction_id: adjustment.and_then(|tx| tx.id),
+            checked_at: now,
+        };
+        Ok::<_, Error>((updated, result))
+    }
+    .await;
+
+    let (updated, result) = commit_or_abort(&mut session, result).await?;
+    db.record_balance_history(&[BalanceSnapshot::of(&updated, SNAPSHOT_MANUAL)]).await;
+
+    save_result(db, &result).await?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use common::test_util::TransactionBuilder;
+
+    fn account(initial_balance: f64) -> Account {
+        let created = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        Account {
+            id: Some("acc".to_string()),
+            user_id: "user".to_string(),
+            name: "储蓄卡".to_string(),
+            account_type: "debit_card".to_string(),
+            currency: "CNY".to_string(),
+            initial_balance,
+            current_balance: initial_balance,
+            available_credit: None,
+            icon: "default".to_string(),
+            color: "#1890ff".to_string(),
+            description: None,
+            meta: None,
+            is_excluded_from_total: false,
+            status: "active".to_string(),
+            created_at: created,
+            updated_at: created,
+        }
+    }
+
+    fn transaction(transaction_type: &str, account_id: &str, amount: f64, day: u32) -> Transaction {
+        TransactionBuilder::new(transaction_type, amount)
+            .account(account_id)
+            .date(Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn test_ledger_balance_replays_transactions() {
+        let account = account(1000.0);
+
+        let mut incoming = transaction("transfer", "other", 300.0, 5);
+        incoming.to_account_id = Some("acc".to_string());
+        incoming.to_amount = Some(42.5);
+        let transactions = vec![
+            transaction("income", "acc", 200.0, 1),
+            transaction("expense", "acc", 50.25, 3),
+            incoming,
+            transaction("expense", "other", 999.0, 4),
+        ];
+
+        assert_eq!(ledger_balance(&account, &transactions, None), 1192.25);
+        // 截止到 3 月 4 日零点 (不含)，3 月 3 日的交易全部计入
+        let until = Utc.with_ymd_and_hms(2024, 3, 4, 0, 0, 0).unwrap();
+        assert_eq!(ledger_balance(&account, &transactions, Some(until)), 1149.75);
+        let until = Utc.with_ymd_and_hms(2024, 3, 3, 12, 0, 0).unwrap();
+        assert_eq!(ledger_balance(&account, &transactions, Some(until)), 1200.0);
+    }
+}
@@ -1 +1,94 @@
 // 服务层逻辑 - 可在此处添加复杂业务逻辑
+use chrono::{DateTime, Utc};
+use common::{Error, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// 余额历史的聚合粒度，对应 TimescaleDB 中的连续聚合视图
+#[derive(Debug, Clone, Copy)]
+pub enum BalanceInterval {
+    Day,
+    Month,
+}
+
+impl BalanceInterval {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "day" => Ok(Self::Day),
+            "month" => Ok(Self::Month),
+            _ => Err(Error::InvalidInput("interval must be day or month".to_string())),
+        }
+    }
+
+    fn view(&self) -> &'static str {
+        match self {
+            Self::Day => "account_balance_daily",
+            Self::Month => "account_balance_monthly",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalancePoint {
+    pub time: DateTime<Utc>,
+    pub open_balance: f64,
+    pub close_balance: f64,
+    pub min_balance: f64,
+    pub max_balance: f64,
+}
+
+pub fn history_pool(pg: &Option<PgPool>) -> Result<&PgPool> {
+    pg.as_ref()
+        .ok_or_else(|| Error::InternalServer("Balance history store is not configured".to_string()))
+}
+
+/// 查询 [from, to) 内按日/按月聚合的余额
+pub async fn balance_history(
+    pool: &PgPool,
+    account_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    interval: BalanceInterval,
+) -> Result<Vec<BalancePoint>> {
+    let query = format!(
+        "SELECT bucket, open_balance::float8, close_balance::float8, min_balance::float8, max_balance::float8
+         FROM {}
+         WHERE account_id = $1 AND bucket >= $2 AND bucket < $3
+         ORDER BY bucket",
+        interval.view()
+    );
+
+    let rows: Vec<(DateTime<Utc>, f64, f64, f64, f64)> = sqlx::query_as(&query)
+        .bind(account_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(time, open_balance, close_balance, min_balance, max_balance)| BalancePoint {
+            time,
+            open_balance,
+            close_balance,
+            min_balance,
+            max_balance,
+        })
+        .collect())
+}
+
+/// 查询 as_of 时刻的账户余额 (该时刻之前的最后一条快照)
+pub async fn balance_as_of(pool: &PgPool, account_id: &str, as_of: DateTime<Utc>) -> Result<Option<f64>> {
+    let row: Option<(f64,)> = sqlx::query_as(
+        "SELECT balance::float8 FROM account_balance_history
+         WHERE account_id = $1 AND time < $2
+         ORDER BY time DESC
+         LIMIT 1",
+    )
+    .bind(account_id)
+    .bind(as_of)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(balance,)| balance))
+}
@@ -1,9 +1,11 @@
 use axum::{
-    extract::{Path, Query, State, Extension},
+    extract::{Path, Query, State},
     Json,
 };
-use common::{Budget, Transaction, ApiResponse, PaginationResponse, PaginationMeta, Claims, Error, Result, BudgetPredictor, PredictionResult};
+use common::{Budget, Calendar, Period, Transaction, ApiResponse, PaginationResponse, PaginationMeta, Error, Result, BudgetPredictor, PredictionResult};
+use common::middleware::{scope, Authorized};
 use mongodb::{bson::{self, doc, oid::ObjectId}, options::FindOptions};
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
@@ -14,17 +16,20 @@ pub struct UpdateBudgetRequest {
     pub category_id: String,
     pub amount: f64,
     pub period: String,
-    pub start_date: String,
-    pub end_date: String,
+    /// 未提供时保留原有起止时间
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateBudgetRequest {
     pub category_id: String,
     pub amount: f64,
+    /// daily / weekly / monthly / yearly
     pub period: String,
-    pub start_date: String,
-    pub end_date: String,
+    /// 未提供时取用户时区下包含当前时刻的那个周期
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -38,9 +43,43 @@ pub struct ListQuery {
 fn default_page() -> u64 { 1 }
 fn default_page_size() -> u64 { 10 }
 
+fn parse_period(period: &str) -> Result<Period> {
+    Period::from_budget_type(period)
+        .ok_or_else(|| Error::InvalidInput("period must be daily, weekly, monthly or yearly".to_string()))
+}
+
+fn parse_date(value: &str, field: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(|_| Error::InvalidInput(format!("Invalid {} format", field)))
+}
+
+/// 预算的起止时间；缺省的一端取 now 所在的日历周期，end_date 为周期内的最后一秒 (包含在内)
+fn budget_range(
+    calendar: &Calendar,
+    period: Period,
+    start_date: Option<&str>,
+    end_date: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let (period_start, period_end) = calendar.period_range(period, now);
+    let start = match start_date {
+        Some(value) => parse_date(value, "start date")?,
+        None => period_start,
+    };
+    let end = match end_date {
+        Some(value) => parse_date(value, "end date")?,
+        None => period_end - Duration::seconds(1),
+    };
+    if start >= end {
+        return Err(Error::InvalidInput("start_date must be before end_date".to_string()));
+    }
+    Ok((start, end))
+}
+
 pub async fn list_budgets(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsRead>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<ApiResponse<PaginationResponse<Budget>>>> {
     let collection = state.db.mongo.collection::<Budget>("budgets");
@@ -69,18 +108,18 @@ pub async fn list_budgets(
 
 pub async fn create_budget(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsWrite>,
     Json(req): Json<CreateBudgetRequest>,
 ) -> Result<Json<ApiResponse<Budget>>> {
-    use chrono::DateTime;
-
-    let start_date = DateTime::parse_from_rfc3339(&req.start_date)
-        .map_err(|_| Error::InvalidInput("Invalid start date format".to_string()))?
-        .with_timezone(&chrono::Utc);
-        
-    let end_date = DateTime::parse_from_rfc3339(&req.end_date)
-        .map_err(|_| Error::InvalidInput("Invalid end date format".to_string()))?
-        .with_timezone(&chrono::Utc);
+    let period = parse_period(&req.period)?;
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let (start_date, end_date) = budget_range(
+        &calendar,
+        period,
+        req.start_date.as_deref(),
+        req.end_date.as_deref(),
+        Utc::now(),
+    )?;
 
     let budget = Budget {
         id: Some(ObjectId::new().to_hex()),
@@ -114,7 +153,7 @@ pub async fn create_budget(
 
 pub async fn get_budget(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsRead>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<Budget>>> {
     let collection = state.db.mongo.collection::<Budget>("budgets");
@@ -128,22 +167,29 @@ pub async fn get_budget(
 
 pub async fn update_budget(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsWrite>,
     Path(id): Path<String>,
     Json(req): Json<UpdateBudgetRequest>,
 ) -> Result<Json<ApiResponse<Budget>>> {
-    use chrono::DateTime;
-    
-    let start_date = DateTime::parse_from_rfc3339(&req.start_date)
-        .map_err(|_| Error::InvalidInput("Invalid start date format".to_string()))?
-        .with_timezone(&chrono::Utc);
-    
-    let end_date = DateTime::parse_from_rfc3339(&req.end_date)
-        .map_err(|_| Error::InvalidInput("Invalid end date format".to_string()))?
-        .with_timezone(&chrono::Utc);
-    
+    parse_period(&req.period)?;
     let collection = state.db.mongo.collection::<Budget>("budgets");
-    
+    let existing = collection
+        .find_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Budget not found".to_string()))?;
+
+    let start_date = match &req.start_date {
+        Some(value) => parse_date(value, "start date")?,
+        None => existing.start_date,
+    };
+    let end_date = match &req.end_date {
+        Some(value) => parse_date(value, "end date")?,
+        None => existing.end_date,
+    };
+    if start_date >= end_date {
+        return Err(Error::InvalidInput("start_date must be before end_date".to_string()));
+    }
+
     let update_doc = doc! {
         "$set": {
             "category_ids": vec![&req.category_id],
@@ -169,7 +215,7 @@ pub async fn update_budget(
 
 pub async fn delete_budget(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsWrite>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<()>>> {
     let collection = state.db.mongo.collection::<Budget>("budgets");
@@ -186,7 +232,7 @@ pub async fn delete_budget(
 
 pub async fn predict_budget(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::BudgetsRead>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<PredictionResult>>> {
     let collection = state.db.mongo.collection::<Budget>("budgets");
@@ -198,13 +244,17 @@ pub async fn predict_budget(
     // 获取交易历史
     let tx_collection = state.db.mongo.collection::<Transaction>("transactions");
     
-    // 只使用第一个category_id进行查询
-    let category_id = budget.category_ids.first()
-        .ok_or_else(|| Error::InvalidInput("No category IDs in budget".to_string()))?;
+    if budget.category_ids.is_empty() {
+        return Err(Error::InvalidInput("No category IDs in budget".to_string()));
+    }
     
+    // 拆分交易按拆分行的分类匹配，只计入属于预算分类的金额
     let filter = doc! {
         "user_id": &claims.user_id,
-        "category_id": category_id,
+        "$or": [
+            { "category_id": { "$in": &budget.category_ids } },
+            { "splits.category_id": { "$in": &budget.category_ids } },
+        ],
         "transaction_type": "expense",
         "transaction_date": {
             "$gte": bson::to_bson(&budget.start_date).unwrap(),
@@ -212,12 +262,16 @@ pub async fn predict_budget(
         }
     };
     
+    // 预测器按自然日计算天数和周末，这里把各时刻换算为用户本地的挂钟时间再交给它
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let local = |time: DateTime<Utc>| calendar.wall_clock(time);
+
     let mut cursor = tx_collection.find(filter, None).await?;
     let mut spending_history = Vec::new();
     
     while cursor.advance().await? {
         let tx: Transaction = cursor.deserialize_current()?;
-        spending_history.push((tx.transaction_date, tx.amount));
+        spending_history.push((local(tx.transaction_date), tx.amount_in_categories(&budget.category_ids)));
     }
     
     // 执行预测
@@ -225,10 +279,35 @@ pub async fn predict_budget(
     let prediction = predictor.predict(
         &spending_history,
         budget.amount,
-        budget.start_date,
-        budget.end_date,
-        chrono::Utc::now(),
+        local(budget.start_date),
+        local(budget.end_date),
+        local(Utc::now()),
     );
     
     Ok(Json(ApiResponse::success(prediction)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use common::Tz;
+
+    #[test]
+    fn test_budget_range_defaults_to_local_period() {
+        let calendar = Calendar::new(Tz::America__New_York);
+        let now = Utc.with_ymd_and_hms(2024, 11, 4, 3, 0, 0).unwrap(); // 纽约 11-03 22:00 EST
+
+        let (start, end) = budget_range(&calendar, Period::Month, None, None, now).unwrap();
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 11, 1, 4, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 12, 1, 4, 59, 59).unwrap());
+
+        let (start, end) = budget_range(&calendar, Period::Day, None, None, now).unwrap();
+        assert_eq!(end - start, Duration::hours(25) - Duration::seconds(1));
+
+        let (start, _) = budget_range(&calendar, Period::Week, Some("2024-10-01T00:00:00Z"), None, now).unwrap();
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 10, 1, 0, 0, 0).unwrap());
+        assert!(budget_range(&calendar, Period::Week, None, Some("2024-10-01T00:00:00Z"), now).is_err());
+        assert!(parse_period("quarterly").is_err());
+    }
+}
@@ -5,14 +5,15 @@ use axum::{
     Router,
     middleware,
 };
-use common::{DatabaseConnection, middleware::auth_middleware};
+use common::{DatabaseConnection, JwtManager, KeyRing, middleware::{auth_middleware, AuthState, RateLimit, RateLimitLayer}};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub struct AppState {
     pub db: DatabaseConnection,
 }
 
-pub fn create_router(db: DatabaseConnection) -> Router {
+pub fn create_router(db: DatabaseConnection, jwt: Arc<JwtManager>) -> Router {
     let state = Arc::new(AppState { db: db.clone() });
     
     Router::new()
@@ -22,7 +23,9 @@ pub fn create_router(db: DatabaseConnection) -> Router {
         .route("/budgets/:id", put(handlers::update_budget))
         .route("/budgets/:id", delete(handlers::delete_budget))
         .route("/budgets/:id/prediction", get(handlers::predict_budget))
-        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware))
+        .layer(RateLimitLayer::per_user(&db.redis, RateLimit::global_user()))
+        .layer(middleware::from_fn_with_state(AuthState::new(jwt.clone(), db.clone()), auth_middleware))
+        .layer(RateLimitLayer::per_ip(&db.redis, RateLimit::global_ip()))
         .with_state(state)
 }
 
@@ -30,6 +33,8 @@ pub fn create_router(db: DatabaseConnection) -> Router {
 async fn main() {
     tracing_subscriber::fmt::init();
     
+    let jwt = Arc::new(JwtManager::new(KeyRing::from_env().expect("Failed to load JWT keys")));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
@@ -38,7 +43,7 @@ async fn main() {
         .await
         .expect("Failed to connect to database");
     
-    let app = create_router(db);
+    let app = create_router(db, jwt);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3003")
         .await
@@ -46,7 +51,7 @@ async fn main() {
     
     tracing::info!("Budget service listening on port 3003");
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
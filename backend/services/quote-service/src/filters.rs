@@ -0,0 +1,239 @@
+// 卡尔曼滤波状态存储 - 进程内按币种对加锁，状态快照到Redis，重启后恢复并在多副本间共享
+use chrono::{DateTime, Utc};
+use common::{KalmanFilter, RateSource};
+use redis::{aio::ConnectionManager, AsyncCommands, RedisResult, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// 过程噪声：相对汇率每小时的方差 (约对应日波动0.5%)
+const RELATIVE_PROCESS_NOISE: f64 = 1e-6;
+/// 新建滤波器时的相对初始方差
+const RELATIVE_INITIAL_VARIANCE: f64 = 1e-4;
+/// Redis快照过期时间
+const SNAPSHOT_TTL_SECS: u64 = 30 * 24 * 3600;
+/// 快照被其他副本抢先更新时的最大重试次数
+const MAX_SAVE_ATTEMPTS: usize = 5;
+
+/// 比较并写入快照：Redis中快照的版本 (不存在或无法解析时为0) 等于 ARGV[1] 时才写入
+/// 返回 1 表示写入成功，0 表示快照已被其他副本更新
+const COMPARE_AND_SET_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+local version = 0
+if current then
+    local ok, decoded = pcall(cjson.decode, current)
+    if ok and type(decoded) == 'table' and type(decoded.version) == 'number' then
+        version = decoded.version
+    end
+end
+if version ~= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+return 1
+"#;
+
+/// 单个币种对的滤波状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairState {
+    pub filter: KalmanFilter,
+    /// 每个来源已融合的最新观测时间，避免重复融合同一条观测
+    #[serde(default)]
+    pub consumed: HashMap<String, DateTime<Utc>>,
+    /// 快照版本，每次写入Redis时加一，用于多副本间的比较并写入
+    #[serde(default)]
+    pub version: u64,
+}
+
+impl PairState {
+    fn new(observations: &[(DateTime<Utc>, RateSource)]) -> Self {
+        let total_weight: f64 = observations.iter().map(|(_, s)| s.weight).sum();
+        let initial_rate = if total_weight > 0.0 {
+            observations.iter().map(|(_, s)| s.rate * s.weight).sum::<f64>() / total_weight
+        } else {
+            observations[0].1.rate
+        };
+        let scale = initial_rate * initial_rate;
+
+        Self {
+            filter: KalmanFilter::with_process_noise(
+                initial_rate,
+                RELATIVE_INITIAL_VARIANCE * scale,
+                RELATIVE_PROCESS_NOISE * scale,
+            ),
+            consumed: HashMap::new(),
+            version: 0,
+        }
+    }
+
+    /// 按观测时间顺序融合尚未处理过的观测
+    fn absorb(&mut self, observations: &[(DateTime<Utc>, RateSource)]) {
+        let mut pending: Vec<&(DateTime<Utc>, RateSource)> = observations
+            .iter()
+            .filter(|(at, source)| self.consumed.get(&source.name).is_none_or(|seen| at > seen))
+            .collect();
+        pending.sort_by_key(|(at, _)| *at);
+
+        for (at, source) in pending {
+            self.filter.predict_to(*at);
+            self.filter.update(source.rate, source.noise_variance);
+            self.consumed.insert(source.name.clone(), *at);
+        }
+    }
+}
+
+pub struct FilterStore {
+    redis: Arc<ConnectionManager>,
+    pairs: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<PairState>>>>>,
+    compare_and_set: Script,
+}
+
+impl FilterStore {
+    pub fn new(redis: Arc<ConnectionManager>) -> Self {
+        Self {
+            redis,
+            pairs: Mutex::new(HashMap::new()),
+            compare_and_set: Script::new(COMPARE_AND_SET_SCRIPT),
+        }
+    }
+
+    fn slot(&self, pair: &str) -> Arc<tokio::sync::Mutex<Option<PairState>>> {
+        self.pairs.lock().unwrap().entry(pair.to_string()).or_default().clone()
+    }
+
+    fn redis_key(pair: &str) -> String {
+        format!("quote:kalman:{}", pair)
+    }
+
+    /// 读取Redis中的快照，无法解析的快照视为不存在 (写入时会被覆盖)
+    async fn load_snapshot(&self, pair: &str) -> RedisResult<Option<PairState>> {
+        let mut conn = (*self.redis).clone();
+        let json: Option<String> = conn.get(Self::redis_key(pair)).await?;
+        Ok(json.and_then(|json| {
+            serde_json::from_str(&json)
+                .map_err(|e| tracing::warn!("Discarding invalid Kalman snapshot for {}: {}", pair, e))
+                .ok()
+        }))
+    }
+
+    /// Redis中的快照版本仍为 expected 时写入，返回是否写入成功
+    async fn save_snapshot(&self, pair: &str, state: &PairState, expected: u64) -> RedisResult<bool> {
+        let json = serde_json::to_string(state).expect("PairState is serializable");
+        let mut conn = (*self.redis).clone();
+        let saved: i32 = self
+            .compare_and_set
+            .key(Self::redis_key(pair))
+            .arg(expected)
+            .arg(json)
+            .arg(SNAPSHOT_TTL_SECS)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(saved == 1)
+    }
+
+    /// 把观测融合进币种对的持久化滤波状态，返回 (汇率, 置信度)
+    ///
+    /// 优先使用Redis中的快照 (可能由其他副本更新)，Redis不可用时退回进程内状态。
+    /// 读取到写入之间快照被其他副本更新时，基于新快照重新融合，避免覆盖其他副本的结果。
+    pub async fn fuse(&self, pair: &str, observations: &[(DateTime<Utc>, RateSource)]) -> Option<(f64, f64)> {
+        let slot = self.slot(pair);
+        let mut local = slot.lock().await;
+
+        let mut fused = None;
+        for _ in 0..MAX_SAVE_ATTEMPTS {
+            let (snapshot, shared) = match self.load_snapshot(pair).await {
+                Ok(snapshot) => (snapshot, true),
+                Err(e) => {
+                    tracing::warn!("Failed to load Kalman snapshot for {}: {}", pair, e);
+                    (local.clone(), false)
+                }
+            };
+            let expected = snapshot.as_ref().map_or(0, |state| state.version);
+            let mut state = match snapshot {
+                Some(state) => state,
+                None if observations.is_empty() => return None,
+                None => PairState::new(observations),
+            };
+
+            state.absorb(observations);
+            state.version = expected + 1;
+            let saved = !shared
+                || self.save_snapshot(pair, &state, expected).await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to save Kalman snapshot for {}: {}", pair, e);
+                    true
+                });
+            fused = Some(state);
+            if saved {
+                break;
+            }
+        }
+
+        let state = fused?;
+        let rate = state.filter.get_estimate();
+        let variance = state.filter.variance_at(Utc::now());
+        *local = Some(state);
+
+        Some((rate, 1.0 / (1.0 + variance)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn source(name: &str, rate: f64) -> RateSource {
+        RateSource {
+            name: name.to_string(),
+            rate,
+            weight: 0.5,
+            noise_variance: 1e-6 * rate * rate,
+        }
+    }
+
+    #[test]
+    fn test_absorb_skips_consumed_observations() {
+        let day = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let observations = vec![(day, source("ecb", 7.20)), (day, source("json", 7.22))];
+
+        let mut state = PairState::new(&observations);
+        state.absorb(&observations);
+        let (estimate, variance) = (state.filter.get_estimate(), state.filter.get_variance());
+        assert!(estimate > 7.19 && estimate < 7.23);
+
+        // 再次查询相同观测不应继续收缩方差
+        state.absorb(&observations);
+        assert_eq!(state.filter.get_estimate(), estimate);
+        assert_eq!(state.filter.get_variance(), variance);
+
+        // 新观测经过一天后融合，预测阶段方差按时间增长
+        let next_day = day + chrono::Duration::days(1);
+        assert!(state.filter.variance_at(next_day) > variance);
+        state.absorb(&[(next_day, source("ecb", 7.30))]);
+        assert!(state.filter.get_estimate() > estimate);
+        assert_eq!(state.consumed["ecb"], next_day);
+        assert_eq!(state.consumed["json"], day);
+    }
+
+    #[test]
+    fn test_pair_state_survives_snapshot_roundtrip() {
+        let day = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let observations = vec![(day, source("ecb", 7.20))];
+        let mut state = PairState::new(&observations);
+        state.absorb(&observations);
+        state.version = 3;
+
+        let json = serde_json::to_string(&state).unwrap();
+        let restored: PairState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.filter.get_estimate(), state.filter.get_estimate());
+        assert_eq!(restored.filter.last_update(), Some(day));
+        assert_eq!(restored.consumed, state.consumed);
+        assert_eq!(restored.version, 3);
+
+        // 加入版本号之前写入的快照按版本 0 读取
+        let mut legacy: serde_json::Value = serde_json::from_str(&json).unwrap();
+        legacy.as_object_mut().unwrap().remove("version");
+        let legacy: PairState = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.version, 0);
+    }
+}
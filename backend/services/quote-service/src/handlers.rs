@@ -2,12 +2,19 @@ use axum::{
     extract::{Path, Query, State},
     Json,
 };
-use common::{ApiResponse, Result, Error, ExchangeRateFusion, RateSource};
+use common::{ApiResponse, Error, Result};
+use common::middleware::{scope, Authorized};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
+use crate::networth::{self, NetWorthResponse};
+use crate::rates;
+use crate::store::Candle;
 use crate::AppState;
 
+/// 单次历史查询最多返回的K线数量
+const MAX_HISTORY_POINTS: i64 = 2000;
+
 #[derive(Serialize)]
 pub struct QuoteResponse {
     pub pair: String,
@@ -21,6 +28,8 @@ pub struct ConvertQuery {
     pub from: String,
     pub to: String,
     pub amount: f64,
+    /// 按该日期 (YYYY-MM-DD 或 RFC3339) 的汇率换算，缺省为当前汇率
+    pub date: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -30,81 +39,146 @@ pub struct ConvertResponse {
     pub original_amount: f64,
     pub converted_amount: f64,
     pub rate: f64,
+    pub date: Option<String>,
+    pub via: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub interval: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryResponse {
+    pub pair: String,
+    pub interval: String,
+    pub via: Option<String>,
+    pub points: Vec<Candle>,
+}
+
+#[derive(Deserialize)]
+pub struct NetWorthQuery {
+    /// 折算币种，缺省为用户设置中的默认币种
+    pub currency: Option<String>,
+    /// 每日净值序列的天数
+    pub days: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ManualRateRequest {
+    pub base: String,
+    pub target: String,
+    pub rate: f64,
 }
 
 pub async fn get_quote(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Path(pair): Path<String>,
 ) -> Result<Json<ApiResponse<QuoteResponse>>> {
-    // 从多个来源获取汇率
-    let sources = fetch_exchange_rates(&pair).await?;
-    
-    // 使用卡尔曼滤波融合汇率
-    let mut fusion = ExchangeRateFusion::new();
-    let fused_rate = fusion.fuse_rates(&pair, &sources);
-    
-    let (rate, confidence) = fusion
-        .get_rate_with_confidence(&pair)
-        .unwrap_or((fused_rate, 1.0));
-    
+    let (base, target) = rates::parse_pair(&pair)?;
+
+    // 从历史库读取各来源最新汇率，使用卡尔曼滤波融合
+    let resolved = rates::resolve_rate(&state, &base, &target, None).await?;
+
     Ok(Json(ApiResponse::success(QuoteResponse {
-        pair,
-        rate,
-        confidence,
+        pair: format!("{}/{}", base, target),
+        rate: resolved.rate,
+        confidence: resolved.confidence,
         timestamp: chrono::Utc::now().timestamp(),
     })))
 }
 
 pub async fn convert_currency(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Query(query): Query<ConvertQuery>,
 ) -> Result<Json<ApiResponse<ConvertResponse>>> {
-    let pair = format!("{}/{}", query.from, query.to);
-    let sources = fetch_exchange_rates(&pair).await?;
-    
-    let mut fusion = ExchangeRateFusion::new();
-    let rate = fusion.fuse_rates(&pair, &sources);
-    
-    let converted_amount = query.amount * rate;
-    
+    let (from, to) = rates::parse_pair(&format!("{}/{}", query.from, query.to))?;
+    let as_of = query
+        .date
+        .as_deref()
+        .map(|date| rates::parse_time(date, true))
+        .transpose()?;
+    let resolved = rates::resolve_rate(&state, &from, &to, as_of).await?;
+
+    let converted_amount = query.amount * resolved.rate;
+
     Ok(Json(ApiResponse::success(ConvertResponse {
-        from_currency: query.from,
-        to_currency: query.to,
+        from_currency: from,
+        to_currency: to,
         original_amount: query.amount,
         converted_amount,
-        rate,
+        rate: resolved.rate,
+        date: query.date,
+        via: resolved.via,
+    })))
+}
+
+pub async fn get_history(
+    State(state): State<Arc<AppState>>,
+    Path(pair): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<ApiResponse<HistoryResponse>>> {
+    let (base, target) = rates::parse_pair(&pair)?;
+    let interval = query.interval.unwrap_or_else(|| "day".to_string());
+    let (bucket, bucket_length) = rates::parse_interval(&interval)?;
+
+    let to = match &query.to {
+        Some(to) => rates::parse_time(to, true)?,
+        None => chrono::Utc::now(),
+    };
+    let from = match &query.from {
+        Some(from) => rates::parse_time(from, false)?,
+        None => to - chrono::Duration::days(30),
+    };
+
+    if from >= to {
+        return Err(Error::InvalidInput("from must be earlier than to".to_string()));
+    }
+    if (to - from).num_seconds() / bucket_length.num_seconds() > MAX_HISTORY_POINTS {
+        return Err(Error::InvalidInput(format!(
+            "Requested range exceeds {} points, use a larger interval",
+            MAX_HISTORY_POINTS
+        )));
+    }
+
+    let (points, via) = rates::history(&state, &base, &target, from, to, bucket).await?;
+
+    Ok(Json(ApiResponse::success(HistoryResponse {
+        pair: format!("{}/{}", base, target),
+        interval,
+        via,
+        points,
     })))
 }
 
-async fn fetch_exchange_rates(pair: &str) -> Result<Vec<RateSource>> {
-    // 模拟从多个源获取汇率
-    
-    let parts: Vec<&str> = pair.split('/').collect();
-    if parts.len() != 2 {
-        return Err(Error::InvalidInput("Invalid currency pair".to_string()));
+pub async fn get_net_worth(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::AccountsRead>,
+    Query(query): Query<NetWorthQuery>,
+) -> Result<Json<ApiResponse<NetWorthResponse>>> {
+    let days = query.days.unwrap_or(30);
+    if !(1..=366).contains(&days) {
+        return Err(Error::InvalidInput("days must be between 1 and 366".to_string()));
     }
-    
-    // 模拟数据
-    let base_rate = 6.5; // CNY/USD汇率
-    
-    Ok(vec![
-        RateSource {
-            name: "央行".to_string(),
-            rate: base_rate,
-            weight: 0.5,
-            noise_variance: 0.0001,
-        },
-        RateSource {
-            name: "Yahoo".to_string(),
-            rate: base_rate + 0.02,
-            weight: 0.3,
-            noise_variance: 0.01,
-        },
-        RateSource {
-            name: "手动".to_string(),
-            rate: base_rate - 0.01,
-            weight: 0.2,
-            noise_variance: 0.05,
-        },
-    ])
+
+    let report = networth::compute(&state, &claims.user_id, query.currency, days).await?;
+    Ok(Json(ApiResponse::success(report)))
+}
+
+/// 管理员设置手动汇率，立即写入历史库
+pub async fn set_manual_rate(
+    State(state): State<Arc<AppState>>,
+    _: Authorized<scope::Admin>,
+    Json(payload): Json<ManualRateRequest>,
+) -> Result<Json<ApiResponse<QuoteResponse>>> {
+    let observation = state.manual.set_rate(&payload.base, &payload.target, payload.rate).await?;
+
+    Ok(Json(ApiResponse::success(QuoteResponse {
+        pair: format!("{}/{}", observation.base, observation.target),
+        rate: observation.rate,
+        confidence: 1.0,
+        timestamp: observation.observed_at.timestamp(),
+    })))
 }
@@ -1,46 +1,101 @@
+mod filters;
 mod handlers;
+mod networth;
+mod providers;
+mod rates;
+mod store;
 
 use axum::{
-    routing::get,
+    middleware,
+    routing::{get, post},
     Router,
 };
-use common::DatabaseConnection;
+use common::{
+    middleware::{auth_middleware, AuthState, RateLimit, RateLimitLayer},
+    DatabaseConnection, JwtManager, KeyRing,
+};
+use filters::FilterStore;
+use providers::{ManualProvider, RateProvider};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use store::RateStore;
 
 pub struct AppState {
     pub db: DatabaseConnection,
+    pub jwt: Arc<JwtManager>,
+    pub store: RateStore,
+    pub providers: Vec<Arc<dyn RateProvider>>,
+    pub manual: Arc<ManualProvider>,
+    /// 各币种对的卡尔曼滤波状态
+    pub filters: FilterStore,
+    /// 超过该时长的观测不参与融合
+    pub max_rate_age: chrono::Duration,
 }
 
-pub fn create_router(db: DatabaseConnection) -> Router {
-    let state = Arc::new(AppState { db });
-    
+pub fn create_router(state: Arc<AppState>) -> Router {
+    let auth = middleware::from_fn_with_state(AuthState::new(state.jwt.clone(), state.db.clone()), auth_middleware);
+    let user_limit = RateLimitLayer::per_user(&state.db.redis, RateLimit::global_user());
+
     Router::new()
         .route("/quotes/:pair", get(handlers::get_quote))
+        .route("/quotes/:pair/history", get(handlers::get_history))
         .route("/quotes/convert", get(handlers::convert_currency))
+        .route(
+            "/quotes/net-worth",
+            get(handlers::get_net_worth).layer(user_limit.clone()).layer(auth.clone()),
+        )
+        .route(
+            "/quotes/manual",
+            post(handlers::set_manual_rate).layer(user_limit).layer(auth),
+        )
+        .layer(RateLimitLayer::per_ip(&state.db.redis, RateLimit::global_ip()))
         .with_state(state)
 }
 
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    
+
+    let jwt = Arc::new(JwtManager::new(KeyRing::from_env().expect("Failed to load JWT keys")));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
-    
+
     let db = DatabaseConnection::new(&mongo_uri, &redis_uri, pg_uri.as_deref(), "abook")
         .await
         .expect("Failed to connect to database");
-    
-    let app = create_router(db);
-    
+
+    let store = RateStore::new(db.pg.clone().expect("POSTGRES_URI is required for quote-service"));
+
+    let manual = Arc::new(ManualProvider::new(store.clone()));
+
+    let state = Arc::new(AppState {
+        filters: FilterStore::new(db.redis.clone()),
+        db,
+        jwt,
+        store,
+        providers: rates::configured_providers(manual.clone()),
+        manual,
+        max_rate_age: chrono::Duration::hours(env_u64("RATE_MAX_AGE_HOURS", 96) as i64),
+    });
+
+    let poll_interval = std::time::Duration::from_secs(env_u64("RATE_POLL_INTERVAL_SECS", 3600));
+    rates::spawn_poller(state.clone(), poll_interval);
+
+    let app = create_router(state);
+
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3005")
         .await
         .expect("Failed to bind port");
-    
+
     tracing::info!("Quote service listening on port 3005");
-    
-    axum::serve(listener, app)
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
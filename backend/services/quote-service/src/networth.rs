@@ -0,0 +1,327 @@
+// 资产净值 - 把用户各账户余额按融合汇率折算为默认币种，并由交易记录倒推每日净值
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use common::{is_liability_account, Account, Calendar, Error, Result, Transaction, User, ACCOUNT_STATUS_ARCHIVED};
+use mongodb::bson::{self, doc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::providers::normalize_currency;
+use crate::rates;
+use crate::store::Candle;
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct NetWorthAccount {
+    pub account_id: String,
+    pub name: String,
+    pub account_type: String,
+    pub balance: f64,
+    pub currency: String,
+    pub converted_balance: f64,
+    pub is_liability: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NetWorthPoint {
+    pub date: String,
+    pub assets: f64,
+    pub liabilities: f64,
+    pub net_worth: f64,
+}
+
+#[derive(Serialize)]
+pub struct NetWorthResponse {
+    pub net_worth: f64,
+    pub currency: String,
+    pub total_assets: f64,
+    pub total_liabilities: f64,
+    pub accounts: Vec<NetWorthAccount>,
+    pub series: Vec<NetWorthPoint>,
+    pub calculated_at: DateTime<Utc>,
+}
+
+/// 从当前余额倒推每个日终时刻的账户余额：冲销发生在该时刻之后的交易
+/// day_ends 需按时间升序，返回结果与之一一对应
+fn daily_balances(
+    current: &HashMap<String, f64>,
+    transactions: &[Transaction],
+    day_ends: &[DateTime<Utc>],
+) -> Vec<HashMap<String, f64>> {
+    let mut sorted: Vec<&Transaction> = transactions.iter().collect();
+    sorted.sort_by_key(|tx| std::cmp::Reverse(tx.transaction_date));
+
+    let mut balances = current.clone();
+    let mut pending = sorted.into_iter().peekable();
+    let mut snapshots: Vec<HashMap<String, f64>> = day_ends
+        .iter()
+        .rev()
+        .map(|day_end| {
+            while let Some(tx) = pending.next_if(|tx| tx.transaction_date >= *day_end) {
+                for (account_id, delta) in tx.balance_effects() {
+                    if let Some(balance) = balances.get_mut(account_id) {
+                        *balance -= delta;
+                    }
+                }
+            }
+            balances.clone()
+        })
+        .collect();
+
+    snapshots.reverse();
+    snapshots
+}
+
+/// 按账户类型汇总 (资产, 负债)，负债以正数表示欠款
+fn split_totals<'a>(entries: impl Iterator<Item = (&'a Account, f64)>) -> (f64, f64) {
+    entries.fold((0.0, 0.0), |(assets, liabilities), (account, converted)| {
+        if is_liability_account(&account.account_type) {
+            (assets, liabilities - converted)
+        } else {
+            (assets + converted, liabilities)
+        }
+    })
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// 日K线序列中 time 之前最近一根的收盘价，用于向前填充没有报价的日期；candles 需按时间升序
+fn close_before(candles: &[Candle], time: DateTime<Utc>) -> Option<f64> {
+    let index = candles.partition_point(|candle| candle.time < time);
+    index.checked_sub(1).map(|i| candles[i].close)
+}
+
+/// 按汇率换算：当前汇率按币种缓存，历史汇率每个币种只查询一次日K线
+struct Converter<'a> {
+    state: &'a AppState,
+    target: String,
+    current: HashMap<String, f64>,
+    daily: HashMap<String, Vec<Candle>>,
+}
+
+impl<'a> Converter<'a> {
+    async fn current_rate(&mut self, currency: &str) -> Result<f64> {
+        if let Some(rate) = self.current.get(currency) {
+            return Ok(*rate);
+        }
+        let rate = rates::resolve_rate(self.state, currency, &self.target, None).await?.rate;
+        self.current.insert(currency.to_string(), rate);
+        Ok(rate)
+    }
+
+    /// 加载 [from, to) 内各币种的日K线
+    async fn load_daily(&mut self, currencies: &[&str], from: DateTime<Utc>, to: DateTime<Utc>) -> Result<()> {
+        for currency in currencies {
+            if *currency == self.target || self.daily.contains_key(*currency) {
+                continue;
+            }
+            let (candles, _) = rates::history(self.state, currency, &self.target, from, to, "1 day").await?;
+            self.daily.insert(currency.to_string(), candles);
+        }
+        Ok(())
+    }
+
+    /// day_end 时刻的汇率：取之前最近的日线收盘价，没有历史报价时回退到当前汇率
+    async fn rate_at(&mut self, currency: &str, day_end: DateTime<Utc>) -> Result<f64> {
+        match self.daily.get(currency).and_then(|candles| close_before(candles, day_end)) {
+            Some(rate) => Ok(rate),
+            None => self.current_rate(currency).await,
+        }
+    }
+}
+
+/// 计算用户的资产净值及最近 days 天的每日净值
+pub async fn compute(
+    state: &AppState,
+    user_id: &str,
+    currency: Option<String>,
+    days: i64,
+) -> Result<NetWorthResponse> {
+    let target = match currency {
+        Some(currency) => normalize_currency(&currency)
+            .ok_or_else(|| Error::InvalidInput("Invalid currency code".to_string()))?,
+        None => state
+            .db
+            .mongo
+            .collection::<User>("users")
+            .find_one(doc! { "_id": user_id }, None)
+            .await?
+            .map(|user| user.settings.default_currency)
+            .unwrap_or_else(|| common::UserSettings::default().default_currency),
+    };
+
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<Account>("accounts")
+        .find(
+            doc! {
+                "user_id": user_id,
+                "is_excluded_from_total": false,
+                "status": { "$ne": ACCOUNT_STATUS_ARCHIVED },
+            },
+            None,
+        )
+        .await?;
+    let mut accounts: Vec<Account> = Vec::new();
+    while cursor.advance().await? {
+        accounts.push(cursor.deserialize_current()?);
+    }
+
+    let mut converter = Converter {
+        state,
+        target: target.clone(),
+        current: HashMap::new(),
+        daily: HashMap::new(),
+    };
+
+    let mut entries = Vec::new();
+    for account in &accounts {
+        let rate = converter.current_rate(&account.currency).await?;
+        entries.push(NetWorthAccount {
+            account_id: account.id.clone().unwrap_or_default(),
+            name: account.name.clone(),
+            account_type: account.account_type.clone(),
+            balance: account.current_balance,
+            currency: account.currency.clone(),
+            converted_balance: round2(account.current_balance * rate),
+            is_liability: is_liability_account(&account.account_type),
+        });
+    }
+    let (total_assets, total_liabilities) =
+        split_totals(accounts.iter().zip(entries.iter().map(|e| e.converted_balance)));
+
+    // 每日净值按用户时区的自然日切分
+    let calendar = Calendar::for_user(&state.db.mongo, user_id).await?;
+    let now = Utc::now();
+    let today = calendar.local_date(now);
+    let first_day = today - Duration::days(days - 1);
+    let series_start = calendar.start_of_day(first_day);
+
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<Transaction>("transactions")
+        .find(
+            doc! {
+                "user_id": user_id,
+                "transaction_date": { "$gte": bson::to_bson(&series_start).unwrap() },
+            },
+            None,
+        )
+        .await?;
+    let mut transactions = Vec::new();
+    while cursor.advance().await? {
+        transactions.push(cursor.deserialize_current()?);
+    }
+
+    let days_list: Vec<NaiveDate> = first_day.iter_days().take(days as usize).collect();
+    let day_ends: Vec<DateTime<Utc>> = days_list
+        .iter()
+        .map(|day| calendar.start_of_day(day.succ_opt().unwrap_or(*day)).min(now))
+        .collect();
+
+    let current: HashMap<String, f64> = accounts
+        .iter()
+        .map(|account| (account.id.clone().unwrap_or_default(), account.current_balance))
+        .collect();
+    let snapshots = daily_balances(&current, &transactions, &day_ends);
+
+    // 往前多取 max_rate_age，使序列第一天也有可向前填充的收盘价
+    let currencies: Vec<&str> = accounts.iter().map(|account| account.currency.as_str()).collect();
+    converter.load_daily(&currencies, series_start - state.max_rate_age, now).await?;
+
+    let mut series = Vec::with_capacity(days_list.len());
+    for ((day, day_end), balances) in days_list.iter().zip(&day_ends).zip(snapshots) {
+        let mut converted = Vec::with_capacity(accounts.len());
+        for account in &accounts {
+            let rate = if *day == today {
+                converter.current_rate(&account.currency).await?
+            } else {
+                converter.rate_at(&account.currency, *day_end).await?
+            };
+            let balance = balances.get(account.id.as_deref().unwrap_or_default()).copied().unwrap_or(0.0);
+            converted.push((account, balance * rate));
+        }
+
+        let (assets, liabilities) = split_totals(converted.into_iter());
+        series.push(NetWorthPoint {
+            date: day.format("%Y-%m-%d").to_string(),
+            assets: round2(assets),
+            liabilities: round2(liabilities),
+            net_worth: round2(assets - liabilities),
+        });
+    }
+
+    Ok(NetWorthResponse {
+        net_worth: round2(total_assets - total_liabilities),
+        currency: target,
+        total_assets: round2(total_assets),
+        total_liabilities: round2(total_liabilities),
+        accounts: entries,
+        series,
+        calculated_at: now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use common::test_util::TransactionBuilder;
+
+    fn transaction(transaction_type: &str, account_id: &str, amount: f64, day: u32) -> Transaction {
+        TransactionBuilder::new(transaction_type, amount)
+            .account(account_id)
+            .date(Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn test_close_before_forward_fills() {
+        let candle = |day: u32, close: f64| Candle {
+            time: Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            samples: 1,
+        };
+        let candles = vec![candle(1, 7.1), candle(2, 7.2), candle(5, 7.5)];
+        let at = |day: u32, hour: u32| Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap();
+
+        assert_eq!(close_before(&candles, at(1, 0)), None);
+        assert_eq!(close_before(&candles, at(2, 16)), Some(7.2));
+        // 3、4日没有报价，沿用2日收盘价
+        assert_eq!(close_before(&candles, at(4, 16)), Some(7.2));
+        assert_eq!(close_before(&candles, at(6, 0)), Some(7.5));
+    }
+
+    #[test]
+    fn test_daily_balances_reverse_later_transactions() {
+        let current = HashMap::from([("bank".to_string(), 1000.0), ("card".to_string(), -300.0)]);
+
+        let mut repayment = transaction("transfer", "bank", 200.0, 3);
+        repayment.to_account_id = Some("card".to_string());
+        let transactions = vec![
+            transaction("income", "bank", 500.0, 2),
+            transaction("expense", "card", 100.0, 3),
+            repayment,
+        ];
+
+        let day_ends: Vec<DateTime<Utc>> = (1..=3)
+            .map(|day| Utc.with_ymd_and_hms(2024, 3, day + 1, 0, 0, 0).unwrap())
+            .collect();
+        let snapshots = daily_balances(&current, &transactions, &day_ends);
+
+        assert_eq!(snapshots[2]["bank"], 1000.0);
+        assert_eq!(snapshots[2]["card"], -300.0);
+        // 3日的还款和消费被冲销
+        assert_eq!(snapshots[1]["bank"], 1200.0);
+        assert_eq!(snapshots[1]["card"], -400.0);
+        // 2日的收入被冲销
+        assert_eq!(snapshots[0]["bank"], 700.0);
+        assert_eq!(snapshots[0]["card"], -400.0);
+    }
+}
@@ -0,0 +1,359 @@
+// 汇率数据源 - 每个来源实现 RateProvider，由轮询任务定期拉取并写入历史库
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use common::{Error, Result};
+use std::time::Duration;
+
+use crate::store::RateStore;
+
+/// 单条汇率观测：1 单位 base 可兑换 rate 单位 target
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateObservation {
+    pub source: String,
+    pub base: String,
+    pub target: String,
+    pub rate: f64,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl RateObservation {
+    /// 规范化币种代码并校验汇率，非法数据返回 None
+    pub fn new(source: &str, base: &str, target: &str, rate: f64, observed_at: DateTime<Utc>) -> Option<Self> {
+        let base = normalize_currency(base)?;
+        let target = normalize_currency(target)?;
+        if base == target || !rate.is_finite() || rate <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            source: source.to_string(),
+            base,
+            target,
+            rate,
+            observed_at,
+        })
+    }
+}
+
+/// 校验并转换为大写的三位ISO币种代码
+pub fn normalize_currency(code: &str) -> Option<String> {
+    let code = code.trim();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(code.to_ascii_uppercase())
+    } else {
+        None
+    }
+}
+
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+    /// 来源名称，同时作为历史表中的 source 字段
+    fn name(&self) -> &str;
+
+    /// 融合时的初始权重
+    fn weight(&self) -> f64;
+
+    /// 相对观测噪声方差 (相对误差的方差，融合时按汇率量级换算)
+    fn noise_variance(&self) -> f64;
+
+    /// 拉取当前可用的全部汇率
+    async fn fetch(&self) -> Result<Vec<RateObservation>>;
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+async fn fetch_text(client: &reqwest::Client, source: &str, url: &str) -> Result<String> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| Error::InternalServer(format!("Failed to fetch rates from {}: {}", source, e)))?
+        .text()
+        .await
+        .map_err(|e| Error::InternalServer(format!("Failed to read rates from {}: {}", source, e)))
+}
+
+/// 欧洲央行风格的XML汇率源 (以EUR为基准，按日发布)
+pub struct EcbProvider {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl EcbProvider {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            client: http_client(),
+        }
+    }
+
+    /// 解析 `<Cube time='...'>` 下的 `<Cube currency='USD' rate='1.09'/>` 条目
+    pub fn parse(&self, xml: &str) -> Result<Vec<RateObservation>> {
+        let mut observed_at = None;
+        let mut observations = Vec::new();
+
+        for tag in xml.split("<Cube").skip(1) {
+            let tag = tag.split('>').next().unwrap_or_default();
+
+            if let Some(time) = xml_attribute(tag, "time") {
+                let date = NaiveDate::parse_from_str(&time, "%Y-%m-%d")
+                    .map_err(|_| Error::InternalServer(format!("Invalid date '{}' in {} feed", time, self.name)))?;
+                observed_at = Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
+            }
+
+            if let (Some(currency), Some(rate)) = (xml_attribute(tag, "currency"), xml_attribute(tag, "rate")) {
+                let rate = rate
+                    .parse::<f64>()
+                    .map_err(|_| Error::InternalServer(format!("Invalid rate '{}' in {} feed", rate, self.name)))?;
+                let time = observed_at.unwrap_or_else(Utc::now);
+                observations.extend(RateObservation::new(&self.name, "EUR", &currency, rate, time));
+            }
+        }
+
+        if observations.is_empty() {
+            return Err(Error::InternalServer(format!("No rates found in {} feed", self.name)));
+        }
+        Ok(observations)
+    }
+}
+
+#[async_trait]
+impl RateProvider for EcbProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn weight(&self) -> f64 {
+        0.5
+    }
+
+    fn noise_variance(&self) -> f64 {
+        1e-6
+    }
+
+    async fn fetch(&self) -> Result<Vec<RateObservation>> {
+        let xml = fetch_text(&self.client, &self.name, &self.url).await?;
+        self.parse(&xml)
+    }
+}
+
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let preceded_by_space = rest[..pos].ends_with(char::is_whitespace);
+        let after = rest[pos + name.len()..].trim_start();
+        if preceded_by_space {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+                let value = &value[1..];
+                return value.find(quote).map(|end| value[..end].to_string());
+            }
+        }
+        rest = &rest[pos + name.len()..];
+    }
+    None
+}
+
+/// 通用JSON汇率源，兼容 `{"base": "USD", "rates": {"CNY": 7.1}, "timestamp": 1700000000}`
+/// 以及 `base_code` / `conversion_rates` / `time_last_update_unix` 等常见字段名
+pub struct JsonProvider {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl JsonProvider {
+    pub fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            client: http_client(),
+        }
+    }
+
+    pub fn parse(&self, body: &str) -> Result<Vec<RateObservation>> {
+        let value: serde_json::Value = serde_json::from_str(body)
+            .map_err(|e| Error::InternalServer(format!("Invalid JSON from {}: {}", self.name, e)))?;
+
+        let base = ["base", "base_code"]
+            .iter()
+            .find_map(|key| value.get(*key).and_then(|v| v.as_str()))
+            .ok_or_else(|| Error::InternalServer(format!("Missing base currency in {} response", self.name)))?;
+        let rates = ["rates", "conversion_rates"]
+            .iter()
+            .find_map(|key| value.get(*key).and_then(|v| v.as_object()))
+            .ok_or_else(|| Error::InternalServer(format!("Missing rates in {} response", self.name)))?;
+        let observed_at = ["timestamp", "time_last_update_unix"]
+            .iter()
+            .find_map(|key| value.get(*key).and_then(|v| v.as_i64()))
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+            .unwrap_or_else(Utc::now);
+
+        let observations: Vec<RateObservation> = rates
+            .iter()
+            .filter_map(|(target, rate)| {
+                RateObservation::new(&self.name, base, target, rate.as_f64()?, observed_at)
+            })
+            .collect();
+
+        if observations.is_empty() {
+            return Err(Error::InternalServer(format!("No rates found in {} response", self.name)));
+        }
+        Ok(observations)
+    }
+}
+
+#[async_trait]
+impl RateProvider for JsonProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn weight(&self) -> f64 {
+        0.3
+    }
+
+    fn noise_variance(&self) -> f64 {
+        4e-6
+    }
+
+    async fn fetch(&self) -> Result<Vec<RateObservation>> {
+        let body = fetch_text(&self.client, &self.name, &self.url).await?;
+        self.parse(&body)
+    }
+}
+
+/// 管理员手动维护的汇率，保存在数据库中供所有副本共享；观测时间为设置时间，
+/// 设置时写入一次历史，之后的轮询不会把旧汇率当作新观测重复写入
+pub struct ManualProvider {
+    store: RateStore,
+}
+
+impl ManualProvider {
+    pub const NAME: &'static str = "manual";
+
+    pub fn new(store: RateStore) -> Self {
+        Self { store }
+    }
+
+    /// 校验并生成手动汇率的观测记录
+    fn observation(base: &str, target: &str, rate: f64, at: DateTime<Utc>) -> Result<RateObservation> {
+        RateObservation::new(Self::NAME, base, target, rate, at)
+            .ok_or_else(|| Error::InvalidInput("Invalid currency pair or rate".to_string()))
+    }
+
+    /// 设置手动汇率并写入历史库，返回对应的观测记录
+    pub async fn set_rate(&self, base: &str, target: &str, rate: f64) -> Result<RateObservation> {
+        let observation = Self::observation(base, target, rate, Utc::now())?;
+        self.store.save_manual_rate(&observation).await?;
+        Ok(observation)
+    }
+}
+
+#[async_trait]
+impl RateProvider for ManualProvider {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn weight(&self) -> f64 {
+        0.2
+    }
+
+    fn noise_variance(&self) -> f64 {
+        2.5e-5
+    }
+
+    /// 返回当前的手动汇率 (保留设置时间)，历史库中已有的观测会被忽略
+    async fn fetch(&self) -> Result<Vec<RateObservation>> {
+        self.store.manual_rates(Self::NAME).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+
+    const ECB_FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+    <gesmes:subject>Reference rates</gesmes:subject>
+    <Cube>
+        <Cube time='2024-03-15'>
+            <Cube currency='USD' rate='1.0890'/>
+            <Cube currency='JPY' rate='161.69'/>
+            <Cube currency="CNY" rate="7.8384"/>
+        </Cube>
+    </Cube>
+</gesmes:Envelope>"#;
+
+    const JSON_FEED: &str = r#"{"result":"success","base_code":"USD","time_last_update_unix":1710460800,
+        "conversion_rates":{"USD":1,"CNY":7.1982,"EUR":0.9183,"bogus":"n/a"}}"#;
+
+    /// 在本地随机端口启动桩服务，返回其地址
+    async fn stub_server() -> String {
+        let app = Router::new()
+            .route("/ecb.xml", get(|| async { ECB_FEED }))
+            .route("/latest.json", get(|| async { JSON_FEED }))
+            .route("/broken.json", get(|| async { "{\"rates\": 1}" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_ecb_provider() {
+        let base_url = stub_server().await;
+        let provider = EcbProvider::new("ecb", &format!("{}/ecb.xml", base_url));
+
+        let observations = provider.fetch().await.unwrap();
+        assert_eq!(observations.len(), 3);
+
+        let cny = observations.iter().find(|o| o.target == "CNY").unwrap();
+        assert_eq!(cny.base, "EUR");
+        assert_eq!(cny.source, "ecb");
+        assert!((cny.rate - 7.8384).abs() < 1e-9);
+        assert_eq!(cny.observed_at, Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_json_provider() {
+        let base_url = stub_server().await;
+        let provider = JsonProvider::new("open-er", &format!("{}/latest.json", base_url));
+
+        let mut observations = provider.fetch().await.unwrap();
+        observations.sort_by(|a, b| a.target.cmp(&b.target));
+        let targets: Vec<&str> = observations.iter().map(|o| o.target.as_str()).collect();
+        assert_eq!(targets, vec!["CNY", "EUR"]);
+        assert_eq!(observations[0].base, "USD");
+        assert_eq!(observations[0].observed_at.timestamp(), 1710460800);
+
+        let broken = JsonProvider::new("broken", &format!("{}/broken.json", base_url));
+        assert!(broken.fetch().await.is_err());
+
+        let missing = JsonProvider::new("missing", &format!("{}/missing.json", base_url));
+        assert!(missing.fetch().await.is_err());
+    }
+
+    #[test]
+    fn test_manual_observation() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 15, 8, 0, 0).unwrap();
+        let observation = ManualProvider::observation("usd", "cny", 7.2, at).unwrap();
+        assert_eq!((observation.base.as_str(), observation.target.as_str()), ("USD", "CNY"));
+        assert_eq!(observation.source, ManualProvider::NAME);
+        assert_eq!(observation.rate, 7.2);
+        assert_eq!(observation.observed_at, at);
+        assert!(ManualProvider::observation("USD", "USD", 1.0, at).is_err());
+        assert!(ManualProvider::observation("USD", "CNY", -1.0, at).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+// 汇率轮询与融合 - 定期把各来源的观测写入历史库，查询时从历史库读取各来源最新值后做卡尔曼融合
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use common::{Error, ExchangeRateFusion, RateSource, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::providers::{
+    normalize_currency, EcbProvider, JsonProvider, ManualProvider, RateObservation, RateProvider,
+};
+use crate::store::{Candle, RateStore};
+use crate::AppState;
+
+/// 未注册来源 (如已下线的数据源) 的默认权重与相对噪声
+const UNKNOWN_SOURCE_WEIGHT: f64 = 0.1;
+const UNKNOWN_SOURCE_NOISE: f64 = 1e-4;
+
+/// 直接报价缺失时用于交叉换算的中间币种 (ECB以EUR为基准，多数JSON源以USD为基准)
+const TRIANGULATION_BASES: [&str; 2] = ["EUR", "USD"];
+
+/// 根据环境变量构建数据源列表
+///
+/// - `ECB_RATES_URL`：欧洲央行XML源，默认官方地址，设为空字符串可禁用
+/// - `JSON_RATES_URL` / `JSON_RATES_NAME`：可选的通用JSON源
+pub fn configured_providers(manual: Arc<ManualProvider>) -> Vec<Arc<dyn RateProvider>> {
+    let mut providers: Vec<Arc<dyn RateProvider>> = Vec::new();
+
+    let ecb_url = std::env::var("ECB_RATES_URL")
+        .unwrap_or_else(|_| "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml".to_string());
+    if !ecb_url.is_empty() {
+        providers.push(Arc::new(EcbProvider::new("ecb", &ecb_url)));
+    }
+
+    if let Ok(json_url) = std::env::var("JSON_RATES_URL") {
+        if !json_url.is_empty() {
+            let name = std::env::var("JSON_RATES_NAME").unwrap_or_else(|_| "json".to_string());
+            providers.push(Arc::new(JsonProvider::new(&name, &json_url)));
+        }
+    }
+
+    providers.push(manual);
+    providers
+}
+
+/// 拉取所有来源并写入历史库，单个来源失败不影响其他来源
+pub async fn poll_once(providers: &[Arc<dyn RateProvider>], store: &RateStore) {
+    for provider in providers {
+        match provider.fetch().await {
+            Ok(observations) => match store.record(&observations).await {
+                Ok(inserted) => tracing::info!(
+                    "Fetched {} rates from {}, {} new",
+                    observations.len(),
+                    provider.name(),
+                    inserted
+                ),
+                Err(e) => tracing::warn!("Failed to store rates from {}: {}", provider.name(), e),
+            },
+            Err(e) => tracing::warn!("Failed to fetch rates from {}: {}", provider.name(), e),
+        }
+    }
+}
+
+/// 启动后台轮询任务
+pub fn spawn_poller(state: Arc<AppState>, interval: std::time::Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            poll_once(&state.providers, &state.store).await;
+        }
+    });
+}
+
+/// 解析 "CNY/USD" 或 "CNY-USD" 形式的币种对
+pub fn parse_pair(pair: &str) -> Result<(String, String)> {
+    let parts: Vec<&str> = pair.split(['/', '-']).collect();
+    if parts.len() != 2 {
+        return Err(Error::InvalidInput("Invalid currency pair".to_string()));
+    }
+    let base = normalize_currency(parts[0]);
+    let target = normalize_currency(parts[1]);
+    match (base, target) {
+        (Some(base), Some(target)) => Ok((base, target)),
+        _ => Err(Error::InvalidInput("Invalid currency code".to_string())),
+    }
+}
+
+/// 把历史库中的观测转换为带观测时间的融合输入：正向直接使用，反向取倒数；
+/// 相对噪声按汇率量级换算为绝对方差，使不同量级的币种对可比
+fn to_rate_sources(
+    providers: &[Arc<dyn RateProvider>],
+    direct: Vec<RateObservation>,
+    inverse: Vec<RateObservation>,
+) -> Vec<(DateTime<Utc>, RateSource)> {
+    let direct = direct.into_iter().map(|o| (o.source, o.observed_at, o.rate));
+    let inverse = inverse.into_iter().map(|o| (o.source, o.observed_at, 1.0 / o.rate));
+
+    direct
+        .chain(inverse)
+        .map(|(source, observed_at, rate)| {
+            let (weight, relative_noise) = providers
+                .iter()
+                .find(|p| p.name() == source)
+                .map(|p| (p.weight(), p.noise_variance()))
+                .unwrap_or((UNKNOWN_SOURCE_WEIGHT, UNKNOWN_SOURCE_NOISE));
+
+            let source = RateSource {
+                name: source,
+                rate,
+                weight,
+                noise_variance: relative_noise * rate * rate,
+            };
+            (observed_at, source)
+        })
+        .collect()
+}
+
+/// 解析时间参数：RFC3339 时间点，或 YYYY-MM-DD 日期 (取当天开始；end_of_day 时取次日零点作为开区间上界)
+pub fn parse_time(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidInput(format!("Invalid date: {}", value)))?;
+    let date = if end_of_day { date.succ_opt().unwrap_or(date) } else { date };
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+/// 解析K线周期，返回 (PostgreSQL时间间隔, 桶长度)
+pub fn parse_interval(interval: &str) -> Result<(&'static str, Duration)> {
+    match interval {
+        "hour" | "1h" => Ok(("1 hour", Duration::hours(1))),
+        "day" | "1d" => Ok(("1 day", Duration::days(1))),
+        "week" | "1w" => Ok(("1 week", Duration::weeks(1))),
+        _ => Err(Error::InvalidInput("interval must be one of hour, day, week".to_string())),
+    }
+}
+
+/// 汇率查询结果，via 为交叉换算时使用的中间币种
+pub struct ResolvedRate {
+    pub rate: f64,
+    pub confidence: f64,
+    pub via: Option<String>,
+}
+
+/// 直接或反向报价的融合汇率；as_of 为空时融合进持久化的滤波状态，
+/// 否则只用当时可见的观测独立融合，不影响当前状态
+async fn leg_rate(
+    state: &AppState,
+    base: &str,
+    target: &str,
+    as_of: Option<DateTime<Utc>>,
+) -> Result<Option<(f64, f64)>> {
+    let until = as_of.unwrap_or_else(Utc::now);
+    let since = until - state.max_rate_age;
+    let direct = state.store.latest_per_source(base, target, since, until).await?;
+    let inverse = state.store.latest_per_source(target, base, since, until).await?;
+
+    let sources = to_rate_sources(&state.providers, direct, inverse);
+    if sources.is_empty() {
+        return Ok(None);
+    }
+
+    let pair = format!("{}/{}", base, target);
+    match as_of {
+        None => Ok(state.filters.fuse(&pair, &sources).await),
+        Some(_) => {
+            let sources: Vec<RateSource> = sources.into_iter().map(|(_, source)| source).collect();
+            let mut fusion = ExchangeRateFusion::new();
+            let fused = fusion.fuse_rates(&pair, &sources);
+            Ok(Some(fusion.get_rate_with_confidence(&pair).unwrap_or((fused, 1.0))))
+        }
+    }
+}
+
+/// 查询 base/target 的汇率 (as_of 为空表示当前)，没有直接报价时通过中间币种交叉换算
+pub async fn resolve_rate(
+    state: &AppState,
+    base: &str,
+    target: &str,
+    as_of: Option<DateTime<Utc>>,
+) -> Result<ResolvedRate> {
+    if base == target {
+        return Ok(ResolvedRate { rate: 1.0, confidence: 1.0, via: None });
+    }
+
+    if let Some((rate, confidence)) = leg_rate(state, base, target, as_of).await? {
+        return Ok(ResolvedRate { rate, confidence, via: None });
+    }
+
+    for via in TRIANGULATION_BASES.iter().filter(|via| **via != base && **via != target) {
+        let Some((base_rate, base_confidence)) = leg_rate(state, via, base, as_of).await? else {
+            continue;
+        };
+        let Some((target_rate, target_confidence)) = leg_rate(state, via, target, as_of).await? else {
+            continue;
+        };
+
+        return Ok(ResolvedRate {
+            rate: target_rate / base_rate,
+            confidence: base_confidence * target_confidence,
+            via: Some(via.to_string()),
+        });
+    }
+
+    Err(Error::NotFound(format!("No exchange rate available for {}/{}", base, target)))
+}
+
+/// 直接报价的K线，缺失时使用反向报价换算
+async fn leg_candles(
+    state: &AppState,
+    base: &str,
+    target: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    interval: &str,
+) -> Result<Vec<Candle>> {
+    let direct = state.store.candles(base, target, from, to, interval).await?;
+    if !direct.is_empty() {
+        return Ok(direct);
+    }
+
+    let inverse = state.store.candles(target, base, from, to, interval).await?;
+    Ok(inverse.iter().map(Candle::invert).collect())
+}
+
+/// 由 via/base 与 via/target 两条K线合成 base/target，只保留两侧都有数据的时间桶；
+/// 最高/最低价取两侧极值组合得到的区间边界
+fn cross_candles(base_leg: &[Candle], target_leg: &[Candle]) -> Vec<Candle> {
+    let base_by_time: HashMap<DateTime<Utc>, &Candle> = base_leg.iter().map(|c| (c.time, c)).collect();
+
+    target_leg
+        .iter()
+        .filter_map(|t| {
+            let b = base_by_time.get(&t.time)?;
+            Some(Candle {
+                time: t.time,
+                open: t.open / b.open,
+                high: t.high / b.low,
+                low: t.low / b.high,
+                close: t.close / b.close,
+                samples: t.samples.min(b.samples),
+            })
+        })
+        .collect()
+}
+
+/// 查询 [from, to) 内的K线序列，返回 (K线, 交叉换算使用的中间币种)
+pub async fn history(
+    state: &AppState,
+    base: &str,
+    target: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    interval: &str,
+) -> Result<(Vec<Candle>, Option<String>)> {
+    let candles = leg_candles(state, base, target, from, to, interval).await?;
+    if !candles.is_empty() {
+        return Ok((candles, None));
+    }
+
+    for via in TRIANGULATION_BASES.iter().filter(|via| **via != base && **via != target) {
+        let base_leg = leg_candles(state, via, base, from, to, interval).await?;
+        if base_leg.is_empty() {
+            continue;
+        }
+        let target_leg = leg_candles(state, via, target, from, to, interval).await?;
+        let crossed = cross_candles(&base_leg, &target_leg);
+        if !crossed.is_empty() {
+            return Ok((crossed, Some(via.to_string())));
+        }
+    }
+
+    Ok((Vec::new(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pair() {
+        assert_eq!(parse_pair("cny/usd").unwrap(), ("CNY".to_string(), "USD".to_string()));
+        assert_eq!(parse_pair("EUR-JPY").unwrap(), ("EUR".to_string(), "JPY".to_string()));
+        assert!(parse_pair("CNYUSD").is_err());
+        assert!(parse_pair("CN/USD").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_to_rate_sources_inverts_and_scales_noise() {
+        // 只用到来源的权重与噪声，不会连接数据库
+        let pool = sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/abook").unwrap();
+        let manual = Arc::new(ManualProvider::new(RateStore::new(pool)));
+        let providers: Vec<Arc<dyn RateProvider>> = vec![manual];
+        let at = Utc::now();
+
+        let direct = vec![RateObservation::new("manual", "USD", "CNY", 7.2, at).unwrap()];
+        let inverse = vec![RateObservation::new("retired", "CNY", "USD", 0.125, at).unwrap()];
+
+        let sources = to_rate_sources(&providers, direct, inverse);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].0, at);
+        assert_eq!(sources[0].1.weight, 0.2);
+        assert!((sources[0].1.noise_variance - 2.5e-5 * 7.2 * 7.2).abs() < 1e-12);
+        assert_eq!(sources[1].1.rate, 8.0);
+        assert_eq!(sources[1].1.weight, UNKNOWN_SOURCE_WEIGHT);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let start = parse_time("2024-03-15", false).unwrap();
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap());
+        let end = parse_time("2024-03-15", true).unwrap();
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 3, 16, 0, 0, 0).unwrap());
+        let instant = parse_time("2024-03-15T08:30:00+08:00", true).unwrap();
+        assert_eq!(instant, Utc.with_ymd_and_hms(2024, 3, 15, 0, 30, 0).unwrap());
+        assert!(parse_time("15/03/2024", false).is_err());
+    }
+
+    #[test]
+    fn test_cross_candles() {
+        let day = |d| Utc.with_ymd_and_hms(2024, 3, d, 0, 0, 0).unwrap();
+        let candle = |d, open, high, low, close| Candle { time: day(d), open, high, low, close, samples: 2 };
+
+        // EUR/CNY 与 EUR/USD 合成 CNY/USD
+        let eur_cny = vec![candle(1, 8.0, 8.0, 7.8, 7.8), candle(2, 7.8, 7.9, 7.8, 7.9)];
+        let eur_usd = vec![candle(1, 1.12, 1.12, 1.092, 1.092), candle(3, 1.1, 1.1, 1.1, 1.1)];
+
+        let crossed = cross_candles(&eur_cny, &eur_usd);
+        assert_eq!(crossed.len(), 1);
+        assert_eq!(crossed[0].time, day(1));
+        assert!((crossed[0].open - 0.14).abs() < 1e-12);
+        assert!((crossed[0].close - 0.14).abs() < 1e-12);
+        assert!((crossed[0].high - 1.12 / 7.8).abs() < 1e-12);
+        assert!((crossed[0].low - 1.092 / 8.0).abs() < 1e-12);
+
+        let inverted = crossed[0].invert();
+        assert!((inverted.high - 8.0 / 1.092).abs() < 1e-9);
+        assert!((inverted.open - 1.0 / 0.14).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,191 @@
+// 汇率存储 - TimescaleDB 中的 exchange_rate_history 超表，以及管理员维护的手动汇率表
+use chrono::{DateTime, Utc};
+use common::Result;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::providers::RateObservation;
+
+type ObservationRow = (String, String, String, f64, DateTime<Utc>);
+
+fn from_row((source, base, target, rate, observed_at): ObservationRow) -> RateObservation {
+    RateObservation {
+        source,
+        base,
+        target,
+        rate,
+        observed_at,
+    }
+}
+
+/// 按时间桶聚合的K线数据
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candle {
+    pub time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub samples: i64,
+}
+
+impl Candle {
+    /// 换算为反向币种对的K线 (最高价与最低价互换)
+    pub fn invert(&self) -> Self {
+        Self {
+            time: self.time,
+            open: 1.0 / self.open,
+            high: 1.0 / self.low,
+            low: 1.0 / self.high,
+            close: 1.0 / self.close,
+            samples: self.samples,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateStore {
+    pool: PgPool,
+}
+
+impl RateStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// 批量写入观测，同一来源同一时刻的重复数据被忽略，返回实际写入条数
+    pub async fn record(&self, observations: &[RateObservation]) -> Result<u64> {
+        if observations.is_empty() {
+            return Ok(0);
+        }
+
+        let times: Vec<DateTime<Utc>> = observations.iter().map(|o| o.observed_at).collect();
+        let bases: Vec<String> = observations.iter().map(|o| o.base.clone()).collect();
+        let targets: Vec<String> = observations.iter().map(|o| o.target.clone()).collect();
+        let rates: Vec<f64> = observations.iter().map(|o| o.rate).collect();
+        let sources: Vec<String> = observations.iter().map(|o| o.source.clone()).collect();
+
+        let result = sqlx::query(
+            "INSERT INTO exchange_rate_history (time, base_currency, target_currency, rate, source)
+             SELECT * FROM UNNEST($1::timestamptz[], $2::varchar[], $3::varchar[], $4::float8[], $5::varchar[])
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(&times)
+        .bind(&bases)
+        .bind(&targets)
+        .bind(&rates)
+        .bind(&sources)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 查询某个币种对在 [since, until) 内每个来源的最新观测
+    pub async fn latest_per_source(
+        &self,
+        base: &str,
+        target: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<RateObservation>> {
+        let rows: Vec<ObservationRow> = sqlx::query_as(
+            "SELECT DISTINCT ON (source) source, base_currency::text, target_currency::text, rate::float8, time
+             FROM exchange_rate_history
+             WHERE base_currency = $1 AND target_currency = $2 AND time >= $3 AND time < $4
+             ORDER BY source, time DESC",
+        )
+        .bind(base)
+        .bind(target)
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(from_row).collect())
+    }
+
+    /// 按时间桶聚合 [from, to) 内的观测 (合并所有来源)，interval 为PostgreSQL时间间隔如 '1 day'
+    pub async fn candles(
+        &self,
+        base: &str,
+        target: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        interval: &str,
+    ) -> Result<Vec<Candle>> {
+        let rows: Vec<(DateTime<Utc>, f64, f64, f64, f64, i64)> = sqlx::query_as(
+            "SELECT time_bucket($5::interval, time) AS bucket,
+                    first(rate, time)::float8, max(rate)::float8, min(rate)::float8, last(rate, time)::float8,
+                    count(*)
+             FROM exchange_rate_history
+             WHERE base_currency = $1 AND target_currency = $2 AND time >= $3 AND time < $4
+             GROUP BY bucket
+             ORDER BY bucket",
+        )
+        .bind(base)
+        .bind(target)
+        .bind(from)
+        .bind(to)
+        .bind(interval)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(time, open, high, low, close, samples)| Candle {
+                time,
+                open,
+                high,
+                low,
+                close,
+                samples,
+            })
+            .collect())
+    }
+
+    /// 保存手动汇率并写入历史库，两者在同一事务中完成
+    pub async fn save_manual_rate(&self, observation: &RateObservation) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO manual_exchange_rates (base_currency, target_currency, rate, updated_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (base_currency, target_currency) DO UPDATE
+             SET rate = EXCLUDED.rate, updated_at = EXCLUDED.updated_at",
+        )
+        .bind(&observation.base)
+        .bind(&observation.target)
+        .bind(observation.rate)
+        .bind(observation.observed_at)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO exchange_rate_history (time, base_currency, target_currency, rate, source)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(observation.observed_at)
+        .bind(&observation.base)
+        .bind(&observation.target)
+        .bind(observation.rate)
+        .bind(&observation.source)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 当前的手动汇率，观测时间为设置时间
+    pub async fn manual_rates(&self, source: &str) -> Result<Vec<RateObservation>> {
+        let rows: Vec<(String, String, f64, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT base_currency::text, target_currency::text, rate, updated_at FROM manual_exchange_rates",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(base, target, rate, observed_at)| from_row((source.to_string(), base, target, rate, observed_at)))
+            .collect())
+    }
+}
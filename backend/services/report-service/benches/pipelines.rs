@@ -0,0 +1,156 @@
+// 报表聚合管道基准 - 对比逐条拉回交易在服务内存中累加与 MongoDB 聚合管道的耗时
+// 需要可用的 MongoDB：MONGO_URI=mongodb://localhost:27017 BENCH_ROWS=200000 cargo bench -p report-service
+use chrono::{DateTime, Duration, Utc};
+use common::test_util::TransactionBuilder;
+use common::{Calendar, Period, Transaction, TransactionSplit};
+use criterion::{criterion_group, criterion_main, Criterion};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::ClientOptions,
+    Client, Database,
+};
+use report_service::pipelines::{category_breakdown, daily_trend, ensure_indexes, monthly_summary};
+use std::collections::HashMap;
+
+const USER_ID: &str = "bench-user";
+
+struct Folded {
+    income: f64,
+    expense: f64,
+    by_category: HashMap<String, f64>,
+    by_day: HashMap<String, (f64, f64)>,
+}
+
+/// 旧实现：把交易逐条拉回服务端，在 HashMap 中累加
+async fn fold_in_process(db: &Database, start: DateTime<Utc>, end: DateTime<Utc>) -> Folded {
+    let filter = doc! {
+        "user_id": USER_ID,
+        "transaction_date": {
+            "$gte": bson::to_bson(&start).unwrap(),
+            "$lt": bson::to_bson(&end).unwrap(),
+        }
+    };
+    let mut cursor = db.collection::<Transaction>("transactions").find(filter, None).await.unwrap();
+
+    let mut folded = Folded { income: 0.0, expense: 0.0, by_category: HashMap::new(), by_day: HashMap::new() };
+    while cursor.advance().await.unwrap() {
+        let tx = cursor.deserialize_current().unwrap();
+        let day = folded
+            .by_day
+            .entry(tx.transaction_date.format("%Y-%m-%d").to_string())
+            .or_insert((0.0, 0.0));
+        match tx.transaction_type.as_str() {
+            "income" => {
+                folded.income += tx.amount;
+                day.0 += tx.amount;
+            }
+            "expense" => {
+                folded.expense += tx.amount;
+                day.1 += tx.amount;
+                for (category_id, amount) in tx.category_amounts() {
+                    *folded.by_category.entry(category_id.to_string()).or_insert(0.0) += amount;
+                }
+            }
+            _ => {}
+        }
+    }
+    folded
+}
+
+/// 连接 MongoDB；不可用时返回 None
+async fn connect() -> Option<Client> {
+    let uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+    let mut options = ClientOptions::parse(&uri).await.ok()?;
+    options.server_selection_timeout = Some(std::time::Duration::from_secs(2));
+    let client = Client::with_options(options).ok()?;
+    client.database("admin").run_command(doc! { "ping": 1 }, None).await.ok()?;
+    Some(client)
+}
+
+/// 生成约三年的历史数据，每 10 笔支出有一笔拆出一部分到购物
+async fn seed(db: &Database, rows: usize, origin: DateTime<Utc>) {
+    let categories = ["dining", "transport", "shopping", "housing", "salary", "bonus"];
+    let batch: Vec<Transaction> = (0..rows)
+        .map(|i| {
+            let date = origin + Duration::minutes((i as i64 * 7919) % (3 * 365 * 24 * 60));
+            let category = categories[i % categories.len()];
+            let transaction_type = if category == "salary" || category == "bonus" { "income" } else { "expense" };
+            let amount = ((i % 997) as f64 * 137.0).round() / 100.0 + 1.0;
+            let builder = TransactionBuilder::new(transaction_type, amount)
+                .id(&ObjectId::new().to_hex())
+                .user(USER_ID)
+                .account("bench-account")
+                .category(category)
+                .description(&format!("bench {}", i))
+                .date(date);
+            let builder = if transaction_type == "expense" && category != "shopping" && i % 10 == 1 {
+                builder.splits(vec![
+                    TransactionSplit { category_id: category.to_string(), amount: amount - 0.5, note: None },
+                    TransactionSplit { category_id: "shopping".to_string(), amount: 0.5, note: None },
+                ])
+            } else {
+                builder
+            };
+            builder.build()
+        })
+        .collect();
+    for chunk in batch.chunks(10_000) {
+        db.collection::<Transaction>("transactions").insert_many(chunk, None).await.unwrap();
+    }
+    ensure_indexes(db).await.unwrap();
+}
+
+/// 聚合管道的结果必须与逐条累加一致，否则比较耗时没有意义
+async fn assert_same_results(db: &Database, start: DateTime<Utc>, end: DateTime<Utc>) {
+    let folded = fold_in_process(db, start, end).await;
+    let monthly = monthly_summary(db, USER_ID, start, end).await.unwrap();
+    let categories = category_breakdown(db, USER_ID, start, end).await.unwrap();
+    let trend = daily_trend(db, USER_ID, start, end, &Calendar::utc(), Period::Day).await.unwrap();
+
+    assert!((monthly.total_income - folded.income).abs() < 1e-6);
+    assert!((monthly.total_expense - folded.expense).abs() < 1e-6);
+    assert_eq!(categories.len(), folded.by_category.len());
+    for report in &categories {
+        assert!((folded.by_category[&report.category_id] - report.amount).abs() < 1e-6);
+    }
+    assert_eq!(trend.len(), folded.by_day.len());
+    for day in &trend {
+        let (income, expense) = folded.by_day[&day.date];
+        assert!((day.income - income).abs() < 1e-6);
+        assert!((day.expense - expense).abs() < 1e-6);
+    }
+}
+
+fn bench_pipelines(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let Some(client) = runtime.block_on(connect()) else {
+        eprintln!("MongoDB is not reachable, skipping report pipeline benchmarks");
+        return;
+    };
+    let db = client.database(&format!("abook_bench_{}", ObjectId::new().to_hex()));
+    let rows: usize = std::env::var("BENCH_ROWS").ok().and_then(|v| v.parse().ok()).unwrap_or(200_000);
+    let end = Utc::now();
+    let start = end - Duration::days(3 * 365);
+
+    runtime.block_on(seed(&db, rows, start));
+    runtime.block_on(assert_same_results(&db, start, end));
+
+    let mut group = c.benchmark_group(format!("reports/{}_transactions", rows));
+    group.sample_size(10);
+    group.bench_function("in_process_fold", |b| {
+        b.to_async(&runtime).iter(|| fold_in_process(&db, start, end))
+    });
+    group.bench_function("aggregation_pipelines", |b| {
+        b.to_async(&runtime).iter(|| async {
+            monthly_summary(&db, USER_ID, start, end).await.unwrap();
+            category_breakdown(&db, USER_ID, start, end).await.unwrap();
+            daily_trend(&db, USER_ID, start, end, &Calendar::utc(), Period::Day).await.unwrap();
+        })
+    });
+    group.finish();
+
+    runtime.block_on(db.drop(None)).unwrap();
+}
+
+criterion_group!(benches, bench_pipelines);
+criterion_main!(benches);
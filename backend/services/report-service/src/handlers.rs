@@ -1,20 +1,22 @@
 use axum::{
-    extract::{Query, State, Extension},
+    extract::{Query, State},
     Json,
 };
-use common::{Transaction, ApiResponse, Claims, Result, get_category_name};
-use mongodb::bson::doc;
+use common::{ApiResponse, Calendar, Period, Result};
+use common::middleware::{scope, Authorized};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use chrono::Datelike;
+use chrono::{DateTime, Duration, Utc};
 
-use crate::AppState;
+use crate::{pipelines, AppState};
 
 #[derive(Deserialize)]
 pub struct ReportQuery {
+    /// RFC3339 时刻或 YYYY-MM-DD (用户时区的本地日期，终点包含当天)
     pub start_date: Option<String>,
     pub end_date: Option<String>,
+    /// 趋势分桶粒度：day (默认)、week、month、year
+    pub interval: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -46,199 +48,105 @@ pub struct DailyData {
     pub expense: f64,
 }
 
+/// 解析查询区间 [起点, 终点)；未指定起点时使用 default_start，未指定终点时为当前时刻
+fn parse_range(
+    calendar: &Calendar,
+    query: &ReportQuery,
+    default_start: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = match &query.start_date {
+        Some(value) => calendar.parse_bound(value, false)?,
+        None => default_start,
+    };
+    let end = match &query.end_date {
+        Some(value) => calendar.parse_bound(value, true)?,
+        None => Utc::now(),
+    };
+    Ok((start, end))
+}
+
 pub async fn monthly_report(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::ReportsRead>,
     Query(query): Query<ReportQuery>,
 ) -> Result<Json<ApiResponse<MonthlyReport>>> {
-    let collection = state.db.mongo.collection::<Transaction>("transactions");
-    
-    let start_date = if let Some(date_str) = &query.start_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        let now = chrono::Utc::now();
-        now.with_day(1).unwrap().with_timezone(&chrono::Utc)
-    };
-
-    let end_date = if let Some(date_str) = &query.end_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        chrono::Utc::now()
-    };
-    
-    let filter = doc! {
-        "user_id": &claims.user_id,
-        "transaction_date": {
-            "$gte": mongodb::bson::to_bson(&start_date).unwrap(),
-            "$lte": mongodb::bson::to_bson(&end_date).unwrap(),
-        }
-    };
-    
-    let mut cursor = collection.find(filter, None).await?;
-    
-    let mut total_income = 0.0;
-    let mut total_expense = 0.0;
-    let mut transaction_count = 0;
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let (month_start, _) = calendar.period_range(Period::Month, Utc::now());
+    let (start_date, end_date) = parse_range(&calendar, &query, month_start)?;
     
-    while cursor.advance().await? {
-        let tx: Transaction = cursor.deserialize_current()?;
-        transaction_count += 1;
-        
-        match tx.transaction_type.as_str() {
-            "income" => total_income += tx.amount,
-            "expense" => total_expense += tx.amount,
-            _ => {}
-        }
-    }
+    let report = pipelines::monthly_summary(&state.db.mongo, &claims.user_id, start_date, end_date).await?;
     
-    let net_income = total_income - total_expense;
-    
-    Ok(Json(ApiResponse::success(MonthlyReport {
-        total_income,
-        total_expense,
-        net_income,
-        transaction_count,
-        top_categories: Vec::new(),
-    })))
+    Ok(Json(ApiResponse::success(report)))
 }
 
 pub async fn category_report(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::ReportsRead>,
     Query(query): Query<ReportQuery>,
 ) -> Result<Json<ApiResponse<Vec<CategoryReport>>>> {
-    let collection = state.db.mongo.collection::<Transaction>("transactions");
-    
-    let start_date = if let Some(date_str) = &query.start_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        let now = chrono::Utc::now();
-        now.with_day(1).unwrap().with_timezone(&chrono::Utc)
-    };
-
-    let end_date = if let Some(date_str) = &query.end_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        chrono::Utc::now()
-    };
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let (month_start, _) = calendar.period_range(Period::Month, Utc::now());
+    let (start_date, end_date) = parse_range(&calendar, &query, month_start)?;
 
-    let filter = doc! { 
-        "user_id": &claims.user_id,
-        "transaction_date": {
-            "$gte": mongodb::bson::to_bson(&start_date).unwrap(),
-            "$lte": mongodb::bson::to_bson(&end_date).unwrap(),
-        }
-    };
-    let mut cursor = collection.find(filter, None).await?;
-    
-    let mut category_totals: HashMap<String, f64> = HashMap::new();
-    let mut total_amount = 0.0;
-    
-    while cursor.advance().await? {
-        let tx: Transaction = cursor.deserialize_current()?;
-        if tx.transaction_type == "expense" {
-            *category_totals.entry(tx.category_id.clone()).or_insert(0.0) += tx.amount;
-            total_amount += tx.amount;
-        }
-    }
-    
-    let mut reports: Vec<CategoryReport> = category_totals
-        .into_iter()
-        .map(|(category_id, amount)| {
-            let percentage = if total_amount > 0.0 {
-                (amount / total_amount) * 100.0
-            } else {
-                0.0
-            };
-            
-            CategoryReport {
-                category_name: get_category_name(&category_id),
-                category_id,
-                amount,
-                percentage,
-            }
-        })
-        .collect();
-    
-    reports.sort_by(|a, b| b.amount.partial_cmp(&a.amount).unwrap());
+    let reports = pipelines::category_breakdown(&state.db.mongo, &claims.user_id, start_date, end_date).await?;
     
     Ok(Json(ApiResponse::success(reports)))
 }
 
 pub async fn trend_report(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::ReportsRead>,
     Query(query): Query<ReportQuery>,
 ) -> Result<Json<ApiResponse<TrendReport>>> {
-    let collection = state.db.mongo.collection::<Transaction>("transactions");
-    
-    let start_date = if let Some(date_str) = &query.start_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        let now = chrono::Utc::now();
-        now - chrono::Duration::days(30)
-    };
+    let calendar = Calendar::for_user(&state.db.mongo, &claims.user_id).await?;
+    let interval = query.interval.as_deref().map(Period::parse).transpose()?.unwrap_or(Period::Day);
+    // 默认最近 30 个自然日 (含今天)
+    let default_start = calendar.start_of_day(calendar.local_date(Utc::now()) - Duration::days(29));
+    let (start_date, end_date) = parse_range(&calendar, &query, default_start)?;
 
-    let end_date = if let Some(date_str) = &query.end_date {
-        chrono::DateTime::parse_from_rfc3339(date_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|_| chrono::Utc::now())
-    } else {
-        chrono::Utc::now()
-    };
-    
-    let filter = doc! {
-        "user_id": &claims.user_id,
-        "transaction_date": {
-            "$gte": mongodb::bson::to_bson(&start_date).unwrap(),
-            "$lte": mongodb::bson::to_bson(&end_date).unwrap(),
-        }
-    };
-    
-    let mut cursor = collection.find(filter, None).await?;
-    let mut daily_map: HashMap<String, (f64, f64)> = HashMap::new();
-    
-    while cursor.advance().await? {
-        let tx: Transaction = cursor.deserialize_current()?;
-        let date_str = tx.transaction_date.format("%Y-%m-%d").to_string();
-        let entry = daily_map.entry(date_str).or_insert((0.0, 0.0));
-        
-        match tx.transaction_type.as_str() {
-            "income" => entry.0 += tx.amount,
-            "expense" => entry.1 += tx.amount,
-            _ => {}
-        }
-    }
-    
-    let mut daily_data: Vec<DailyData> = daily_map
-        .into_iter()
-        .map(|(date, (income, expense))| DailyData {
-            date,
-            income,
-            expense,
-        })
-        .collect();
-    
-    // Sort by date
-    daily_data.sort_by(|a, b| a.date.cmp(&b.date));
+    let daily_data =
+        pipelines::daily_trend(&state.db.mongo, &claims.user_id, start_date, end_date, &calendar, interval).await?;
     
     Ok(Json(ApiResponse::success(TrendReport { daily_data })))
 }
 
 pub async fn export_report(
     State(_state): State<Arc<AppState>>,
-    Extension(_claims): Extension<Claims>,
+    _: Authorized<scope::ReportsRead>,
 ) -> Result<Json<ApiResponse<String>>> {
     // 简化实现，实际应生成CSV/PDF
     Ok(Json(ApiResponse::success("Report export functionality".to_string())))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use common::Tz;
+
+    fn query(start_date: Option<&str>, end_date: Option<&str>) -> ReportQuery {
+        ReportQuery {
+            start_date: start_date.map(str::to_string),
+            end_date: end_date.map(str::to_string),
+            interval: None,
+        }
+    }
+
+    #[test]
+    fn test_end_date_resolves_to_next_local_midnight() {
+        let calendar = Calendar::new(Tz::Asia__Shanghai);
+        let (start, end) = parse_range(&calendar, &query(Some("2024-03-01"), Some("2024-03-31")), Utc::now()).unwrap();
+
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 2, 29, 16, 0, 0).unwrap());
+        // 3月31日整天都包含在内，区间止于4月1日本地零点 (不含)
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 3, 31, 16, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_rfc3339_end_is_kept_as_exclusive_instant() {
+        let calendar = Calendar::new(Tz::Asia__Shanghai);
+        let (_, end) = parse_range(&calendar, &query(None, Some("2024-04-01T00:00:00+08:00")), Utc::now()).unwrap();
+
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 3, 31, 16, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,32 @@
+pub mod handlers;
+pub mod pipelines;
+
+use axum::{
+    routing::get,
+    Router,
+    middleware,
+};
+use common::{DatabaseConnection, JwtManager, middleware::{auth_middleware, AuthState, RateLimit, RateLimitLayer}};
+use std::sync::Arc;
+
+pub struct AppState {
+    pub db: DatabaseConnection,
+}
+
+pub fn create_router(db: DatabaseConnection, jwt: Arc<JwtManager>) -> Router {
+    let state = Arc::new(AppState { db: db.clone() });
+    
+    Router::new()
+        .route("/reports/monthly", get(handlers::monthly_report))
+        .route("/reports/category", get(handlers::category_report))
+        .route("/reports/trend", get(handlers::trend_report))
+        .route(
+            "/reports/export",
+            get(handlers::export_report)
+                .layer(RateLimitLayer::per_user(&db.redis, RateLimit::per_hour("export", 10))),
+        )
+        .layer(RateLimitLayer::per_user(&db.redis, RateLimit::global_user()))
+        .layer(middleware::from_fn_with_state(AuthState::new(jwt.clone(), db.clone()), auth_middleware))
+        .layer(RateLimitLayer::per_ip(&db.redis, RateLimit::global_ip()))
+        .with_state(state)
+}
@@ -1,33 +1,14 @@
-mod handlers;
-
-use axum::{
-    routing::get,
-    Router,
-    middleware,
-};
-use common::{DatabaseConnection, middleware::auth_middleware};
+use common::{DatabaseConnection, JwtManager, KeyRing};
+use report_service::{create_router, pipelines};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-pub struct AppState {
-    pub db: DatabaseConnection,
-}
-
-pub fn create_router(db: DatabaseConnection) -> Router {
-    let state = Arc::new(AppState { db: db.clone() });
-    
-    Router::new()
-        .route("/reports/monthly", get(handlers::monthly_report))
-        .route("/reports/category", get(handlers::category_report))
-        .route("/reports/trend", get(handlers::trend_report))
-        .route("/reports/export", get(handlers::export_report))
-        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware))
-        .with_state(state)
-}
-
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
     
+    let jwt = Arc::new(JwtManager::new(KeyRing::from_env().expect("Failed to load JWT keys")));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
@@ -36,7 +17,11 @@ async fn main() {
         .await
         .expect("Failed to connect to database");
     
-    let app = create_router(db);
+    if let Err(e) = pipelines::ensure_indexes(&db.mongo).await {
+        tracing::warn!("Failed to create report indexes: {}", e);
+    }
+    
+    let app = create_router(db, jwt);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3004")
         .await
@@ -44,7 +29,7 @@ async fn main() {
     
     tracing::info!("Report service listening on port 3004");
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
@@ -0,0 +1,307 @@
+// 报表聚合管道 - 在MongoDB端完成分组求和，避免把全部交易拉到服务内存中
+use chrono::{DateTime, Utc};
+use common::{get_category_name, Calendar, Category, Period, Result, Transaction};
+use mongodb::{
+    bson::{self, doc, Document},
+    options::IndexOptions,
+    Database, IndexModel,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::handlers::{CategoryReport, DailyData, MonthlyReport};
+
+/// 月报中返回的分类数量
+const TOP_CATEGORY_LIMIT: i64 = 5;
+
+#[derive(Deserialize)]
+struct TypeTotal {
+    #[serde(rename = "_id")]
+    transaction_type: Option<String>,
+    amount: f64,
+    count: i64,
+}
+
+#[derive(Deserialize)]
+struct CategoryTotal {
+    #[serde(rename = "_id")]
+    category_id: String,
+    amount: f64,
+}
+
+#[derive(Deserialize)]
+struct MonthlyFacets {
+    totals: Vec<TypeTotal>,
+    top_categories: Vec<CategoryTotal>,
+}
+
+#[derive(Deserialize)]
+struct DailyTotal {
+    #[serde(rename = "_id")]
+    date: String,
+    income: f64,
+    expense: f64,
+}
+
+/// 创建报表查询依赖的复合索引
+pub async fn ensure_indexes(db: &Database) -> Result<()> {
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! { "user_id": 1, "transaction_date": 1 })
+            .options(IndexOptions::builder().name("user_date".to_string()).build())
+            .build(),
+        IndexModel::builder()
+            .keys(doc! { "user_id": 1, "category_id": 1, "transaction_date": 1 })
+            .options(IndexOptions::builder().name("user_category_date".to_string()).build())
+            .build(),
+    ];
+
+    db.collection::<Transaction>("transactions")
+        .create_indexes(indexes, None)
+        .await?;
+    Ok(())
+}
+
+/// 按左闭右开区间 [start, end) 筛选交易：终点时刻本身不计入，恰好落在次日本地零点的交易
+/// (例如周期交易) 只计入次日，保证相邻区间既不重复也不遗漏
+fn match_stage(user_id: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Document {
+    doc! {
+        "$match": {
+            "user_id": user_id,
+            "transaction_date": {
+                "$gte": bson::to_bson(&start).unwrap(),
+                "$lt": bson::to_bson(&end).unwrap(),
+            }
+        }
+    }
+}
+
+/// 交易日期以RFC3339字符串存储，分桶前需先转换为日期；按用户时区 (IANA 名称) 划分周期，
+/// 分桶键与 Calendar::bucket_key 一致
+fn bucket_expression(timezone: &str, interval: Period) -> Document {
+    doc! {
+        "$dateToString": {
+            "format": interval.key_format(),
+            "date": { "$toDate": "$transaction_date" },
+            "timezone": timezone,
+        }
+    }
+}
+
+/// 按分类分组求和：拆分交易展开为各拆分行，分别计入各自的分类
+fn category_group_stages() -> [Document; 2] {
+    [
+        doc! { "$unwind": { "path": "$splits", "preserveNullAndEmptyArrays": true } },
+        doc! {
+            "$group": {
+                "_id": { "$ifNull": ["$splits.category_id", "$category_id"] },
+                "amount": { "$sum": { "$ifNull": ["$splits.amount", "$amount"] } },
+            }
+        },
+    ]
+}
+
+pub fn monthly_pipeline(user_id: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Document> {
+    let [unwind, group] = category_group_stages();
+    vec![
+        match_stage(user_id, start, end),
+        doc! {
+            "$facet": {
+                "totals": [
+                    { "$group": {
+                        "_id": "$transaction_type",
+                        "amount": { "$sum": "$amount" },
+                        "count": { "$sum": 1 },
+                    } },
+                ],
+                "top_categories": [
+                    { "$match": { "transaction_type": "expense" } },
+                    unwind,
+                    group,
+                    { "$sort": { "amount": -1, "_id": 1 } },
+                    { "$limit": TOP_CATEGORY_LIMIT },
+                ],
+            }
+        },
+    ]
+}
+
+pub fn category_pipeline(user_id: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<Document> {
+    let [unwind, group] = category_group_stages();
+    vec![
+        match_stage(user_id, start, end),
+        doc! { "$match": { "transaction_type": "expense" } },
+        unwind,
+        group,
+        doc! { "$sort": { "amount": -1, "_id": 1 } },
+    ]
+}
+
+pub fn trend_pipeline(
+    user_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timezone: &str,
+    interval: Period,
+) -> Vec<Document> {
+    vec![
+        match_stage(user_id, start, end),
+        doc! {
+            "$group": {
+                "_id": bucket_expression(timezone, interval),
+                "income": { "$sum": { "$cond": [{ "$eq": ["$transaction_type", "income"] }, "$amount", 0.0] } },
+                "expense": { "$sum": { "$cond": [{ "$eq": ["$transaction_type", "expense"] }, "$amount", 0.0] } },
+            }
+        },
+        doc! { "$sort": { "_id": 1 } },
+    ]
+}
+
+async fn run<T: serde::de::DeserializeOwned>(db: &Database, pipeline: Vec<Document>) -> Result<Vec<T>> {
+    let mut cursor = db
+        .collection::<Transaction>("transactions")
+        .aggregate(pipeline, None)
+        .await?;
+
+    let mut rows = Vec::new();
+    while cursor.advance().await? {
+        let document = cursor.deserialize_current()?;
+        rows.push(
+            bson::from_document(document)
+                .map_err(|e| common::Error::Database(format!("Unexpected aggregation result: {}", e)))?,
+        );
+    }
+    Ok(rows)
+}
+
+/// 用户分类的显示名称 (含用户改过名的系统分类)，按交易引用的标识索引
+async fn category_names(db: &Database, user_id: &str) -> Result<HashMap<String, String>> {
+    let mut cursor = db
+        .collection::<Category>("categories")
+        .find(doc! { "user_id": user_id }, None)
+        .await?;
+
+    let mut names = HashMap::new();
+    while cursor.advance().await? {
+        let category = cursor.deserialize_current()?;
+        names.insert(category.reference_id().to_string(), category.name);
+    }
+    Ok(names)
+}
+
+fn to_category_reports(
+    totals: Vec<CategoryTotal>,
+    total_amount: f64,
+    names: &HashMap<String, String>,
+) -> Vec<CategoryReport> {
+    totals
+        .into_iter()
+        .map(|total| {
+            let percentage = if total_amount > 0.0 {
+                (total.amount / total_amount) * 100.0
+            } else {
+                0.0
+            };
+
+            CategoryReport {
+                category_name: names
+                    .get(&total.category_id)
+                    .cloned()
+                    .unwrap_or_else(|| get_category_name(&total.category_id)),
+                category_id: total.category_id,
+                amount: total.amount,
+                percentage,
+            }
+        })
+        .collect()
+}
+
+pub async fn monthly_summary(
+    db: &Database,
+    user_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<MonthlyReport> {
+    let facets = run::<MonthlyFacets>(db, monthly_pipeline(user_id, start, end))
+        .await?
+        .pop()
+        .unwrap_or(MonthlyFacets {
+            totals: Vec::new(),
+            top_categories: Vec::new(),
+        });
+
+    let mut total_income = 0.0;
+    let mut total_expense = 0.0;
+    let mut transaction_count = 0;
+
+    for total in &facets.totals {
+        transaction_count += total.count as u32;
+        match total.transaction_type.as_deref() {
+            Some("income") => total_income += total.amount,
+            Some("expense") => total_expense += total.amount,
+            _ => {}
+        }
+    }
+
+    Ok(MonthlyReport {
+        total_income,
+        total_expense,
+        net_income: total_income - total_expense,
+        transaction_count,
+        top_categories: to_category_reports(facets.top_categories, total_expense, &category_names(db, user_id).await?),
+    })
+}
+
+pub async fn category_breakdown(
+    db: &Database,
+    user_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CategoryReport>> {
+    let totals = run::<CategoryTotal>(db, category_pipeline(user_id, start, end)).await?;
+    let total_amount: f64 = totals.iter().map(|t| t.amount).sum();
+
+    Ok(to_category_reports(totals, total_amount, &category_names(db, user_id).await?))
+}
+
+pub async fn daily_trend(
+    db: &Database,
+    user_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    calendar: &Calendar,
+    interval: Period,
+) -> Result<Vec<DailyData>> {
+    let totals = run::<DailyTotal>(db, trend_pipeline(user_id, start, end, calendar.tz().name(), interval)).await?;
+
+    Ok(totals
+        .into_iter()
+        .map(|total| DailyData {
+            date: total.date,
+            income: total.income,
+            expense: total.expense,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_match_stage_excludes_end_instant() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap();
+        let stage = match_stage("user", start, end);
+        let range = stage
+            .get_document("$match")
+            .unwrap()
+            .get_document("transaction_date")
+            .unwrap();
+
+        assert_eq!(range.get("$gte"), Some(&bson::to_bson(&start).unwrap()));
+        assert_eq!(range.get("$lt"), Some(&bson::to_bson(&end).unwrap()));
+        assert!(range.get("$lte").is_none());
+    }
+}
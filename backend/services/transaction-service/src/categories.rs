@@ -0,0 +1,675 @@
+// 分类管理 - 系统分类预置、层级 (最多两级)、排序、归档、删除与合并
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use common::middleware::{scope, Authorized};
+use common::{
+    commit_or_abort, find_category, reference_filter, seed_system_categories, ApiResponse, Budget, Category,
+    CategoryRule, Error, RecurringTransaction, Result, Transaction, CATEGORY_TYPE_EXPENSE, CATEGORY_TYPE_INCOME,
+};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::{FindOptions, IndexOptions, UpdateOptions},
+    ClientSession, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{suggest, AppState};
+
+/// 系统分类按 (user_id, key) 唯一，并发预置时不会插入重复的分类；与 init-mongo.js 中的定义一致
+pub async fn ensure_indexes(db: &Database) -> Result<()> {
+    db.collection::<Category>("categories")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "key": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! { "key": { "$exists": true } })
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategoryRequest {
+    pub name: String,
+    pub category_type: String,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub parent_id: Option<String>,
+    pub order: Option<i32>,
+}
+
+/// 只更新请求中出现的字段；parent_id 为空字符串表示移到顶层
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategoryRequest {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub parent_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CategoryQuery {
+    pub category_type: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ReorderRequest {
+    /// 同级分类按此顺序排列
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    pub target_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryNode {
+    #[serde(flatten)]
+    pub category: Category,
+    pub children: Vec<CategoryNode>,
+}
+
+#[derive(Serialize)]
+pub struct MergeResult {
+    pub source_id: String,
+    pub target_id: String,
+    pub transactions_updated: u64,
+    pub budgets_updated: u64,
+    pub subcategories_moved: u64,
+}
+
+fn validate_type(category_type: &str) -> Result<()> {
+    if category_type != CATEGORY_TYPE_EXPENSE && category_type != CATEGORY_TYPE_INCOME {
+        return Err(Error::Validation("category_type must be expense or income".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 30 {
+        return Err(Error::Validation("Category name must be 1-30 characters".to_string()));
+    }
+    Ok(name.to_string())
+}
+
+/// 按 order、名称排序后组装成树；父分类不在列表中 (如已归档被过滤) 的分类挂在顶层
+pub fn build_tree(mut categories: Vec<Category>) -> Vec<CategoryNode> {
+    categories.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+    let references: Vec<String> = categories.iter().map(|c| c.reference_id().to_string()).collect();
+
+    let mut children: HashMap<String, Vec<Category>> = HashMap::new();
+    let mut roots = Vec::new();
+    for category in categories {
+        match category.parent_id.clone() {
+            Some(parent) if references.contains(&parent) => children.entry(parent).or_default().push(category),
+            _ => roots.push(category),
+        }
+    }
+
+    fn attach(category: Category, children: &mut HashMap<String, Vec<Category>>) -> CategoryNode {
+        let kids = children.remove(category.reference_id()).unwrap_or_default();
+        CategoryNode {
+            children: kids.into_iter().map(|kid| attach(kid, children)).collect(),
+            category,
+        }
+    }
+    roots.into_iter().map(|root| attach(root, &mut children)).collect()
+}
+
+async fn load_categories(db: &Database, user_id: &str, query: &CategoryQuery) -> Result<Vec<Category>> {
+    seed_system_categories(db, user_id).await?;
+
+    let mut filter = doc! { "user_id": user_id };
+    if let Some(category_type) = &query.category_type {
+        filter.insert("category_type", category_type);
+    }
+    if !query.include_archived {
+        filter.insert("is_archived", false);
+    }
+
+    let options = FindOptions::builder().sort(doc! { "order": 1, "name": 1 }).build();
+    let mut cursor = db.collection::<Category>("categories").find(filter, options).await?;
+    let mut categories = Vec::new();
+    while cursor.advance().await? {
+        categories.push(cursor.deserialize_current()?);
+    }
+    Ok(categories)
+}
+
+async fn get_category(db: &Database, user_id: &str, reference: &str) -> Result<Category> {
+    find_category(db, user_id, reference)
+        .await?
+        .ok_or_else(|| Error::NotFound("Category not found".to_string()))
+}
+
+/// 校验父分类：同一用户、类型一致，且本身是顶层分类 (分类最多两级)
+async fn check_parent(db: &Database, user_id: &str, parent_id: &str, category_type: &str) -> Result<Category> {
+    let parent = get_category(db, user_id, parent_id).await?;
+    if parent.category_type != category_type {
+        return Err(Error::Validation("Parent category must have the same category_type".to_string()));
+    }
+    if parent.parent_id.is_some() {
+        return Err(Error::Validation("Categories can only be nested one level deep".to_string()));
+    }
+    Ok(parent)
+}
+
+async fn has_children(db: &Database, user_id: &str, reference: &str) -> Result<bool> {
+    Ok(db
+        .collection::<Category>("categories")
+        .count_documents(doc! { "user_id": user_id, "parent_id": reference }, None)
+        .await?
+        > 0)
+}
+
+async fn set_archived(db: &Database, user_id: &str, reference: &str, archived: bool) -> Result<Category> {
+    let category = get_category(db, user_id, reference).await?;
+    db.collection::<Category>("categories")
+        .update_one(
+            doc! { "_id": category.id.as_deref().unwrap_or_default() },
+            doc! { "$set": {
+                "is_archived": archived,
+                "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+    get_category(db, user_id, reference).await
+}
+
+pub async fn list_categories(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Query(query): Query<CategoryQuery>,
+) -> Result<Json<ApiResponse<Vec<Category>>>> {
+    let categories = load_categories(&state.db.mongo, &claims.user_id, &query).await?;
+    Ok(Json(ApiResponse::success(categories)))
+}
+
+pub async fn category_tree(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Query(query): Query<CategoryQuery>,
+) -> Result<Json<ApiResponse<Vec<CategoryNode>>>> {
+    let categories = load_categories(&state.db.mongo, &claims.user_id, &query).await?;
+    Ok(Json(ApiResponse::success(build_tree(categories))))
+}
+
+pub async fn create_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Json(req): Json<CreateCategoryRequest>,
+) -> Result<Json<ApiResponse<Category>>> {
+    let db = &state.db.mongo;
+    let name = validate_name(&req.name)?;
+    validate_type(&req.category_type)?;
+    seed_system_categories(db, &claims.user_id).await?;
+    if let Some(parent_id) = &req.parent_id {
+        check_parent(db, &claims.user_id, parent_id, &req.category_type).await?;
+    }
+
+    // 未指定顺序时排在同级最后
+    let order = match req.order {
+        Some(order) => order,
+        None => {
+            db.collection::<Category>("categories")
+                .count_documents(doc! { "user_id": &claims.user_id, "parent_id": &req.parent_id }, None)
+                .await? as i32
+        }
+    };
+
+    let now = chrono::Utc::now();
+    let category = Category {
+        id: Some(ObjectId::new().to_hex()),
+        user_id: Some(claims.user_id),
+        key: None,
+        name,
+        category_type: req.category_type,
+        icon: req.icon.unwrap_or_else(|| "tag".to_string()),
+        color: req.color.unwrap_or_else(|| "#1890ff".to_string()),
+        parent_id: req.parent_id,
+        order,
+        is_system: false,
+        is_archived: false,
+        created_at: now,
+        updated_at: now,
+    };
+    db.collection::<Category>("categories").insert_one(&category, None).await?;
+
+    Ok(Json(ApiResponse::success(category)))
+}
+
+/// 修改名称、图标、颜色或父分类；系统分类同样可以改名，类型不可修改
+pub async fn update_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateCategoryRequest>,
+) -> Result<Json<ApiResponse<Category>>> {
+    let db = &state.db.mongo;
+    let category = get_category(db, &claims.user_id, &id).await?;
+
+    let mut set = doc! { "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap() };
+    if let Some(name) = &req.name {
+        set.insert("name", validate_name(name)?);
+    }
+    if let Some(icon) = &req.icon {
+        set.insert("icon", icon);
+    }
+    if let Some(color) = &req.color {
+        set.insert("color", color);
+    }
+    let mut unset = doc! {};
+    match req.parent_id.as_deref() {
+        Some("") => {
+            unset.insert("parent_id", "");
+        }
+        Some(parent_id) => {
+            let parent = check_parent(db, &claims.user_id, parent_id, &category.category_type).await?;
+            if parent.reference_id() == category.reference_id() {
+                return Err(Error::Validation("A category cannot be its own parent".to_string()));
+            }
+            if has_children(db, &claims.user_id, category.reference_id()).await? {
+                return Err(Error::Validation("Categories can only be nested one level deep".to_string()));
+            }
+            set.insert("parent_id", parent.reference_id());
+        }
+        None => {}
+    }
+
+    let mut update = doc! { "$set": set };
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    db.collection::<Category>("categories")
+        .update_one(doc! { "_id": category.id.as_deref().unwrap_or_default() }, update, None)
+        .await?;
+
+    Ok(Json(ApiResponse::success(get_category(db, &claims.user_id, &id).await?)))
+}
+
+/// 归档后不能再用于新交易，历史交易保持不变
+pub async fn archive_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Category>>> {
+    let category = set_archived(&state.db.mongo, &claims.user_id, &id, true).await?;
+    Ok(Json(ApiResponse::success(category)))
+}
+
+pub async fn unarchive_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Category>>> {
+    let category = set_archived(&state.db.mongo, &claims.user_id, &id, false).await?;
+    Ok(Json(ApiResponse::success(category)))
+}
+
+/// 删除未被使用的自定义分类；仍被交易、预算或子分类引用时返回 409，建议合并或归档
+pub async fn delete_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>> {
+    let db = &state.db.mongo;
+    let category = get_category(db, &claims.user_id, &id).await?;
+    if category.is_system {
+        return Err(Error::BadRequest("System categories cannot be deleted; archive them instead".to_string()));
+    }
+
+    let reference = category.reference_id();
+    if has_children(db, &claims.user_id, reference).await? {
+        return Err(Error::Conflict("Category has subcategories".to_string()));
+    }
+    let transactions = db
+        .collection::<Transaction>("transactions")
+        .count_documents(
+            doc! {
+                "user_id": &claims.user_id,
+                "$or": [
+                    { "category_id": reference },
+                    { "subcategory_id": reference },
+                    { "splits.category_id": reference },
+                ],
+            },
+            None,
+        )
+        .await?;
+    let budgets = db
+        .collection::<Budget>("budgets")
+        .count_documents(doc! { "user_id": &claims.user_id, "category_ids": reference }, None)
+        .await?;
+    let rules = db
+        .collection::<CategoryRule>("category_rules")
+        .count_documents(
+            doc! {
+                "user_id": &claims.user_id,
+                "$or": [{ "actions.category_id": reference }, { "actions.subcategory_id": reference }],
+            },
+            None,
+        )
+        .await?;
+    let recurring = db
+        .collection::<RecurringTransaction>("recurring_transactions")
+        .count_documents(doc! { "user_id": &claims.user_id, "template.category_id": reference }, None)
+        .await?;
+    if transactions > 0 || budgets > 0 || rules > 0 || recurring > 0 {
+        return Err(Error::Conflict(format!(
+            "Category is used by {} transactions, {} budgets, {} rules and {} recurring transactions; merge it into another category or archive it",
+            transactions, budgets, rules, recurring
+        )));
+    }
+
+    db.collection::<Category>("categories")
+        .delete_one(doc! { "_id": category.id.as_deref().unwrap_or_default() }, None)
+        .await?;
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// 按给定顺序重排分类的 order
+pub async fn reorder_categories(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Json(req): Json<ReorderRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    let collection = state.db.mongo.collection::<Category>("categories");
+    let now = bson::to_bson(&chrono::Utc::now()).unwrap();
+    for (order, id) in req.ids.iter().enumerate() {
+        let result = collection
+            .update_one(
+                reference_filter(&claims.user_id, id),
+                doc! { "$set": { "order": order as i32, "updated_at": now.clone() } },
+                None,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            return Err(Error::NotFound(format!("Category {} not found", id)));
+        }
+    }
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+async fn execute_merge(
+    db: &Database,
+    session: &mut ClientSession,
+    user_id: &str,
+    source: &Category,
+    target: &Category,
+) -> Result<MergeResult> {
+    let (from, to) = (source.reference_id(), target.reference_id());
+    let transactions = db.collection::<Transaction>("transactions");
+    let budgets = db.collection::<Budget>("budgets");
+    let categories = db.collection::<Category>("categories");
+    let now = bson::to_bson(&chrono::Utc::now()).unwrap();
+
+    let mut transactions_updated = transactions
+        .update_many_with_session(
+            doc! { "user_id": user_id, "category_id": from },
+            doc! { "$set": { "category_id": to, "updated_at": now.clone() } },
+            None,
+            session,
+        )
+        .await?
+        .modified_count;
+    transactions_updated += transactions
+        .update_many_with_session(
+            doc! { "user_id": user_id, "subcategory_id": from },
+            doc! { "$set": { "subcategory_id": to, "updated_at": now.clone() } },
+            None,
+            session,
+        )
+        .await?
+        .modified_count;
+    transactions_updated += transactions
+        .update_many_with_session(
+            doc! { "user_id": user_id, "splits.category_id": from },
+            doc! { "$set": { "splits.$[line].category_id": to, "updated_at": now.clone() } },
+            UpdateOptions::builder()
+                .array_filters(vec![doc! { "line.category_id": from }])
+                .build(),
+            session,
+        )
+        .await?
+        .modified_count;
+
+    budgets
+        .update_many_with_session(
+            doc! { "user_id": user_id, "category_ids": from },
+            doc! { "$addToSet": { "category_ids": to } },
+            None,
+            session,
+        )
+        .await?;
+    let budgets_updated = budgets
+        .update_many_with_session(
+            doc! { "user_id": user_id, "category_ids": from },
+            doc! { "$pull": { "category_ids": from } },
+            None,
+            session,
+        )
+        .await?
+        .modified_count;
+    recompute_budgets(db, session, user_id, to).await?;
+
+    let rules = db.collection::<CategoryRule>("category_rules");
+    for field in ["actions.category_id", "actions.subcategory_id"] {
+        rules
+            .update_many_with_session(
+                doc! { "user_id": user_id, field: from },
+                doc! { "$set": { field: to, "updated_at": now.clone() } },
+                None,
+                session,
+            )
+            .await?;
+    }
+
+    // 周期性交易的模板和单次修改都指向新分类
+    let recurring = db.collection::<RecurringTransaction>("recurring_transactions");
+    recurring
+        .update_many_with_session(
+            doc! { "user_id": user_id, "template.category_id": from },
+            doc! { "$set": { "template.category_id": to, "updated_at": now.clone() } },
+            None,
+            session,
+        )
+        .await?;
+    recurring
+        .update_many_with_session(
+            doc! { "user_id": user_id, "exceptions.category_id": from },
+            doc! { "$set": { "exceptions.$[e].category_id": to, "updated_at": now.clone() } },
+            UpdateOptions::builder()
+                .array_filters(vec![doc! { "e.category_id": from }])
+                .build(),
+            session,
+        )
+        .await?;
+
+    let subcategories_moved = categories
+        .update_many_with_session(
+            doc! { "user_id": user_id, "parent_id": from },
+            doc! { "$set": { "parent_id": to, "updated_at": now.clone() } },
+            None,
+            session,
+        )
+        .await?
+        .modified_count;
+
+    // 系统分类会被重新预置，只归档不删除
+    let source_filter = doc! { "_id": source.id.as_deref().unwrap_or_default() };
+    if source.is_system {
+        categories
+            .update_one_with_session(
+                source_filter,
+                doc! { "$set": { "is_archived": true, "updated_at": now } },
+                None,
+                session,
+            )
+            .await?;
+    } else {
+        categories.delete_one_with_session(source_filter, None, session).await?;
+    }
+
+    Ok(MergeResult {
+        source_id: from.to_string(),
+        target_id: to.to_string(),
+        transactions_updated,
+        budgets_updated,
+        subcategories_moved,
+    })
+}
+
+/// 按交易重新计算包含该分类的生效预算的占用：合并后源分类的支出归入目标分类，
+/// 原先只含源分类或只含目标分类的预算占用都会变化，增量调整无法覆盖，因此从交易重算
+async fn recompute_budgets(
+    db: &Database,
+    session: &mut ClientSession,
+    user_id: &str,
+    category_id: &str,
+) -> Result<()> {
+    let budgets = db.collection::<Budget>("budgets");
+    let transactions = db.collection::<Transaction>("transactions");
+
+    let mut cursor = budgets
+        .find_with_session(
+            doc! { "user_id": user_id, "category_ids": category_id, "status": "active" },
+            None,
+            session,
+        )
+        .await?;
+    let mut affected = Vec::new();
+    while cursor.advance(session).await? {
+        affected.push(cursor.deserialize_current()?);
+    }
+
+    for mut budget in affected {
+        let filter = doc! {
+            "user_id": user_id,
+            "transaction_type": "expense",
+            "transaction_date": {
+                "$gte": bson::to_bson(&budget.start_date).unwrap(),
+                "$lte": bson::to_bson(&budget.end_date).unwrap(),
+            },
+            "$or": [
+                { "category_id": { "$in": &budget.category_ids } },
+                { "splits.category_id": { "$in": &budget.category_ids } },
+            ],
+        };
+        let mut cursor = transactions.find_with_session(filter, None, session).await?;
+        let mut spent = 0.0;
+        while cursor.advance(session).await? {
+            let tx: Transaction = cursor.deserialize_current()?;
+            spent += tx.amount_in_categories(&budget.category_ids);
+        }
+
+        budget.spent = spent;
+        budget.remaining = budget.amount - budget.spent;
+        budget.progress = (budget.spent / budget.amount) * 100.0;
+        budget.updated_at = chrono::Utc::now();
+        budgets
+            .replace_one_with_session(doc! { "_id": budget.id.as_ref().unwrap() }, &budget, None, session)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 把分类合并到另一个同类型分类：交易、预算、规则和子分类改为引用目标分类，然后删除 (系统分类为归档) 源分类
+pub async fn merge_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<MergeRequest>,
+) -> Result<Json<ApiResponse<MergeResult>>> {
+    let db = &state.db.mongo;
+    let source = get_category(db, &claims.user_id, &id).await?;
+    let target = get_category(db, &claims.user_id, &req.target_id).await?;
+
+    if source.reference_id() == target.reference_id() {
+        return Err(Error::Validation("Cannot merge a category into itself".to_string()));
+    }
+    if source.category_type != target.category_type {
+        return Err(Error::Validation("Categories must have the same category_type".to_string()));
+    }
+    if target.is_archived {
+        return Err(Error::Validation("Cannot merge into an archived category".to_string()));
+    }
+    if target.parent_id.as_deref() == Some(source.reference_id()) {
+        return Err(Error::Validation("Cannot merge a category into its own subcategory".to_string()));
+    }
+    if target.parent_id.is_some() && has_children(db, &claims.user_id, source.reference_id()).await? {
+        return Err(Error::Validation("Categories can only be nested one level deep".to_string()));
+    }
+
+    let mut session = state.db.start_transaction().await?;
+    let result = execute_merge(db, &mut session, &claims.user_id, &source, &target).await;
+    let merged = commit_or_abort(&mut session, result).await?;
+    suggest::invalidate(db, &claims.user_id).await;
+
+    Ok(Json(ApiResponse::success(merged)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: &str, key: Option<&str>, parent_id: Option<&str>, order: i32) -> Category {
+        let now = chrono::Utc::now();
+        Category {
+            id: Some(id.to_string()),
+            user_id: Some("user".to_string()),
+            key: key.map(str::to_string),
+            name: id.to_string(),
+            category_type: "expense".to_string(),
+            icon: "tag".to_string(),
+            color: "#1890ff".to_string(),
+            parent_id: parent_id.map(str::to_string),
+            order,
+            is_system: key.is_some(),
+            is_archived: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_build_tree_nests_by_reference_and_sorts() {
+        let tree = build_tree(vec![
+            category("c", None, Some("dining"), 1),
+            category("a", Some("dining"), None, 1),
+            category("b", None, Some("dining"), 0),
+            category("d", Some("transport"), None, 0),
+            category("orphan", None, Some("missing"), 2),
+        ]);
+
+        let roots: Vec<&str> = tree.iter().map(|n| n.category.name.as_str()).collect();
+        assert_eq!(roots, vec!["d", "a", "orphan"]);
+        let children: Vec<&str> = tree[1].children.iter().map(|n| n.category.name.as_str()).collect();
+        assert_eq!(children, vec!["b", "c"]);
+        assert!(tree[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_reference_id_prefers_system_key() {
+        assert_eq!(category("64f0", Some("dining"), None, 0).reference_id(), "dining");
+        assert_eq!(category("64f0", None, None, 0).reference_id(), "64f0");
+        assert!(validate_type("transfer").is_err());
+        assert!(validate_name("  ").is_err());
+    }
+}
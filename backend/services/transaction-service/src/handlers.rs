@@ -1,25 +1,15 @@
 use axum::{
-    extract::{Path, Query, State, Extension},
+    extract::{Path, Query, State, Multipart},
     Json,
 };
-use common::{Transaction, Category, Budget, Account, ApiResponse, PaginationResponse, PaginationMeta, Claims, Error, Result};
+use common::{ensure_transaction_category, ensure_transaction_splits, get_category_name, Category, Transaction, TransactionSplit, Account, ImportJob, DuplicateDetector, ApiResponse, PaginationResponse, PaginationMeta, Error, Result, ACCOUNT_STATUS_ARCHIVED, is_editable_transaction_status};
+use common::middleware::{scope, Authorized};
 use mongodb::{bson::{self, doc, oid::ObjectId}, options::FindOptions};
+use std::collections::HashMap;
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
-
-#[derive(Debug, Deserialize, Serialize)]
-pub struct CreateCategoryRequest {
-    pub name: String,
-    pub category_type: String,
-    pub icon: String,
-    pub color: String,
-    pub parent_id: Option<String>,
-    pub order: i32,
-    pub is_system: bool,
-    pub is_archived: bool,
-}
+use crate::{import::{self, CsvMapping, ImportFormat}, rules, service::{self, DuplicateCheck}, suggest, AppState};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateTransactionRequest {
@@ -27,10 +17,20 @@ pub struct CreateTransactionRequest {
     pub amount: f64,
     pub currency: String,
     pub account_id: String,
-    pub category_id: String,
+    pub to_account_id: Option<String>,
+    pub exchange_rate: Option<f64>,
+    /// 未指定时由自动分类规则决定；转账可不指定分类
+    pub category_id: Option<String>,
     pub description: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// 按分类拆分的明细行，金额之和须等于 amount
+    pub splits: Option<Vec<TransactionSplit>>,
     pub transaction_date: String,
     pub status: String,
+    /// 用户确认不是重复交易时跳过去重检查
+    #[serde(default)]
+    pub allow_duplicate: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -39,12 +39,24 @@ pub struct UpdateTransactionRequest {
     pub amount: f64,
     pub currency: Option<String>,
     pub account_id: Option<String>,
+    pub to_account_id: Option<String>,
+    pub exchange_rate: Option<f64>,
+    /// 转账可省略
+    #[serde(default)]
     pub category_id: String,
+    /// 替换原有的拆分行，不传则取消拆分
+    pub splits: Option<Vec<TransactionSplit>>,
     pub description: Option<String>,
     pub transaction_date: String,
     pub status: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReviewDuplicateRequest {
+    /// keep: 确认不是重复并保留；discard: 删除该交易并冲销其影响
+    pub action: String,
+}
+
 #[derive(Deserialize)]
 pub struct ListQuery {
     #[serde(default = "default_page")]
@@ -53,6 +65,7 @@ pub struct ListQuery {
     page_size: u64,
     category_id: Option<String>,
     transaction_type: Option<String>,
+    status: Option<String>,
     start_date: Option<String>,
     end_date: Option<String>,
 }
@@ -78,19 +91,40 @@ pub struct CategoryStat {
 
 pub async fn list_transactions(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<ApiResponse<PaginationResponse<Transaction>>>> {
     let collection = state.db.mongo.collection::<Transaction>("transactions");
     
     let mut filter = doc! { "user_id": &claims.user_id };
     
+    // 拆分交易的任一拆分行属于该分类即可
     if let Some(category_id) = query.category_id {
-        filter.insert("category_id", category_id);
+        filter.insert("$or", vec![doc! { "category_id": &category_id }, doc! { "splits.category_id": &category_id }]);
     }
     if let Some(tx_type) = query.transaction_type {
         filter.insert("transaction_type", tx_type);
     }
+    if let Some(status) = query.status {
+        filter.insert("status", status);
+    }
+    
+    let mut date_filter = doc! {};
+    if let Some(start_date) = &query.start_date {
+        let start = chrono::DateTime::parse_from_rfc3339(start_date)
+            .map_err(|_| Error::InvalidInput("Invalid start date format".to_string()))?
+            .with_timezone(&chrono::Utc);
+        date_filter.insert("$gte", bson::to_bson(&start).unwrap());
+    }
+    if let Some(end_date) = &query.end_date {
+        let end = chrono::DateTime::parse_from_rfc3339(end_date)
+            .map_err(|_| Error::InvalidInput("Invalid end date format".to_string()))?
+            .with_timezone(&chrono::Utc);
+        date_filter.insert("$lte", bson::to_bson(&end).unwrap());
+    }
+    if !date_filter.is_empty() {
+        filter.insert("transaction_date", date_filter);
+    }
     
     let total = collection.count_documents(filter.clone(), None).await?;
     
@@ -116,7 +150,7 @@ pub async fn list_transactions(
 
 pub async fn create_transaction(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
     Json(req): Json<CreateTransactionRequest>,
 ) -> Result<Json<ApiResponse<Transaction>>> {
     use chrono::DateTime;
@@ -125,31 +159,53 @@ pub async fn create_transaction(
         .map_err(|_| Error::InvalidInput("Invalid date format".to_string()))?
         .with_timezone(&chrono::Utc);
     
-    // Capture values needed for budget update
-    let transaction_type_for_update = req.transaction_type.clone();
-    let category_id_for_update = req.category_id.clone();
-    let amount_for_update = req.amount;
-    let user_id_for_update = claims.user_id.clone();
-    let transaction_date_for_update = transaction_date;
-    let account_id_for_update = req.account_id.clone();
+    if req.amount <= 0.0 {
+        return Err(Error::InvalidInput("Amount must be positive".to_string()));
+    }
+    check_status(&req.status)?;
+    
+    // 转账需要目标账户，跨币种时按汇率换算入账金额
+    let (to_account_id, to_amount, exchange_rate) = if req.transaction_type == "transfer" {
+        let to_account_id = req
+            .to_account_id
+            .ok_or_else(|| Error::InvalidInput("to_account_id is required for transfers".to_string()))?;
+        let rate = service::resolve_transfer_rate(
+            &state.db,
+            &claims.user_id,
+            &req.account_id,
+            &to_account_id,
+            &req.currency,
+            req.exchange_rate,
+            transaction_date,
+        )
+        .await?;
+        let to_amount = service::converted_amount(req.amount, rate);
+        (Some(to_account_id), Some(to_amount), Some(rate))
+    } else {
+        (None, None, None)
+    };
 
-    let transaction = Transaction {
+    let mut transaction = Transaction {
         id: Some(ObjectId::new().to_hex()),
         user_id: claims.user_id.clone(),
         transaction_type: req.transaction_type,
         amount: req.amount,
         currency: req.currency,
         account_id: req.account_id,
-        to_account_id: None,
-        category_id: req.category_id,
+        to_account_id,
+        to_amount,
+        exchange_rate,
+        category_id: req.category_id.unwrap_or_default(),
         subcategory_id: None,
-        tags: None,
+        splits: req.splits,
+        tags: req.tags,
         description: req.description.unwrap_or_default(),
-        payee: None,
+        payee: req.payee,
         transaction_date,
         location: None,
         attachments: None,
         dedup_hash: None,
+        duplicate_of: None,
         external_id: None,
         status: req.status,
         notes: None,
@@ -157,62 +213,56 @@ pub async fn create_transaction(
         updated_at: chrono::Utc::now(),
         created_by: claims.user_id,
     };
-    
-    let collection = state.db.mongo.collection::<Transaction>("transactions");
-    collection.insert_one(&transaction, None).await?;
-    
-    // Update budgets if it's an expense
-    if transaction_type_for_update == "expense" {
-        let budgets_collection = state.db.mongo.collection::<Budget>("budgets");
-        let filter = doc! {
-            "user_id": &user_id_for_update,
-            "category_ids": &category_id_for_update,
-            "start_date": { "$lte": bson::to_bson(&transaction_date_for_update).unwrap() },
-            "end_date": { "$gte": bson::to_bson(&transaction_date_for_update).unwrap() },
-            "status": "active"
-        };
-        
-        let mut cursor = budgets_collection.find(filter, None).await?;
-        while cursor.advance().await? {
-            let mut budget = cursor.deserialize_current()?;
-            budget.spent += amount_for_update;
-            budget.remaining = budget.amount - budget.spent;
-            budget.progress = (budget.spent / budget.amount) * 100.0;
-            budget.updated_at = chrono::Utc::now();
-            
-            budgets_collection.replace_one(
-                doc! { "_id": budget.id.as_ref().unwrap() },
-                budget,
-                None
-            ).await?;
+    // 规则只补全请求中没有指定的分类和收款方
+    let engine = rules::load_engine(&state.db.mongo, &transaction.user_id).await?;
+    let mut matches = HashMap::new();
+    rules::count_matches(&mut matches, engine.apply(&mut transaction, false));
+    // 拆分交易的分类取金额最大的拆分行
+    ensure_transaction_splits(&state.db.mongo, &mut transaction).await?;
+    // 转账可以不指定分类
+    if transaction.category_id.is_empty() {
+        if transaction.transaction_type != "transfer" {
+            return Err(Error::Validation("category_id is required when no rule matches".to_string()));
         }
+    } else {
+        ensure_transaction_category(&state.db.mongo, &transaction.user_id, &transaction.category_id, &transaction.transaction_type).await?;
     }
-
-    // Update account balance
-    let accounts_collection = state.db.mongo.collection::<Account>("accounts");
-    if let Some(mut account) = accounts_collection.find_one(doc! { "_id": &account_id_for_update, "user_id": &user_id_for_update }, None).await? {
-        match transaction_type_for_update.as_str() {
-            "expense" => {
-                account.current_balance -= amount_for_update;
-            },
-            "income" => {
-                account.current_balance += amount_for_update;
-            },
-            _ => {}
+    transaction.dedup_hash = Some(DuplicateDetector::canonical_hash(&transaction));
+    
+    // 完全重复或疑似重复都标记后入库，等待复核 (同日同额的真实交易也会命中哈希)
+    if !req.allow_duplicate {
+        match service::check_duplicates(&state.db, &transaction).await? {
+            DuplicateCheck::Exact(existing_id) => {
+                transaction.status = "possible_duplicate".to_string();
+                transaction.duplicate_of = Some(existing_id);
+            }
+            DuplicateCheck::Possible(found) => {
+                transaction.status = "possible_duplicate".to_string();
+                transaction.duplicate_of = Some(found.transaction_id);
+            }
+            DuplicateCheck::Unique => {}
         }
-        account.updated_at = chrono::Utc::now();
-        
-        accounts_collection.replace_one(
-            doc! { "_id": &account_id_for_update },
-            account,
-            None
-        ).await?;
     }
     
+    service::insert_transaction(&state.db, &transaction).await?;
+    rules::record_matches(&state.db.mongo, &matches).await;
+    suggest::observe(&state.db.mongo, &transaction.user_id, None, Some(&transaction)).await;
+    
     Ok(Json(ApiResponse::success(transaction)))
-}pub async fn get_transaction(
+}
+
+/// 交易状态只能设为用户可编辑的值，待复核状态由去重检查设置
+fn check_status(status: &str) -> Result<()> {
+    if is_editable_transaction_status(status) {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!("Invalid transaction status: {}", status)))
+    }
+}
+
+pub async fn get_transaction(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<Transaction>>> {
     let collection = state.db.mongo.collection::<Transaction>("transactions");
@@ -226,7 +276,7 @@ pub async fn create_transaction(
 
 pub async fn update_transaction(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
     Path(id): Path<String>,
     Json(req): Json<UpdateTransactionRequest>,
 ) -> Result<Json<ApiResponse<Transaction>>> {
@@ -236,63 +286,258 @@ pub async fn update_transaction(
         .map_err(|_| Error::InvalidInput("Invalid date format".to_string()))?
         .with_timezone(&chrono::Utc);
     
+    if req.amount <= 0.0 {
+        return Err(Error::InvalidInput("Amount must be positive".to_string()));
+    }
+    
     let collection = state.db.mongo.collection::<Transaction>("transactions");
+    let previous = collection
+        .find_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Transaction not found".to_string()))?;
     
-    let mut update_fields = doc! {
-        "amount": req.amount,
-        "category_id": &req.category_id,
-        "description": req.description.unwrap_or_default(),
-        "transaction_date": bson::to_bson(&transaction_date).unwrap(),
-        "updated_at": mongodb::bson::to_bson(&chrono::Utc::now()).unwrap(),
-    };
+    let mut updated = previous.clone();
+    updated.amount = req.amount;
+    updated.category_id = req.category_id;
+    updated.splits = req.splits;
+    updated.description = req.description.unwrap_or_default();
+    updated.transaction_date = transaction_date;
+    updated.updated_at = chrono::Utc::now();
     
-    if let Some(transaction_type) = &req.transaction_type {
-        update_fields.insert("transaction_type", transaction_type);
+    if let Some(transaction_type) = req.transaction_type {
+        updated.transaction_type = transaction_type;
     }
-    if let Some(currency) = &req.currency {
-        update_fields.insert("currency", currency);
+    if let Some(currency) = req.currency {
+        updated.currency = currency;
     }
-    if let Some(account_id) = &req.account_id {
-        update_fields.insert("account_id", account_id);
+    if let Some(account_id) = req.account_id {
+        updated.account_id = account_id;
     }
-    if let Some(status) = &req.status {
-        update_fields.insert("status", status);
+    if let Some(status) = req.status {
+        check_status(&status)?;
+        updated.status = status;
+    }
+    ensure_transaction_splits(&state.db.mongo, &mut updated).await?;
+    if updated.category_id.is_empty() {
+        if updated.transaction_type != "transfer" {
+            return Err(Error::Validation("category_id is required".to_string()));
+        }
+    } else {
+        ensure_transaction_category(&state.db.mongo, &claims.user_id, &updated.category_id, &updated.transaction_type).await?;
     }
     
-    let update_doc = doc! { "$set": update_fields };
-    
-    collection
-        .update_one(doc! { "_id": &id, "user_id": &claims.user_id }, update_doc, None)
+    if updated.transaction_type == "transfer" {
+        let to_account_id = req
+            .to_account_id
+            .or_else(|| previous.to_account_id.clone())
+            .ok_or_else(|| Error::InvalidInput("to_account_id is required for transfers".to_string()))?;
+        
+        // 账户未变且未指定新汇率时沿用原汇率，避免仅修改备注也导致入账金额变化
+        let same_accounts = previous.transaction_type == "transfer"
+            && previous.account_id == updated.account_id
+            && previous.to_account_id.as_deref() == Some(to_account_id.as_str());
+        let manual_rate = req
+            .exchange_rate
+            .or(if same_accounts { previous.exchange_rate } else { None });
+        let rate = service::resolve_transfer_rate(
+            &state.db,
+            &claims.user_id,
+            &updated.account_id,
+            &to_account_id,
+            &updated.currency,
+            manual_rate,
+            updated.transaction_date,
+        )
         .await?;
+        
+        updated.to_account_id = Some(to_account_id);
+        updated.to_amount = Some(service::converted_amount(updated.amount, rate));
+        updated.exchange_rate = Some(rate);
+    } else {
+        updated.to_account_id = None;
+        updated.to_amount = None;
+        updated.exchange_rate = None;
+    }
+    updated.dedup_hash = Some(DuplicateDetector::canonical_hash(&updated));
     
-    let updated = collection
-        .find_one(doc! { "_id": &id }, None)
-        .await?
-        .ok_or_else(|| Error::NotFound("Transaction not found".to_string()))?;
+    service::replace_transaction(&state.db, &previous, &updated).await?;
+    suggest::observe(&state.db.mongo, &claims.user_id, Some(&previous), Some(&updated)).await;
     
     Ok(Json(ApiResponse::success(updated)))
 }
 
 pub async fn delete_transaction(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
     Path(id): Path<String>,
 ) -> Result<Json<ApiResponse<()>>> {
+    let removed = service::remove_transaction(&state.db, &claims.user_id, &id).await?;
+    suggest::observe(&state.db.mongo, &claims.user_id, Some(&removed), None).await;
+    
+    Ok(Json(ApiResponse::success(())))
+}
+
+pub async fn review_duplicate(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<ReviewDuplicateRequest>,
+) -> Result<Json<ApiResponse<Option<Transaction>>>> {
     let collection = state.db.mongo.collection::<Transaction>("transactions");
-    let result = collection
-        .delete_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
-        .await?;
+    let filter = doc! { "_id": &id, "user_id": &claims.user_id, "status": "possible_duplicate" };
     
-    if result.deleted_count == 0 {
-        return Err(Error::NotFound("Transaction not found".to_string()));
+    if collection.find_one(filter.clone(), None).await?.is_none() {
+        return Err(Error::NotFound("No pending duplicate review for this transaction".to_string()));
     }
     
-    Ok(Json(ApiResponse::success(())))
+    match req.action.as_str() {
+        "keep" => {
+            collection
+                .update_one(
+                    filter,
+                    doc! {
+                        "$set": {
+                            "status": "confirmed",
+                            "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap(),
+                        },
+                        "$unset": { "duplicate_of": "" },
+                    },
+                    None,
+                )
+                .await?;
+            let kept = collection
+                .find_one(doc! { "_id": &id }, None)
+                .await?
+                .ok_or_else(|| Error::NotFound("Transaction not found".to_string()))?;
+            suggest::observe(&state.db.mongo, &claims.user_id, None, Some(&kept)).await;
+            Ok(Json(ApiResponse::success(Some(kept))))
+        }
+        "discard" => {
+            service::remove_transaction(&state.db, &claims.user_id, &id).await?;
+            Ok(Json(ApiResponse::success(None)))
+        }
+        _ => Err(Error::InvalidInput("action must be keep or discard".to_string())),
+    }
+}
+
+pub async fn import_transactions(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    mut multipart: Multipart,
+) -> Result<Json<ApiResponse<ImportJob>>> {
+    let mut file: Option<(String, Vec<u8>)> = None;
+    let mut account_id: Option<String> = None;
+    let mut format: Option<String> = None;
+    let mut mapping = CsvMapping::default();
+    let mut skip_duplicates = true;
+    
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| Error::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or("upload").to_string();
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| Error::BadRequest(format!("Failed to read file: {}", e)))?;
+            file = Some((file_name, data.to_vec()));
+            continue;
+        }
+        
+        let value = field
+            .text()
+            .await
+            .map_err(|e| Error::BadRequest(format!("Invalid field {}: {}", name, e)))?;
+        match name.as_str() {
+            "account_id" => account_id = Some(value),
+            "format" => format = Some(value),
+            "mapping" => {
+                mapping = serde_json::from_str(&value)
+                    .map_err(|e| Error::InvalidInput(format!("Invalid column mapping: {}", e)))?;
+            }
+            "skip_duplicates" => skip_duplicates = value.trim() != "false",
+            _ => {}
+        }
+    }
+    
+    let (file_name, data) = file.ok_or_else(|| Error::InvalidInput("file is required".to_string()))?;
+    let account_id = account_id.ok_or_else(|| Error::InvalidInput("account_id is required".to_string()))?;
+    let format = match format {
+        Some(format) => ImportFormat::parse(&format),
+        None => ImportFormat::from_file_name(&file_name),
+    }
+    .ok_or_else(|| Error::InvalidInput("Unsupported import format, expected csv, ofx or qif".to_string()))?;
+    let content = String::from_utf8(data)
+        .map_err(|_| Error::InvalidInput("File must be UTF-8 encoded".to_string()))?;
+    
+    let account = state
+        .db
+        .mongo
+        .collection::<Account>("accounts")
+        .find_one(doc! { "_id": &account_id, "user_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Account not found".to_string()))?;
+    if account.status == ACCOUNT_STATUS_ARCHIVED {
+        return Err(Error::BadRequest("Cannot import into an archived account".to_string()));
+    }
+    
+    let now = chrono::Utc::now();
+    let job = ImportJob {
+        id: Some(ObjectId::new().to_hex()),
+        user_id: claims.user_id,
+        account_id,
+        file_name,
+        format: format.as_str().to_string(),
+        status: "pending".to_string(),
+        total_rows: 0,
+        processed_rows: 0,
+        inserted: 0,
+        flagged: 0,
+        skipped: 0,
+        failed: 0,
+        errors: Vec::new(),
+        created_at: now,
+        updated_at: now,
+        finished_at: None,
+    };
+    
+    state.db.mongo.collection::<ImportJob>("import_jobs").insert_one(&job, None).await?;
+    
+    tokio::spawn(import::run_import_job(
+        state.db.clone(),
+        job.clone(),
+        account,
+        format,
+        content,
+        mapping,
+        skip_duplicates,
+    ));
+    
+    Ok(Json(ApiResponse::success(job)))
+}
+
+pub async fn get_import_status(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<ImportJob>>> {
+    let job = state
+        .db
+        .mongo
+        .collection::<ImportJob>("import_jobs")
+        .find_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Import job not found".to_string()))?;
+    
+    Ok(Json(ApiResponse::success(job)))
 }
 
 pub async fn get_statistics(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
 ) -> Result<Json<ApiResponse<Statistics>>> {
     let collection = state.db.mongo.collection::<Transaction>("transactions");
     
@@ -302,6 +547,7 @@ pub async fn get_statistics(
     let mut total_income = 0.0;
     let mut total_expense = 0.0;
     let mut transaction_count = 0;
+    let mut by_category: HashMap<String, (f64, u32)> = HashMap::new();
     
     while cursor.advance().await? {
         let tx: Transaction = cursor.deserialize_current()?;
@@ -310,57 +556,60 @@ pub async fn get_statistics(
         match tx.transaction_type.as_str() {
             "income" => total_income += tx.amount,
             "expense" => total_expense += tx.amount,
-            _ => {}
+            _ => continue,
+        }
+        // 拆分交易的各行计入各自的分类
+        for (category_id, amount) in tx.category_amounts() {
+            let stat = by_category.entry(category_id.to_string()).or_insert((0.0, 0));
+            stat.0 += amount;
+            stat.1 += 1;
         }
     }
     
+    let mut names = HashMap::new();
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<Category>("categories")
+        .find(doc! { "user_id": &claims.user_id }, None)
+        .await?;
+    while cursor.advance().await? {
+        let category: Category = cursor.deserialize_current()?;
+        names.insert(category.reference_id().to_string(), category.name);
+    }
+    
+    let mut by_category: Vec<CategoryStat> = by_category
+        .into_iter()
+        .map(|(category_id, (amount, count))| CategoryStat {
+            category_name: names
+                .remove(&category_id)
+                .unwrap_or_else(|| get_category_name(&category_id)),
+            category_id,
+            amount: (amount * 100.0).round() / 100.0,
+            count,
+        })
+        .collect();
+    by_category.sort_by(|a, b| b.amount.total_cmp(&a.amount).then_with(|| a.category_id.cmp(&b.category_id)));
+    
     Ok(Json(ApiResponse::success(Statistics {
         total_income,
         total_expense,
         transaction_count,
-        by_category: Vec::new(), // 简化实现
+        by_category,
     })))
 }
 
-pub async fn list_categories(
-    State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
-) -> Result<Json<ApiResponse<Vec<Category>>>> {
-    let collection = state.db.mongo.collection::<Category>("categories");
-    
-    let filter = doc! { "user_id": &claims.user_id };
-    let mut cursor = collection.find(filter, None).await?;
-    
-    let mut categories = Vec::new();
-    while cursor.advance().await? {
-        categories.push(cursor.deserialize_current()?);
-    }
-    
-    Ok(Json(ApiResponse::success(categories)))
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub async fn create_category(
-    State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
-    Json(req): Json<CreateCategoryRequest>,
-) -> Result<Json<ApiResponse<Category>>> {
-    let category = Category {
-        id: Some(ObjectId::new().to_hex()),
-        user_id: Some(claims.user_id),
-        name: req.name,
-        category_type: req.category_type,
-        icon: req.icon,
-        color: req.color,
-        parent_id: req.parent_id,
-        order: req.order,
-        is_system: req.is_system,
-        is_archived: req.is_archived,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-    };
-    
-    let collection = state.db.mongo.collection::<Category>("categories");
-    collection.insert_one(&category, None).await?;
-    
-    Ok(Json(ApiResponse::success(category)))
+    #[test]
+    fn test_check_status() {
+        for status in ["confirmed", "pending", "completed", "cancelled"] {
+            assert!(check_status(status).is_ok());
+        }
+        assert!(check_status("possible_duplicate").is_err());
+        assert!(check_status("").is_err());
+        assert!(check_status("deleted").is_err());
+    }
 }
@@ -0,0 +1,782 @@
+// 批量导入 - 解析CSV/OFX/QIF对账单，并在后台任务中逐行写入交易
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use common::{Account, Calendar, DatabaseConnection, DuplicateDetector, ImportJob, ImportRowError, RuleEngine, Transaction};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    Database,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::{rules, suggest};
+use crate::service::{self, DuplicateCheck};
+
+/// 每处理这么多行就持久化一次进度
+const PROGRESS_INTERVAL: u32 = 50;
+/// 任务文档中最多保留的行级错误数，避免文档无限增长
+const MAX_REPORTED_ERRORS: usize = 500;
+/// 超过这么久没有更新进度的未完成任务视为已中断
+const STALE_JOB_MINUTES: i64 = 15;
+/// 检查中断任务的间隔
+const STALE_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+    Ofx,
+    Qif,
+}
+
+impl ImportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "ofx" | "qfx" => Some(Self::Ofx),
+            "qif" => Some(Self::Qif),
+            _ => None,
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        file_name.rsplit_once('.').and_then(|(_, ext)| Self::parse(ext))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ofx => "ofx",
+            Self::Qif => "qif",
+        }
+    }
+}
+
+/// CSV列映射，列可以用表头名（不区分大小写）或从0开始的列序号指定
+#[derive(Debug, Clone, Deserialize)]
+pub struct CsvMapping {
+    #[serde(default = "default_date_column")]
+    pub date: String,
+    #[serde(default = "default_amount_column")]
+    pub amount: String,
+    #[serde(default = "default_description_column")]
+    pub description: String,
+    pub payee: Option<String>,
+    pub category: Option<String>,
+    pub external_id: Option<String>,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// 金额的小数点，"." 或 ","（如 1.234,56）；另一个符号视为千分位
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: char,
+    /// 对账单中支出为正数时（如信用卡账单）设为true
+    #[serde(default)]
+    pub invert_amount: bool,
+}
+
+fn default_date_column() -> String { "date".to_string() }
+fn default_amount_column() -> String { "amount".to_string() }
+fn default_description_column() -> String { "description".to_string() }
+fn default_date_format() -> String { "%Y-%m-%d".to_string() }
+fn default_delimiter() -> char { ',' }
+fn default_decimal_separator() -> char { '.' }
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self {
+            date: default_date_column(),
+            amount: default_amount_column(),
+            description: default_description_column(),
+            payee: None,
+            category: None,
+            external_id: None,
+            date_format: default_date_format(),
+            delimiter: default_delimiter(),
+            decimal_separator: default_decimal_separator(),
+            invert_amount: false,
+        }
+    }
+}
+
+/// 对账单中的一行交易，金额带符号：负数为支出，正数为收入
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedRow {
+    pub date: DateTime<Utc>,
+    pub amount: f64,
+    pub description: String,
+    pub payee: Option<String>,
+    pub category_id: Option<String>,
+    pub external_id: Option<String>,
+}
+
+/// 一行交易的导入结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowOutcome {
+    Inserted,
+    /// 已写入，但与已有交易重复，标记为 possible_duplicate 等待复核
+    Flagged,
+    /// external_id 与已导入的交易相同，跳过
+    Skipped,
+}
+
+/// 行号（从1开始）及该行的解析结果
+pub type RowResult = (u32, std::result::Result<ParsedRow, String>);
+
+/// 解析整个文件；文件级错误（如缺少必需列）返回Err，行级错误保留在各行结果中。
+/// 不带时区的日期和时间按 calendar 所在时区（用户设置的时区）解释
+pub fn parse_statement(
+    format: ImportFormat,
+    content: &str,
+    mapping: &CsvMapping,
+    calendar: &Calendar,
+) -> std::result::Result<Vec<RowResult>, String> {
+    if !matches!(mapping.decimal_separator, '.' | ',') {
+        return Err("Decimal separator must be '.' or ','".to_string());
+    }
+    let content = content.trim_start_matches('\u{feff}');
+    match format {
+        ImportFormat::Csv => parse_csv(content, mapping, calendar),
+        ImportFormat::Ofx => parse_ofx(content, calendar),
+        ImportFormat::Qif => parse_qif(content, mapping, calendar),
+    }
+}
+
+pub fn parse_csv(content: &str, mapping: &CsvMapping, calendar: &Calendar) -> std::result::Result<Vec<RowResult>, String> {
+    if !mapping.delimiter.is_ascii() {
+        return Err("CSV delimiter must be an ASCII character".to_string());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter as u8)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .clone();
+    let column = |name: &str| -> Option<usize> {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .or_else(|| name.parse().ok())
+    };
+    let required = |name: &str| column(name).ok_or_else(|| format!("Column '{}' not found", name));
+
+    let date_idx = required(&mapping.date)?;
+    let amount_idx = required(&mapping.amount)?;
+    let description_idx = column(&mapping.description);
+    let payee_idx = mapping.payee.as_deref().map(required).transpose()?;
+    let category_idx = mapping.category.as_deref().map(required).transpose()?;
+    let external_id_idx = mapping.external_id.as_deref().map(required).transpose()?;
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let row = i as u32 + 1;
+        let parsed = record.map_err(|e| e.to_string()).and_then(|record| {
+            let field = |idx: Option<usize>| {
+                idx.and_then(|i| record.get(i))
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_string)
+            };
+
+            let date = parse_date(record.get(date_idx).unwrap_or(""), &mapping.date_format, calendar)?;
+            let mut amount = parse_amount(record.get(amount_idx).unwrap_or(""), mapping.decimal_separator)?;
+            if mapping.invert_amount {
+                amount = -amount;
+            }
+
+            Ok(ParsedRow {
+                date,
+                amount,
+                description: field(description_idx).unwrap_or_default(),
+                payee: field(payee_idx),
+                category_id: field(category_idx),
+                external_id: field(external_id_idx),
+            })
+        });
+        rows.push((row, parsed));
+    }
+
+    Ok(rows)
+}
+
+/// 解析OFX（SGML 1.x 与 XML 2.x 均可），每个 <STMTTRN> 为一行
+pub fn parse_ofx(content: &str, calendar: &Calendar) -> std::result::Result<Vec<RowResult>, String> {
+    // 仅转换ASCII字符，字节偏移与原文一致
+    let upper = content.to_ascii_uppercase();
+    if !upper.contains("<OFX>") {
+        return Err("Not an OFX document".to_string());
+    }
+
+    let mut rows = Vec::new();
+    let mut cursor = 0;
+    while let Some(found) = upper[cursor..].find("<STMTTRN>") {
+        let start = cursor + found + "<STMTTRN>".len();
+        let end = ["</STMTTRN>", "<STMTTRN>", "</BANKTRANLIST>"]
+            .iter()
+            .filter_map(|tag| upper[start..].find(tag))
+            .min()
+            .map(|offset| start + offset)
+            .unwrap_or(content.len());
+
+        let row = rows.len() as u32 + 1;
+        rows.push((row, parse_ofx_transaction(&content[start..end], calendar)));
+        cursor = end;
+    }
+
+    Ok(rows)
+}
+
+fn parse_ofx_transaction(block: &str, calendar: &Calendar) -> std::result::Result<ParsedRow, String> {
+    let date = ofx_tag(block, "DTPOSTED").ok_or("Missing DTPOSTED")?;
+    let amount = ofx_tag(block, "TRNAMT").ok_or("Missing TRNAMT")?;
+    let name = ofx_tag(block, "NAME");
+    let memo = ofx_tag(block, "MEMO");
+
+    Ok(ParsedRow {
+        date: parse_ofx_date(&date, calendar)?,
+        amount: parse_amount(&amount, '.')?,
+        description: memo.or_else(|| name.clone()).unwrap_or_default(),
+        payee: name,
+        category_id: None,
+        external_id: ofx_tag(block, "FITID"),
+    })
+}
+
+fn ofx_tag(block: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = block.to_ascii_uppercase().find(&open)? + open.len();
+    let rest = &block[start..];
+    let value = rest[..rest.find('<').unwrap_or(rest.len())].trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// OFX日期格式：YYYYMMDD[HHMMSS[.XXX]][gmt_offset[:tz_name]]，例如 20240115120000.000[-5:EST]；
+/// 未带偏移时按用户时区解释（银行导出的通常是本地日期）
+fn parse_ofx_date(raw: &str, calendar: &Calendar) -> std::result::Result<DateTime<Utc>, String> {
+    let invalid = || format!("Invalid OFX date '{}'", raw);
+    let (stamp, zone) = match raw.split_once('[') {
+        Some((stamp, zone)) => (stamp, Some(zone.trim_end_matches(']'))),
+        None => (raw, None),
+    };
+
+    let digits: String = stamp.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let naive = match digits.len() {
+        8 => NaiveDate::parse_from_str(&digits, "%Y%m%d")
+            .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+            .map_err(|_| invalid())?,
+        n if n >= 14 => NaiveDateTime::parse_from_str(&digits[..14], "%Y%m%d%H%M%S").map_err(|_| invalid())?,
+        _ => return Err(invalid()),
+    };
+
+    let Some(zone) = zone else {
+        return Ok(calendar.from_local(naive));
+    };
+    let offset_hours = zone
+        .split(':')
+        .next()
+        .unwrap_or("0")
+        .parse::<f64>()
+        .map_err(|_| invalid())?;
+
+    Ok(naive.and_utc() - Duration::minutes((offset_hours * 60.0).round() as i64))
+}
+
+/// 解析QIF，记录以 ^ 结束；D=日期 T/U=金额 P=收款方 M=备注 N=编号
+pub fn parse_qif(content: &str, mapping: &CsvMapping, calendar: &Calendar) -> std::result::Result<Vec<RowResult>, String> {
+    let mut rows = Vec::new();
+    let mut fields: Vec<(char, String)> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        if line.starts_with('^') {
+            if !fields.is_empty() {
+                let row = rows.len() as u32 + 1;
+                rows.push((row, parse_qif_record(&fields, mapping, calendar)));
+                fields.clear();
+            }
+            continue;
+        }
+
+        let mut chars = line.chars();
+        if let Some(code) = chars.next() {
+            fields.push((code, chars.as_str().trim().to_string()));
+        }
+    }
+
+    // 兼容最后一条记录缺少结束符的文件
+    if !fields.is_empty() {
+        let row = rows.len() as u32 + 1;
+        rows.push((row, parse_qif_record(&fields, mapping, calendar)));
+    }
+
+    if rows.is_empty() {
+        return Err("No QIF records found".to_string());
+    }
+    Ok(rows)
+}
+
+fn parse_qif_record(
+    fields: &[(char, String)],
+    mapping: &CsvMapping,
+    calendar: &Calendar,
+) -> std::result::Result<ParsedRow, String> {
+    let get = |code: char| {
+        fields
+            .iter()
+            .find(|(c, value)| *c == code && !value.is_empty())
+            .map(|(_, value)| value.clone())
+    };
+
+    let date = get('D').ok_or("Missing date (D)")?;
+    let amount = get('T').or_else(|| get('U')).ok_or("Missing amount (T)")?;
+    let payee = get('P');
+    let memo = get('M');
+
+    Ok(ParsedRow {
+        date: parse_qif_date(&date, &mapping.date_format, calendar)?,
+        amount: parse_amount(&amount, mapping.decimal_separator)?,
+        description: memo.or_else(|| payee.clone()).unwrap_or_default(),
+        payee,
+        category_id: None,
+        external_id: get('N'),
+    })
+}
+
+/// QIF日期通常是美式 M/D/YYYY，也常见 M/D'YY 以及带空格补齐的 " 1/ 5/24"
+fn parse_qif_date(raw: &str, date_format: &str, calendar: &Calendar) -> std::result::Result<DateTime<Utc>, String> {
+    let normalized: String = raw
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| if c == '\'' { '/' } else { c })
+        .collect();
+
+    [date_format, "%m/%d/%y", "%m/%d/%Y", "%Y-%m-%d", "%d.%m.%Y"]
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(&normalized, fmt).ok())
+        .map(|date| calendar.start_of_day(date))
+        .ok_or_else(|| format!("Invalid date '{}'", raw))
+}
+
+/// RFC3339 时刻按其自带的偏移解析，其余按用户时区的本地时间或本地日期的零点
+fn parse_date(raw: &str, date_format: &str, calendar: &Calendar) -> std::result::Result<DateTime<Utc>, String> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(raw, date_format) {
+        return Ok(calendar.from_local(dt));
+    }
+    NaiveDate::parse_from_str(raw, date_format)
+        .map(|date| calendar.start_of_day(date))
+        .map_err(|_| format!("Invalid date '{}', expected format {}", raw, date_format))
+}
+
+/// 解析金额，容忍货币符号、千分位以及会计格式的括号负数；decimal_separator 为 ',' 时 '.' 视为千分位
+fn parse_amount(raw: &str, decimal_separator: char) -> std::result::Result<f64, String> {
+    let trimmed = raw.trim();
+    let (negative, body) = match trimmed.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => (true, inner),
+        None => (false, trimmed),
+    };
+
+    let cleaned: String = body
+        .chars()
+        .filter_map(|c| match c {
+            '0'..='9' | '-' | '+' => Some(c),
+            c if c == decimal_separator => Some('.'),
+            _ => None,
+        })
+        .collect();
+    let value: f64 = cleaned
+        .parse()
+        .map_err(|_| format!("Invalid amount '{}'", raw))?;
+
+    Ok(if negative { -value.abs() } else { value })
+}
+
+/// 后台执行导入任务：解析文件、逐行写入交易，并定期持久化任务进度
+pub async fn run_import_job(
+    db: DatabaseConnection,
+    mut job: ImportJob,
+    account: Account,
+    format: ImportFormat,
+    content: String,
+    mapping: CsvMapping,
+    skip_duplicates: bool,
+) {
+    job.status = "processing".to_string();
+    if !save_job(&db, &mut job).await {
+        return;
+    }
+
+    let calendar = match Calendar::for_user(&db.mongo, &job.user_id).await {
+        Ok(calendar) => calendar,
+        Err(e) => {
+            tracing::warn!("Failed to load timezone for import job {:?}: {}", job.id, e);
+            Calendar::utc()
+        }
+    };
+    let rows = match parse_statement(format, &content, &mapping, &calendar) {
+        Ok(rows) => rows,
+        Err(message) => {
+            job.status = "failed".to_string();
+            job.errors.push(ImportRowError { row: 0, message });
+            job.finished_at = Some(Utc::now());
+            save_job(&db, &mut job).await;
+            return;
+        }
+    };
+
+    job.total_rows = rows.len() as u32;
+    if !save_job(&db, &mut job).await {
+        return;
+    }
+
+    let engine = match rules::load_engine(&db.mongo, &job.user_id).await {
+        Ok(engine) => engine,
+        Err(e) => {
+            tracing::warn!("Failed to load rules for import job {:?}: {}", job.id, e);
+            RuleEngine::new(Vec::new(), &[])
+        }
+    };
+    let mut matches = HashMap::new();
+
+    for (row, parsed) in rows {
+        let outcome = match parsed {
+            Ok(parsed) => import_row(&db, &job, &account, parsed, skip_duplicates, &engine, &mut matches)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(message) => Err(message),
+        };
+
+        match outcome {
+            Ok(RowOutcome::Inserted) => job.inserted += 1,
+            Ok(RowOutcome::Flagged) => {
+                job.inserted += 1;
+                job.flagged += 1;
+            }
+            Ok(RowOutcome::Skipped) => job.skipped += 1,
+            Err(message) => {
+                job.failed += 1;
+                if job.errors.len() < MAX_REPORTED_ERRORS {
+                    job.errors.push(ImportRowError { row, message });
+                }
+            }
+        }
+
+        job.processed_rows += 1;
+        if job.processed_rows.is_multiple_of(PROGRESS_INTERVAL) && !save_job(&db, &mut job).await {
+            return;
+        }
+    }
+
+    rules::record_matches(&db.mongo, &matches).await;
+    if job.inserted > 0 {
+        suggest::invalidate(&db.mongo, &job.user_id).await;
+    }
+
+    job.status = "completed".to_string();
+    job.finished_at = Some(Utc::now());
+    save_job(&db, &mut job).await;
+}
+
+/// 写入一行交易：external_id 已导入过的行跳过，与已有交易重复的行照常写入并标记待复核
+/// (同日同额的真实交易也会命中哈希)；命中的自动分类规则累加到 matches
+async fn import_row(
+    db: &DatabaseConnection,
+    job: &ImportJob,
+    account: &Account,
+    row: ParsedRow,
+    skip_duplicates: bool,
+    engine: &RuleEngine,
+    matches: &mut HashMap<String, u64>,
+) -> common::Result<RowOutcome> {
+    if row.amount == 0.0 {
+        return Err(common::Error::InvalidInput("Amount must be non-zero".to_string()));
+    }
+
+    if skip_duplicates {
+        if let Some(external_id) = &row.external_id {
+            let existing = db
+                .mongo
+                .collection::<Transaction>("transactions")
+                .find_one(
+                    doc! { "user_id": &job.user_id, "account_id": &job.account_id, "external_id": external_id },
+                    None,
+                )
+                .await?;
+            if existing.is_some() {
+                return Ok(RowOutcome::Skipped);
+            }
+        }
+    }
+
+    let (transaction_type, default_category) = if row.amount < 0.0 {
+        ("expense", "other_expense")
+    } else {
+        ("income", "other_income")
+    };
+    // 文件中的分类不存在、已归档或与收支方向不符时忽略，交给规则或默认分类
+    let category_id = match row.category_id {
+        Some(category_id) => {
+            match common::ensure_transaction_category(&db.mongo, &job.user_id, &category_id, transaction_type).await {
+                Ok(category) => category.reference_id().to_string(),
+                Err(common::Error::Validation(_)) => String::new(),
+                Err(e) => return Err(e),
+            }
+        }
+        None => String::new(),
+    };
+
+    let now = Utc::now();
+    let mut transaction = Transaction {
+        id: Some(ObjectId::new().to_hex()),
+        user_id: job.user_id.clone(),
+        transaction_type: transaction_type.to_string(),
+        amount: row.amount.abs(),
+        currency: account.currency.clone(),
+        account_id: job.account_id.clone(),
+        to_account_id: None,
+        to_amount: None,
+        exchange_rate: None,
+        category_id,
+        subcategory_id: None,
+        splits: None,
+        tags: None,
+        description: row.description,
+        payee: row.payee,
+        transaction_date: row.date,
+        location: None,
+        attachments: None,
+        dedup_hash: None,
+        duplicate_of: None,
+        external_id: row.external_id,
+        status: "confirmed".to_string(),
+        notes: None,
+        created_at: now,
+        updated_at: now,
+        created_by: job.user_id.clone(),
+    };
+
+    let touched = engine.apply(&mut transaction, false);
+    if transaction.category_id.is_empty() {
+        transaction.category_id = default_category.to_string();
+    }
+    transaction.dedup_hash = Some(DuplicateDetector::canonical_hash(&transaction));
+
+    let mut outcome = RowOutcome::Inserted;
+    if skip_duplicates {
+        let duplicate_of = match service::check_duplicates(db, &transaction).await? {
+            DuplicateCheck::Exact(existing_id) => Some(existing_id),
+            DuplicateCheck::Possible(found) => Some(found.transaction_id),
+            DuplicateCheck::Unique => None,
+        };
+        if let Some(duplicate_of) = duplicate_of {
+            transaction.status = "possible_duplicate".to_string();
+            transaction.duplicate_of = Some(duplicate_of);
+            outcome = RowOutcome::Flagged;
+        }
+    }
+
+    service::insert_transaction(db, &transaction).await?;
+    rules::count_matches(matches, touched);
+    Ok(outcome)
+}
+
+/// 定期把长时间没有进度的未完成任务标记为失败，启动时立即执行一次：
+/// 执行它们的进程 (可能是其他副本) 已经退出，任务不会再继续
+pub fn spawn_stale_job_sweep(db: DatabaseConnection) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(STALE_SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match fail_stale_jobs(&db.mongo).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Marked {} interrupted import jobs as failed", count),
+                Err(e) => tracing::warn!("Failed to clean up interrupted import jobs: {}", e),
+            }
+        }
+    });
+}
+
+async fn fail_stale_jobs(db: &Database) -> common::Result<u64> {
+    let now = Utc::now();
+    let cutoff = now - Duration::minutes(STALE_JOB_MINUTES);
+    let result = db
+        .collection::<ImportJob>("import_jobs")
+        .update_many(
+            doc! {
+                "status": { "$in": ["pending", "processing"] },
+                "updated_at": { "$lt": bson::to_bson(&cutoff).unwrap() },
+            },
+            doc! {
+                "$set": {
+                    "status": "failed",
+                    "finished_at": bson::to_bson(&now).unwrap(),
+                    "updated_at": bson::to_bson(&now).unwrap(),
+                },
+                "$push": { "errors": { "row": 0, "message": "Import was interrupted and did not finish" } },
+            },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count)
+}
+
+/// 持久化任务进度；任务已被判定中断并标记失败时不再覆盖，返回 false 让执行方停止
+async fn save_job(db: &DatabaseConnection, job: &mut ImportJob) -> bool {
+    job.updated_at = Utc::now();
+    let result = db
+        .mongo
+        .collection::<ImportJob>("import_jobs")
+        .replace_one(
+            doc! { "_id": job.id.as_ref().unwrap(), "status": { "$in": ["pending", "processing"] } },
+            &*job,
+            None,
+        )
+        .await;
+
+    match result {
+        Ok(result) if result.matched_count == 0 => {
+            tracing::warn!("Import job {:?} was marked as interrupted, stopping", job.id);
+            false
+        }
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!("Failed to save import job {:?}: {}", job.id, e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_csv_with_mapping() {
+        let content = "Posted;Amount;Memo;Ref\n15/01/2024;\"-1,234.50\";Rent;A1\n16/01/2024;200;Refund;A2\nbad;1;x;A3\n";
+        let mapping = CsvMapping {
+            date: "posted".to_string(),
+            description: "memo".to_string(),
+            external_id: Some("Ref".to_string()),
+            date_format: "%d/%m/%Y".to_string(),
+            delimiter: ';',
+            ..CsvMapping::default()
+        };
+
+        let rows = parse_csv(content, &mapping, &Calendar::utc()).unwrap();
+        assert_eq!(rows.len(), 3);
+
+        let first = rows[0].1.as_ref().unwrap();
+        assert_eq!(first.amount, -1234.5);
+        assert_eq!(first.description, "Rent");
+        assert_eq!(first.external_id.as_deref(), Some("A1"));
+        assert_eq!(first.date, Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap());
+
+        assert_eq!(rows[2].0, 3);
+        assert!(rows[2].1.is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_missing_column() {
+        let result = parse_csv("when,value\n2024-01-01,1\n", &CsvMapping::default(), &Calendar::utc());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_ofx_sgml() {
+        let content = "OFXHEADER:100\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>\n\
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240115120000.000[-5:EST]<TRNAMT>-42.10<FITID>9001<NAME>COFFEE SHOP<MEMO>Latte\n\
+            <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240116<TRNAMT>1000.00<FITID>9002<NAME>PAYROLL\n\
+            </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+        let rows = parse_ofx(content, &Calendar::utc()).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let first = rows[0].1.as_ref().unwrap();
+        assert_eq!(first.amount, -42.1);
+        assert_eq!(first.payee.as_deref(), Some("COFFEE SHOP"));
+        assert_eq!(first.description, "Latte");
+        assert_eq!(first.external_id.as_deref(), Some("9001"));
+        assert_eq!(first.date, Utc.with_ymd_and_hms(2024, 1, 15, 17, 0, 0).unwrap());
+
+        let second = rows[1].1.as_ref().unwrap();
+        assert_eq!(second.description, "PAYROLL");
+        assert_eq!(second.amount, 1000.0);
+    }
+
+    #[test]
+    fn test_parse_qif() {
+        let content = "!Type:Bank\nD1/ 5'24\nT-25.00\nPGrocer\nMWeekly shop\n^\nD01/06/2024\nT1,500.00\nPEmployer\n^\nDnope\nT1\n^\n";
+
+        let rows = parse_qif(content, &CsvMapping::default(), &Calendar::utc()).unwrap();
+        assert_eq!(rows.len(), 3);
+
+        let first = rows[0].1.as_ref().unwrap();
+        assert_eq!(first.amount, -25.0);
+        assert_eq!(first.description, "Weekly shop");
+        assert_eq!(first.date, Utc.with_ymd_and_hms(2024, 1, 5, 0, 0, 0).unwrap());
+
+        assert_eq!(rows[1].1.as_ref().unwrap().amount, 1500.0);
+        assert!(rows[2].1.is_err());
+    }
+
+    #[test]
+    fn test_parse_amount_formats() {
+        assert_eq!(parse_amount("¥1,234.56", '.').unwrap(), 1234.56);
+        assert_eq!(parse_amount("(12.00)", '.').unwrap(), -12.0);
+        assert!(parse_amount("abc", '.').is_err());
+    }
+
+    #[test]
+    fn test_parse_amount_with_decimal_comma() {
+        assert_eq!(parse_amount("1.234,56 €", ',').unwrap(), 1234.56);
+        assert_eq!(parse_amount("-12,5", ',').unwrap(), -12.5);
+        assert_eq!(parse_amount("(3,00)", ',').unwrap(), -3.0);
+    }
+
+    #[test]
+    fn test_parse_csv_with_decimal_comma() {
+        let content = "date;amount;description\n2024-01-15;-1.234,50;Miete\n";
+        let mapping = CsvMapping {
+            delimiter: ';',
+            decimal_separator: ',',
+            ..CsvMapping::default()
+        };
+
+        let rows = parse_statement(ImportFormat::Csv, content, &mapping, &Calendar::utc()).unwrap();
+        assert_eq!(rows[0].1.as_ref().unwrap().amount, -1234.5);
+
+        let mapping = CsvMapping { decimal_separator: ' ', ..CsvMapping::default() };
+        assert!(parse_statement(ImportFormat::Csv, content, &mapping, &Calendar::utc()).is_err());
+    }
+
+    #[test]
+    fn test_dates_without_offset_use_user_timezone() {
+        let calendar = Calendar::new(common::Tz::Asia__Shanghai);
+        let content = "date,amount\n2024-01-15,-10\n2024-01-15T09:30:00+00:00,-20\n";
+        let rows = parse_csv(content, &CsvMapping::default(), &calendar).unwrap();
+        // 本地日期取北京时间零点
+        assert_eq!(rows[0].1.as_ref().unwrap().date, Utc.with_ymd_and_hms(2024, 1, 14, 16, 0, 0).unwrap());
+        // 自带偏移的时刻不受用户时区影响
+        assert_eq!(rows[1].1.as_ref().unwrap().date, Utc.with_ymd_and_hms(2024, 1, 15, 9, 30, 0).unwrap());
+
+        let mapping = CsvMapping { date_format: "%Y-%m-%d %H:%M".to_string(), ..CsvMapping::default() };
+        let rows = parse_csv("date,amount\n2024-01-15 08:00,-5\n", &mapping, &calendar).unwrap();
+        assert_eq!(rows[0].1.as_ref().unwrap().date, Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap());
+
+        assert_eq!(parse_ofx_date("20240116", &calendar).unwrap(), Utc.with_ymd_and_hms(2024, 1, 15, 16, 0, 0).unwrap());
+        assert_eq!(
+            parse_qif_date("1/16/24", "%Y-%m-%d", &calendar).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 15, 16, 0, 0).unwrap()
+        );
+    }
+}
@@ -1,18 +1,26 @@
+mod categories;
 mod handlers;
+mod import;
+mod recurring;
+mod rules;
+mod service;
+mod suggest;
 
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post, put, delete},
     Router,
     middleware,
 };
-use common::{DatabaseConnection, middleware::auth_middleware};
+use common::{DatabaseConnection, JwtManager, KeyRing, middleware::{auth_middleware, AuthState, RateLimit, RateLimitLayer}};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub struct AppState {
     pub db: DatabaseConnection,
 }
 
-pub fn create_router(db: DatabaseConnection) -> Router {
+pub fn create_router(db: DatabaseConnection, jwt: Arc<JwtManager>) -> Router {
     let state = Arc::new(AppState { db: db.clone() });
     
     Router::new()
@@ -21,10 +29,42 @@ pub fn create_router(db: DatabaseConnection) -> Router {
         .route("/transactions/:id", get(handlers::get_transaction))
         .route("/transactions/:id", put(handlers::update_transaction))
         .route("/transactions/:id", delete(handlers::delete_transaction))
+        .route("/transactions/:id/review", post(handlers::review_duplicate))
         .route("/transactions/statistics", get(handlers::get_statistics))
-        .route("/categories", get(handlers::list_categories))
-        .route("/categories", post(handlers::create_category))
-        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware))
+        .route("/transactions/suggest-category", get(suggest::suggest_category))
+        .route(
+            "/transactions/import",
+            post(handlers::import_transactions)
+                .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
+                .layer(RateLimitLayer::per_user(&db.redis, RateLimit::per_hour("import", 5))),
+        )
+        .route("/transactions/import/:id", get(handlers::get_import_status))
+        .route("/categories", get(categories::list_categories))
+        .route("/categories", post(categories::create_category))
+        .route("/categories/tree", get(categories::category_tree))
+        .route("/categories/reorder", post(categories::reorder_categories))
+        .route("/categories/:id", put(categories::update_category))
+        .route("/categories/:id", delete(categories::delete_category))
+        .route("/categories/:id/archive", post(categories::archive_category))
+        .route("/categories/:id/unarchive", post(categories::unarchive_category))
+        .route("/categories/:id/merge", post(categories::merge_category))
+        .route("/rules", get(rules::list_rules))
+        .route("/rules", post(rules::create_rule))
+        .route("/rules/apply", post(rules::apply_rules))
+        .route("/rules/:id", put(rules::update_rule))
+        .route("/rules/:id", delete(rules::delete_rule))
+        .route("/recurring", get(recurring::list_recurring))
+        .route("/recurring", post(recurring::create_recurring))
+        .route("/recurring/upcoming", get(recurring::upcoming_bills))
+        .route("/recurring/:id", get(recurring::get_recurring_transaction))
+        .route("/recurring/:id", put(recurring::update_recurring))
+        .route("/recurring/:id", delete(recurring::delete_recurring))
+        .route("/recurring/:id/occurrences/:date", put(recurring::modify_occurrence))
+        .route("/recurring/:id/occurrences/:date", delete(recurring::restore_occurrence))
+        .route("/recurring/:id/occurrences/:date/skip", post(recurring::skip_occurrence))
+        .layer(RateLimitLayer::per_user(&db.redis, RateLimit::global_user()))
+        .layer(middleware::from_fn_with_state(AuthState::new(jwt.clone(), db.clone()), auth_middleware))
+        .layer(RateLimitLayer::per_ip(&db.redis, RateLimit::global_ip()))
         .with_state(state)
 }
 
@@ -32,6 +72,8 @@ pub fn create_router(db: DatabaseConnection) -> Router {
 async fn main() {
     tracing_subscriber::fmt::init();
     
+    let jwt = Arc::new(JwtManager::new(KeyRing::from_env().expect("Failed to load JWT keys")));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
@@ -39,8 +81,22 @@ async fn main() {
     let db = DatabaseConnection::new(&mongo_uri, &redis_uri, pg_uri.as_deref(), "abook")
         .await
         .expect("Failed to connect to database");
+
+    if let Err(e) = categories::ensure_indexes(&db.mongo).await {
+        tracing::warn!("Failed to create category indexes: {}", e);
+    }
+    if let Err(e) = recurring::ensure_indexes(&db.mongo).await {
+        tracing::warn!("Failed to create recurring transaction indexes: {}", e);
+    }
+
+    let recurring_interval = std::env::var("RECURRING_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600);
+    recurring::spawn_recurring_job(db.clone(), std::time::Duration::from_secs(recurring_interval));
+    import::spawn_stale_job_sweep(db.clone());
     
-    let app = create_router(db);
+    let app = create_router(db, jwt);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3002")
         .await
@@ -48,7 +104,7 @@ async fn main() {
     
     tracing::info!("Transaction service listening on port 3002");
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
@@ -0,0 +1,938 @@
+// 周期性交易 - 定期账单的增删改查、单次跳过或修改，以及按发生日生成交易的后台任务
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use common::middleware::{scope, Authorized};
+use common::{
+    ensure_transaction_category, Account, ApiResponse, Calendar, DatabaseConnection, DuplicateDetector, Error,
+    OccurrenceException, RecurrenceRule, RecurringTransaction, Result, Transaction, TransactionTemplate,
+    ACCOUNT_STATUS_ARCHIVED,
+};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{service, AppState};
+
+/// 调度器处理一个周期性交易时持有的租约时长
+const LEASE_SECS: i64 = 300;
+/// 每次最多处理的发生日数，长时间停机后的补记分多次完成
+const MAX_OCCURRENCES_PER_RUN: usize = 366;
+/// 开始日期最早可以回溯的天数，避免一次补记过多历史交易
+const MAX_BACKFILL_DAYS: i64 = 366;
+
+pub const RECURRING_STATUS_ACTIVE: &str = "active";
+pub const RECURRING_STATUS_PAUSED: &str = "paused";
+pub const RECURRING_STATUS_COMPLETED: &str = "completed";
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecurringRequest {
+    pub name: String,
+    pub rrule: String,
+    pub start_date: NaiveDate,
+    pub transaction_type: String,
+    pub amount: f64,
+    /// 默认为账户币种
+    pub currency: Option<String>,
+    pub account_id: String,
+    pub to_account_id: Option<String>,
+    /// 转账可以为空
+    #[serde(default)]
+    pub category_id: String,
+    pub description: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// 只更新请求中出现的字段；修改规则不会重新生成已处理的发生日
+#[derive(Debug, Deserialize)]
+pub struct UpdateRecurringRequest {
+    pub name: Option<String>,
+    pub rrule: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    /// active 或 paused
+    pub status: Option<String>,
+    pub amount: Option<f64>,
+    /// 未给出时，更换账户后改为新账户的币种
+    pub currency: Option<String>,
+    pub account_id: Option<String>,
+    pub to_account_id: Option<String>,
+    pub category_id: Option<String>,
+    pub description: Option<String>,
+    pub payee: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// 修改单次发生，未给出的字段沿用模板
+#[derive(Debug, Deserialize)]
+pub struct ModifyOccurrenceRequest {
+    pub amount: Option<f64>,
+    pub description: Option<String>,
+    pub category_id: Option<String>,
+    pub payee: Option<String>,
+    /// 改到另一天入账
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct UpcomingQuery {
+    /// 默认 30 天，最多 366 天
+    pub days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpcomingBill {
+    pub recurring_id: String,
+    pub name: String,
+    /// 入账日期 (单次改期后为新日期)
+    pub date: NaiveDate,
+    /// 按规则的发生日
+    pub scheduled_date: NaiveDate,
+    pub transaction_type: String,
+    pub amount: f64,
+    pub currency: String,
+    pub account_id: String,
+    pub category_id: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payee: Option<String>,
+    pub modified: bool,
+    /// 已到期但尚未生成 (如账户已归档导致生成失败)
+    pub overdue: bool,
+}
+
+/// 一次发生：规则上的日期、实际入账日期以及对应的例外
+struct Occurrence<'a> {
+    date: NaiveDate,
+    effective_date: NaiveDate,
+    exception: Option<&'a OccurrenceException>,
+}
+
+impl Occurrence<'_> {
+    fn skipped(&self) -> bool {
+        self.exception.is_some_and(|e| e.skip)
+    }
+}
+
+/// 尚未处理的发生日 (晚于 last_occurrence)，按规则顺序
+fn pending_occurrences<'a>(
+    recurring: &'a RecurringTransaction,
+    rule: &RecurrenceRule,
+) -> impl Iterator<Item = Occurrence<'a>> {
+    let last = recurring.last_occurrence;
+    rule.occurrences(recurring.start_date)
+        .skip_while(move |date| last.is_some_and(|last| *date <= last))
+        .map(move |date| {
+            let exception = recurring.exceptions.iter().find(|e| e.date == date);
+            Occurrence {
+                date,
+                effective_date: exception.and_then(|e| e.moved_to).unwrap_or(date),
+                exception,
+            }
+        })
+}
+
+/// 下一次入账的日期，即首个待处理发生日改期后的实际日期，作为调度器筛选到期任务的依据；
+/// 发生日按规则顺序处理，之后的发生日不会早于它入账
+fn next_due(recurring: &RecurringTransaction) -> Result<Option<NaiveDate>> {
+    let rule = RecurrenceRule::parse(&recurring.rrule)?;
+    Ok(pending_occurrences(recurring, &rule).next().map(|o| o.effective_date))
+}
+
+/// 生成的交易按 (user_id, external_id) 唯一，并发运行或重试时同一发生日只会入账一次；
+/// 只约束 recurring: 前缀的编号，导入的银行流水号在不同账户间可能重复
+pub async fn ensure_indexes(db: &Database) -> Result<()> {
+    db.collection::<Transaction>("transactions")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "user_id": 1, "external_id": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("user_recurring_external_id".to_string())
+                        .unique(true)
+                        .partial_filter_expression(doc! {
+                            "external_id": { "$gte": "recurring:", "$lt": "recurring;" },
+                        })
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// 生成交易的 external_id，同一发生日只会生成一次
+fn external_id(recurring: &RecurringTransaction, date: NaiveDate) -> String {
+    format!("recurring:{}:{}", recurring.id, date)
+}
+
+/// 按模板和例外生成交易，入账时间为本地日期的零点
+fn build_transaction(
+    recurring: &RecurringTransaction,
+    occurrence: &Occurrence,
+    calendar: &Calendar,
+    transfer: Option<(f64, f64)>,
+) -> Transaction {
+    let template = &recurring.template;
+    let exception = occurrence.exception;
+    let now = Utc::now();
+    let mut transaction = Transaction {
+        id: Some(ObjectId::new().to_hex()),
+        user_id: recurring.user_id.clone(),
+        transaction_type: template.transaction_type.clone(),
+        amount: exception.and_then(|e| e.amount).unwrap_or(template.amount),
+        currency: template.currency.clone(),
+        account_id: template.account_id.clone(),
+        to_account_id: template.to_account_id.clone(),
+        to_amount: transfer.map(|(to_amount, _)| to_amount),
+        exchange_rate: transfer.map(|(_, rate)| rate),
+        category_id: exception
+            .and_then(|e| e.category_id.clone())
+            .unwrap_or_else(|| template.category_id.clone()),
+        subcategory_id: None,
+        splits: None,
+        tags: template.tags.clone(),
+        description: exception
+            .and_then(|e| e.description.clone())
+            .unwrap_or_else(|| template.description.clone()),
+        payee: exception.and_then(|e| e.payee.clone()).or_else(|| template.payee.clone()),
+        transaction_date: calendar.start_of_day(occurrence.effective_date),
+        location: None,
+        attachments: None,
+        dedup_hash: None,
+        duplicate_of: None,
+        external_id: Some(external_id(recurring, occurrence.date)),
+        status: "confirmed".to_string(),
+        notes: Some(format!("Recurring: {}", recurring.name)),
+        created_at: now,
+        updated_at: now,
+        created_by: recurring.user_id.clone(),
+    };
+    transaction.dedup_hash = Some(DuplicateDetector::canonical_hash(&transaction));
+    transaction
+}
+
+/// 生成所有已到期的发生日，返回新写入的交易数；每处理一个发生日就持久化进度，
+/// 中断后重试时按 external_id 跳过已写入的交易，与其他运行并发写入同一发生日时由唯一索引拦截
+async fn materialize(db: &DatabaseConnection, recurring: &RecurringTransaction, now: DateTime<Utc>) -> Result<u32> {
+    let rule = RecurrenceRule::parse(&recurring.rrule)?;
+    let calendar = Calendar::for_user(&db.mongo, &recurring.user_id).await?;
+    let today = calendar.local_date(now);
+    let collection = db.mongo.collection::<RecurringTransaction>("recurring_transactions");
+    let transactions = db.mongo.collection::<Transaction>("transactions");
+
+    let mut generated = 0;
+    // 按规则顺序处理，改期到未来的发生日会让后续发生日等到它入账后再生成
+    let mut pending = pending_occurrences(recurring, &rule).peekable();
+    for _ in 0..MAX_OCCURRENCES_PER_RUN {
+        let Some(occurrence) = pending.next_if(|o| o.effective_date <= today) else {
+            break;
+        };
+
+        if !occurrence.skipped() {
+            let existing = transactions
+                .count_documents(
+                    doc! {
+                        "user_id": &recurring.user_id,
+                        "external_id": external_id(recurring, occurrence.date),
+                    },
+                    None,
+                )
+                .await?;
+            if existing == 0 {
+                let template = &recurring.template;
+                let transfer = match &template.to_account_id {
+                    Some(to_account_id) if template.transaction_type == "transfer" => {
+                        let rate = service::resolve_transfer_rate(
+                            db,
+                            &recurring.user_id,
+                            &template.account_id,
+                            to_account_id,
+                            &template.currency,
+                            None,
+                            calendar.start_of_day(occurrence.effective_date),
+                        )
+                        .await?;
+                        let amount = occurrence.exception.and_then(|e| e.amount).unwrap_or(template.amount);
+                        Some((service::converted_amount(amount, rate), rate))
+                    }
+                    _ => None,
+                };
+                let transaction = build_transaction(recurring, &occurrence, &calendar, transfer);
+                // 未指定分类的转账无需校验分类
+                if !transaction.category_id.is_empty() || transaction.transaction_type != "transfer" {
+                    ensure_transaction_category(
+                        &db.mongo,
+                        &recurring.user_id,
+                        &transaction.category_id,
+                        &transaction.transaction_type,
+                    )
+                    .await?;
+                }
+                match service::insert_transaction(db, &transaction).await {
+                    Ok(()) => generated += 1,
+                    // 已由并发的另一次运行写入
+                    Err(Error::Conflict(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        collection
+            .update_one(
+                doc! { "_id": &recurring.id },
+                doc! {
+                    "$set": {
+                        "last_occurrence": bson::to_bson(&occurrence.date).unwrap(),
+                        "updated_at": bson::to_bson(&Utc::now()).unwrap(),
+                    },
+                    "$inc": { "generated_count": if occurrence.skipped() { 0 } else { 1 } },
+                },
+                None,
+            )
+            .await?;
+    }
+
+    let next = pending.next().map(|o| o.effective_date);
+
+    let mut set = doc! { "updated_at": bson::to_bson(&Utc::now()).unwrap() };
+    let mut unset = doc! { "last_error": "" };
+    match next {
+        Some(next) => {
+            set.insert("next_occurrence", bson::to_bson(&next).unwrap());
+        }
+        None => {
+            set.insert("status", RECURRING_STATUS_COMPLETED);
+            unset.insert("next_occurrence", "");
+        }
+    }
+    collection
+        .update_one(doc! { "_id": &recurring.id }, doc! { "$set": set, "$unset": unset }, None)
+        .await?;
+    Ok(generated)
+}
+
+/// 取得租约后生成到期交易；其他实例正在处理或已暂停时返回 0
+pub async fn process(db: &DatabaseConnection, id: &str) -> Result<u32> {
+    let now = Utc::now();
+    let collection = db.mongo.collection::<RecurringTransaction>("recurring_transactions");
+    let claimed = collection
+        .find_one_and_update(
+            doc! {
+                "_id": id,
+                "status": RECURRING_STATUS_ACTIVE,
+                "$or": [
+                    { "locked_until": { "$exists": false } },
+                    { "locked_until": { "$lt": bson::to_bson(&now).unwrap() } },
+                ],
+            },
+            doc! { "$set": { "locked_until": bson::to_bson(&(now + Duration::seconds(LEASE_SECS))).unwrap() } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await?;
+    let Some(recurring) = claimed else {
+        return Ok(0);
+    };
+
+    let result = materialize(db, &recurring, now).await;
+    let mut update = doc! { "$unset": { "locked_until": "" } };
+    if let Err(e) = &result {
+        update.insert("$set", doc! { "last_error": e.to_string() });
+    }
+    collection.update_one(doc! { "_id": id }, update, None).await?;
+    result
+}
+
+/// 处理所有到期的周期性交易，返回 (处理数, 生成的交易数)
+pub async fn run_due(db: &DatabaseConnection) -> Result<(u64, u64)> {
+    // 各时区的本地日期最多比 UTC 日期晚一天，先粗筛再按用户时区精确判断
+    let horizon = (Utc::now() + Duration::days(1)).date_naive();
+    let mut cursor = db
+        .mongo
+        .collection::<RecurringTransaction>("recurring_transactions")
+        .find(
+            doc! {
+                "status": RECURRING_STATUS_ACTIVE,
+                "next_occurrence": { "$lte": bson::to_bson(&horizon).unwrap() },
+            },
+            None,
+        )
+        .await?;
+
+    let mut ids = Vec::new();
+    while cursor.advance().await? {
+        let recurring: RecurringTransaction = cursor.deserialize_current()?;
+        ids.push(recurring.id);
+    }
+
+    let (mut processed, mut generated) = (0, 0);
+    for id in ids {
+        match process(db, &id).await {
+            Ok(count) => {
+                processed += 1;
+                generated += count as u64;
+            }
+            Err(e) => tracing::warn!("Failed to materialize recurring transaction {}: {}", id, e),
+        }
+    }
+    Ok((processed, generated))
+}
+
+/// 启动定期生成周期性交易的任务
+pub fn spawn_recurring_job(db: DatabaseConnection, interval: std::time::Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match run_due(&db).await {
+                Ok((processed, generated)) if processed > 0 => {
+                    tracing::info!("Processed {} recurring transactions, generated {}", processed, generated)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Recurring transaction job failed: {}", e),
+            }
+        }
+    });
+}
+
+async fn find_account(db: &Database, user_id: &str, account_id: &str) -> Result<Account> {
+    let account = db
+        .collection::<Account>("accounts")
+        .find_one(doc! { "_id": account_id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::Validation(format!("Unknown account: {}", account_id)))?;
+    if account.status == ACCOUNT_STATUS_ARCHIVED {
+        return Err(Error::Validation(format!("Account {} is archived", account.name)));
+    }
+    Ok(account)
+}
+
+/// 校验模板：金额为正、账户存在且未归档、转账有不同的目标账户、分类与交易类型相符 (转账可以不指定分类)
+async fn validate_template(db: &Database, user_id: &str, template: &TransactionTemplate) -> Result<()> {
+    if template.amount <= 0.0 {
+        return Err(Error::Validation("Amount must be positive".to_string()));
+    }
+    if !["expense", "income", "transfer"].contains(&template.transaction_type.as_str()) {
+        return Err(Error::Validation("transaction_type must be expense, income or transfer".to_string()));
+    }
+    find_account(db, user_id, &template.account_id).await?;
+    if template.transaction_type == "transfer" {
+        let to_account_id = template
+            .to_account_id
+            .as_deref()
+            .ok_or_else(|| Error::Validation("to_account_id is required for transfers".to_string()))?;
+        if to_account_id == template.account_id {
+            return Err(Error::Validation("Cannot transfer to the same account".to_string()));
+        }
+        find_account(db, user_id, to_account_id).await?;
+    }
+    if template.category_id.is_empty() {
+        if template.transaction_type != "transfer" {
+            return Err(Error::Validation("category_id is required".to_string()));
+        }
+    } else {
+        ensure_transaction_category(db, user_id, &template.category_id, &template.transaction_type).await?;
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 50 {
+        return Err(Error::Validation("Name must be 1-50 characters".to_string()));
+    }
+    Ok(name.to_string())
+}
+
+async fn check_start_date(db: &Database, user_id: &str, start_date: NaiveDate) -> Result<()> {
+    let today = Calendar::for_user(db, user_id).await?.local_date(Utc::now());
+    if start_date < today - Duration::days(MAX_BACKFILL_DAYS) {
+        return Err(Error::Validation(format!(
+            "start_date cannot be more than {} days in the past",
+            MAX_BACKFILL_DAYS
+        )));
+    }
+    Ok(())
+}
+
+async fn get_recurring(db: &Database, user_id: &str, id: &str) -> Result<RecurringTransaction> {
+    db.collection::<RecurringTransaction>("recurring_transactions")
+        .find_one(doc! { "_id": id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Recurring transaction not found".to_string()))
+}
+
+/// 保存修改并重新计算下一个发生日，然后立即生成已到期的发生日
+async fn save_and_process(db: &DatabaseConnection, mut recurring: RecurringTransaction) -> Result<RecurringTransaction> {
+    recurring.next_occurrence = next_due(&recurring)?;
+    if recurring.next_occurrence.is_none() {
+        recurring.status = RECURRING_STATUS_COMPLETED.to_string();
+    } else if recurring.status == RECURRING_STATUS_COMPLETED {
+        recurring.status = RECURRING_STATUS_ACTIVE.to_string();
+    }
+    recurring.updated_at = Utc::now();
+
+    let collection = db.mongo.collection::<RecurringTransaction>("recurring_transactions");
+    let replaced = collection
+        .replace_one(
+            doc! {
+                "_id": &recurring.id,
+                "$or": [
+                    { "locked_until": { "$exists": false } },
+                    { "locked_until": { "$lt": bson::to_bson(&Utc::now()).unwrap() } },
+                ],
+            },
+            &recurring,
+            None,
+        )
+        .await?;
+    if replaced.matched_count == 0 {
+        return Err(Error::Conflict("Recurring transaction is being processed, try again".to_string()));
+    }
+
+    if let Err(e) = process(db, &recurring.id).await {
+        tracing::warn!("Failed to materialize recurring transaction {}: {}", recurring.id, e);
+    }
+    get_recurring(&db.mongo, &recurring.user_id, &recurring.id).await
+}
+
+pub async fn list_recurring(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+) -> Result<Json<ApiResponse<Vec<RecurringTransaction>>>> {
+    let options = FindOptions::builder().sort(doc! { "next_occurrence": 1, "name": 1 }).build();
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<RecurringTransaction>("recurring_transactions")
+        .find(doc! { "user_id": &claims.user_id }, options)
+        .await?;
+
+    let mut items = Vec::new();
+    while cursor.advance().await? {
+        items.push(cursor.deserialize_current()?);
+    }
+    Ok(Json(ApiResponse::success(items)))
+}
+
+pub async fn get_recurring_transaction(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    let recurring = get_recurring(&state.db.mongo, &claims.user_id, &id).await?;
+    Ok(Json(ApiResponse::success(recurring)))
+}
+
+pub async fn create_recurring(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Json(req): Json<CreateRecurringRequest>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    let db = &state.db.mongo;
+    let name = validate_name(&req.name)?;
+    let rrule = RecurrenceRule::parse(&req.rrule)?.to_string();
+    check_start_date(db, &claims.user_id, req.start_date).await?;
+
+    let currency = match req.currency {
+        Some(currency) => currency,
+        None => find_account(db, &claims.user_id, &req.account_id).await?.currency,
+    };
+    let template = TransactionTemplate {
+        transaction_type: req.transaction_type,
+        amount: req.amount,
+        currency,
+        account_id: req.account_id,
+        to_account_id: req.to_account_id,
+        category_id: req.category_id,
+        description: req.description.unwrap_or_else(|| name.clone()),
+        payee: req.payee,
+        tags: req.tags,
+    };
+    validate_template(db, &claims.user_id, &template).await?;
+
+    let now = Utc::now();
+    let recurring = RecurringTransaction {
+        id: ObjectId::new().to_hex(),
+        user_id: claims.user_id,
+        name,
+        template,
+        rrule,
+        start_date: req.start_date,
+        status: RECURRING_STATUS_ACTIVE.to_string(),
+        exceptions: Vec::new(),
+        last_occurrence: None,
+        next_occurrence: None,
+        generated_count: 0,
+        last_error: None,
+        locked_until: None,
+        created_at: now,
+        updated_at: now,
+    };
+    db.collection::<RecurringTransaction>("recurring_transactions")
+        .insert_one(&recurring, None)
+        .await?;
+
+    Ok(Json(ApiResponse::success(save_and_process(&state.db, recurring).await?)))
+}
+
+pub async fn update_recurring(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateRecurringRequest>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    let db = &state.db.mongo;
+    let mut recurring = get_recurring(db, &claims.user_id, &id).await?;
+
+    if let Some(name) = &req.name {
+        recurring.name = validate_name(name)?;
+    }
+    if let Some(rrule) = &req.rrule {
+        recurring.rrule = RecurrenceRule::parse(rrule)?.to_string();
+    }
+    if let Some(start_date) = req.start_date {
+        check_start_date(db, &claims.user_id, start_date).await?;
+        recurring.start_date = start_date;
+    }
+    if let Some(status) = req.status {
+        if status != RECURRING_STATUS_ACTIVE && status != RECURRING_STATUS_PAUSED {
+            return Err(Error::Validation("status must be active or paused".to_string()));
+        }
+        recurring.status = status;
+    }
+
+    let template = &mut recurring.template;
+    if let Some(amount) = req.amount {
+        template.amount = amount;
+    }
+    if let Some(account_id) = req.account_id {
+        if account_id != template.account_id && req.currency.is_none() {
+            template.currency = find_account(db, &claims.user_id, &account_id).await?.currency;
+        }
+        template.account_id = account_id;
+    }
+    if let Some(currency) = req.currency {
+        template.currency = currency;
+    }
+    if let Some(to_account_id) = req.to_account_id {
+        template.to_account_id = Some(to_account_id);
+    }
+    if let Some(category_id) = req.category_id {
+        template.category_id = category_id;
+    }
+    if let Some(description) = req.description {
+        template.description = description;
+    }
+    if let Some(payee) = req.payee {
+        template.payee = Some(payee);
+    }
+    if let Some(tags) = req.tags {
+        template.tags = Some(tags);
+    }
+    validate_template(db, &claims.user_id, &recurring.template).await?;
+
+    Ok(Json(ApiResponse::success(save_and_process(&state.db, recurring).await?)))
+}
+
+/// 删除周期性交易，已生成的交易保留
+pub async fn delete_recurring(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>> {
+    let result = state
+        .db
+        .mongo
+        .collection::<RecurringTransaction>("recurring_transactions")
+        .delete_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?;
+    if result.deleted_count == 0 {
+        return Err(Error::NotFound("Recurring transaction not found".to_string()));
+    }
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// 解析路径中的发生日，必须是规则上尚未处理的发生日
+fn check_occurrence(recurring: &RecurringTransaction, date: &str) -> Result<NaiveDate> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidInput(format!("Invalid date: {}", date)))?;
+    let rule = RecurrenceRule::parse(&recurring.rrule)?;
+    if !rule.is_occurrence(recurring.start_date, date) {
+        return Err(Error::Validation(format!("{} is not an occurrence of this schedule", date)));
+    }
+    if recurring.last_occurrence.is_some_and(|last| date <= last) {
+        return Err(Error::Validation(format!("Occurrence on {} has already been processed", date)));
+    }
+    Ok(date)
+}
+
+async fn set_exception(
+    state: &AppState,
+    user_id: &str,
+    id: &str,
+    date: &str,
+    build: impl FnOnce(NaiveDate) -> Option<OccurrenceException>,
+) -> Result<RecurringTransaction> {
+    let mut recurring = get_recurring(&state.db.mongo, user_id, id).await?;
+    let date = check_occurrence(&recurring, date)?;
+    recurring.exceptions.retain(|e| e.date != date);
+    recurring.exceptions.extend(build(date));
+    recurring.exceptions.sort_by_key(|e| e.date);
+    save_and_process(&state.db, recurring).await
+}
+
+/// 跳过单次发生
+pub async fn skip_occurrence(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path((id, date)): Path<(String, String)>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    let recurring = set_exception(&state, &claims.user_id, &id, &date, |date| {
+        Some(OccurrenceException {
+            date,
+            skip: true,
+            amount: None,
+            description: None,
+            category_id: None,
+            payee: None,
+            moved_to: None,
+        })
+    })
+    .await?;
+    Ok(Json(ApiResponse::success(recurring)))
+}
+
+/// 修改单次发生的金额、描述、分类、收款方或入账日期
+pub async fn modify_occurrence(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path((id, date)): Path<(String, String)>,
+    Json(req): Json<ModifyOccurrenceRequest>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    if req.amount.is_some_and(|amount| amount <= 0.0) {
+        return Err(Error::Validation("Amount must be positive".to_string()));
+    }
+    if let Some(category_id) = &req.category_id {
+        let recurring = get_recurring(&state.db.mongo, &claims.user_id, &id).await?;
+        ensure_transaction_category(
+            &state.db.mongo,
+            &claims.user_id,
+            category_id,
+            &recurring.template.transaction_type,
+        )
+        .await?;
+    }
+
+    let recurring = set_exception(&state, &claims.user_id, &id, &date, |date| {
+        Some(OccurrenceException {
+            date,
+            skip: false,
+            amount: req.amount,
+            description: req.description,
+            category_id: req.category_id,
+            payee: req.payee,
+            moved_to: req.date.filter(|moved| *moved != date),
+        })
+    })
+    .await?;
+    Ok(Json(ApiResponse::success(recurring)))
+}
+
+/// 撤销对单次发生的跳过或修改
+pub async fn restore_occurrence(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path((id, date)): Path<(String, String)>,
+) -> Result<Json<ApiResponse<RecurringTransaction>>> {
+    let recurring = set_exception(&state, &claims.user_id, &id, &date, |_| None).await?;
+    Ok(Json(ApiResponse::success(recurring)))
+}
+
+/// 未来 days 天内 (含已到期未生成的) 待入账的账单，按入账日期排序
+pub async fn upcoming_bills(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Query(query): Query<UpcomingQuery>,
+) -> Result<Json<ApiResponse<Vec<UpcomingBill>>>> {
+    let db = &state.db.mongo;
+    let days = query.days.unwrap_or(30).clamp(1, 366);
+    let today = Calendar::for_user(db, &claims.user_id).await?.local_date(Utc::now());
+    let horizon = today + Duration::days(days);
+
+    let mut cursor = db
+        .collection::<RecurringTransaction>("recurring_transactions")
+        .find(doc! { "user_id": &claims.user_id, "status": RECURRING_STATUS_ACTIVE }, None)
+        .await?;
+    let mut bills = Vec::new();
+    while cursor.advance().await? {
+        let recurring: RecurringTransaction = cursor.deserialize_current()?;
+        bills.extend(upcoming_for(&recurring, today, horizon)?);
+    }
+    bills.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(Json(ApiResponse::success(bills)))
+}
+
+fn upcoming_for(recurring: &RecurringTransaction, today: NaiveDate, horizon: NaiveDate) -> Result<Vec<UpcomingBill>> {
+    let rule = RecurrenceRule::parse(&recurring.rrule)?;
+    let template = &recurring.template;
+    Ok(pending_occurrences(recurring, &rule)
+        .take_while(|o| o.date <= horizon)
+        .filter(|o| !o.skipped() && o.effective_date <= horizon)
+        .map(|o| {
+            let exception = o.exception;
+            UpcomingBill {
+                recurring_id: recurring.id.clone(),
+                name: recurring.name.clone(),
+                date: o.effective_date,
+                scheduled_date: o.date,
+                transaction_type: template.transaction_type.clone(),
+                amount: exception.and_then(|e| e.amount).unwrap_or(template.amount),
+                currency: template.currency.clone(),
+                account_id: template.account_id.clone(),
+                category_id: exception
+                    .and_then(|e| e.category_id.clone())
+                    .unwrap_or_else(|| template.category_id.clone()),
+                description: exception
+                    .and_then(|e| e.description.clone())
+                    .unwrap_or_else(|| template.description.clone()),
+                payee: exception.and_then(|e| e.payee.clone()).or_else(|| template.payee.clone()),
+                modified: exception.is_some(),
+                overdue: o.effective_date < today,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn recurring(rrule: &str, last_occurrence: Option<NaiveDate>, exceptions: Vec<OccurrenceException>) -> RecurringTransaction {
+        let now = Utc::now();
+        RecurringTransaction {
+            id: "rent".to_string(),
+            user_id: "user".to_string(),
+            name: "Rent".to_string(),
+            template: TransactionTemplate {
+                transaction_type: "expense".to_string(),
+                amount: 4500.0,
+                currency: "CNY".to_string(),
+                account_id: "acc".to_string(),
+                to_account_id: None,
+                category_id: "housing".to_string(),
+                description: "Monthly rent".to_string(),
+                payee: Some("Landlord".to_string()),
+                tags: None,
+            },
+            rrule: rrule.to_string(),
+            start_date: date(2026, 1, 1),
+            status: RECURRING_STATUS_ACTIVE.to_string(),
+            exceptions,
+            last_occurrence,
+            next_occurrence: None,
+            generated_count: 0,
+            last_error: None,
+            locked_until: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn exception(date: NaiveDate, skip: bool, amount: Option<f64>, moved_to: Option<NaiveDate>) -> OccurrenceException {
+        OccurrenceException {
+            date,
+            skip,
+            amount,
+            description: None,
+            category_id: None,
+            payee: None,
+            moved_to,
+        }
+    }
+
+    #[test]
+    fn test_pending_starts_after_last_occurrence() {
+        let item = recurring("FREQ=MONTHLY;BYMONTHDAY=1", Some(date(2026, 3, 1)), Vec::new());
+        let rule = RecurrenceRule::parse(&item.rrule).unwrap();
+        let dates: Vec<NaiveDate> = pending_occurrences(&item, &rule).take(2).map(|o| o.date).collect();
+        assert_eq!(dates, vec![date(2026, 4, 1), date(2026, 5, 1)]);
+        assert_eq!(next_due(&item).unwrap(), Some(date(2026, 4, 1)));
+
+        let finished = recurring("FREQ=MONTHLY;BYMONTHDAY=1;COUNT=3", Some(date(2026, 3, 1)), Vec::new());
+        assert_eq!(next_due(&finished).unwrap(), None);
+    }
+
+    #[test]
+    fn test_next_due_follows_moved_occurrence() {
+        // 提前入账的发生日要在改期后的日期被调度，而不是等到原定日期
+        let earlier = recurring(
+            "FREQ=MONTHLY;BYMONTHDAY=1",
+            Some(date(2026, 3, 1)),
+            vec![exception(date(2026, 4, 1), false, None, Some(date(2026, 3, 25)))],
+        );
+        assert_eq!(next_due(&earlier).unwrap(), Some(date(2026, 3, 25)));
+
+        let later = recurring(
+            "FREQ=MONTHLY;BYMONTHDAY=1",
+            Some(date(2026, 3, 1)),
+            vec![exception(date(2026, 4, 1), false, None, Some(date(2026, 4, 6)))],
+        );
+        assert_eq!(next_due(&later).unwrap(), Some(date(2026, 4, 6)));
+    }
+
+    #[test]
+    fn test_upcoming_applies_exceptions() {
+        let item = recurring(
+            "FREQ=MONTHLY;BYMONTHDAY=1",
+            Some(date(2026, 9, 1)),
+            vec![
+                exception(date(2026, 11, 1), true, None, None),
+                exception(date(2026, 12, 1), false, Some(5000.0), Some(date(2026, 11, 28))),
+            ],
+        );
+
+        let bills = upcoming_for(&item, date(2026, 10, 18), date(2026, 12, 31)).unwrap();
+        let dates: Vec<NaiveDate> = bills.iter().map(|b| b.date).collect();
+        assert_eq!(dates, vec![date(2026, 10, 1), date(2026, 11, 28)]);
+        assert!(bills[0].overdue);
+        assert!(!bills[0].modified);
+        assert_eq!(bills[1].scheduled_date, date(2026, 12, 1));
+        assert_eq!(bills[1].amount, 5000.0);
+        assert!(bills[1].modified);
+    }
+
+    #[test]
+    fn test_build_transaction_uses_template_and_exception() {
+        let item = recurring("FREQ=MONTHLY;BYMONTHDAY=1", None, Vec::new());
+        let modified = exception(date(2026, 2, 1), false, Some(4800.0), Some(date(2026, 2, 3)));
+        let occurrence = Occurrence {
+            date: date(2026, 2, 1),
+            effective_date: date(2026, 2, 3),
+            exception: Some(&modified),
+        };
+
+        let tx = build_transaction(&item, &occurrence, &Calendar::utc(), None);
+        assert_eq!(tx.amount, 4800.0);
+        assert_eq!(tx.category_id, "housing");
+        assert_eq!(tx.payee.as_deref(), Some("Landlord"));
+        assert_eq!(tx.transaction_date.date_naive(), date(2026, 2, 3));
+        assert_eq!(tx.external_id.as_deref(), Some("recurring:rent:2026-02-01"));
+        assert!(tx.dedup_hash.is_some());
+    }
+}
@@ -0,0 +1,370 @@
+// 自动分类规则 - 规则的增删改查、在新交易上执行，以及对历史交易批量重新应用
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use common::middleware::{scope, Authorized};
+use common::{
+    category_matches, find_category, validate_rule, Account, ApiResponse, Calendar, Category, CategoryRule,
+    DuplicateDetector, Error, Result, RuleActions, RuleConditions, RuleEngine, Transaction, ACCOUNT_STATUS_ARCHIVED,
+};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId},
+    options::FindOptions,
+    Database,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{service, suggest, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRuleRequest {
+    pub name: String,
+    /// 未指定时排在最后
+    pub priority: Option<i32>,
+    pub is_active: Option<bool>,
+    pub conditions: RuleConditions,
+    pub actions: RuleActions,
+}
+
+/// 只更新请求中出现的字段；conditions、actions 整体替换
+#[derive(Debug, Deserialize)]
+pub struct UpdateRuleRequest {
+    pub name: Option<String>,
+    pub priority: Option<i32>,
+    pub is_active: Option<bool>,
+    pub conditions: Option<RuleConditions>,
+    pub actions: Option<RuleActions>,
+}
+
+#[derive(Deserialize)]
+pub struct ApplyRulesRequest {
+    /// YYYY-MM-DD (按用户时区) 或 RFC3339，结束日期包含当天
+    pub start_date: String,
+    pub end_date: String,
+    /// 只应用指定规则，默认全部启用的规则
+    pub rule_ids: Option<Vec<String>>,
+    /// 只统计会被修改的交易，不写入
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct RuleMatchCount {
+    pub rule_id: String,
+    pub name: String,
+    pub transactions: u64,
+}
+
+#[derive(Serialize)]
+pub struct ApplyRulesResult {
+    pub dry_run: bool,
+    pub scanned: u64,
+    pub updated: u64,
+    pub rules: Vec<RuleMatchCount>,
+}
+
+async fn find_rules(db: &Database, user_id: &str) -> Result<Vec<CategoryRule>> {
+    let options = FindOptions::builder().sort(doc! { "priority": 1, "created_at": 1 }).build();
+    let mut cursor = db
+        .collection::<CategoryRule>("category_rules")
+        .find(doc! { "user_id": user_id }, options)
+        .await?;
+
+    let mut rules = Vec::new();
+    while cursor.advance().await? {
+        rules.push(cursor.deserialize_current()?);
+    }
+    Ok(rules)
+}
+
+async fn build_engine(db: &Database, user_id: &str, rules: Vec<CategoryRule>) -> Result<RuleEngine> {
+    if rules.iter().all(|rule| !rule.is_active) {
+        return Ok(RuleEngine::new(Vec::new(), &[]));
+    }
+
+    let mut cursor = db
+        .collection::<Category>("categories")
+        .find(doc! { "user_id": user_id, "is_archived": false }, None)
+        .await?;
+    let mut categories = Vec::new();
+    while cursor.advance().await? {
+        categories.push(cursor.deserialize_current()?);
+    }
+    Ok(RuleEngine::new(rules, &categories))
+}
+
+/// 加载用户启用的规则；没有规则时不查询分类
+pub async fn load_engine(db: &Database, user_id: &str) -> Result<RuleEngine> {
+    let rules = find_rules(db, user_id).await?;
+    build_engine(db, user_id, rules).await
+}
+
+/// 把 RuleEngine::apply 返回的规则ID累加到计数中
+pub fn count_matches(counts: &mut HashMap<String, u64>, touched: Vec<String>) {
+    for rule_id in touched {
+        *counts.entry(rule_id).or_insert(0) += 1;
+    }
+}
+
+/// 累加规则的命中次数；交易已经写入，统计失败只记录日志
+pub async fn record_matches(db: &Database, counts: &HashMap<String, u64>) {
+    let now = bson::to_bson(&Utc::now()).unwrap();
+    for (rule_id, count) in counts {
+        let result = db
+            .collection::<CategoryRule>("category_rules")
+            .update_one(
+                doc! { "_id": rule_id },
+                doc! {
+                    "$inc": { "match_count": *count as i64 },
+                    "$set": { "last_matched_at": now.clone() },
+                },
+                None,
+            )
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to record matches for rule {}: {}", rule_id, e);
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 50 {
+        return Err(Error::Validation("Rule name must be 1-50 characters".to_string()));
+    }
+    Ok(name.to_string())
+}
+
+/// 校验规则引用的分类和账户属于当前用户，且分类与条件中的交易类型相符
+async fn check_references(
+    db: &Database,
+    user_id: &str,
+    conditions: &RuleConditions,
+    actions: &RuleActions,
+) -> Result<()> {
+    validate_rule(conditions, actions)?;
+
+    common::seed_system_categories(db, user_id).await?;
+    for category_id in [&actions.category_id, &actions.subcategory_id].into_iter().flatten() {
+        let category = find_category(db, user_id, category_id)
+            .await?
+            .ok_or_else(|| Error::Validation(format!("Unknown category: {}", category_id)))?;
+        if category.is_archived {
+            return Err(Error::Validation(format!("Category {} is archived", category.name)));
+        }
+        if let Some(transaction_type) = &conditions.transaction_type {
+            if !category_matches(&category.category_type, transaction_type) {
+                return Err(Error::Validation(format!(
+                    "Category {} is for {} transactions, not {}",
+                    category.name, category.category_type, transaction_type
+                )));
+            }
+        }
+    }
+
+    if let Some(account_ids) = &conditions.account_ids {
+        let found = db
+            .collection::<Account>("accounts")
+            .count_documents(doc! { "user_id": user_id, "_id": { "$in": account_ids } }, None)
+            .await?;
+        if found != account_ids.len() as u64 {
+            return Err(Error::Validation("Unknown account in account_ids".to_string()));
+        }
+    }
+    Ok(())
+}
+
+async fn get_rule(db: &Database, user_id: &str, id: &str) -> Result<CategoryRule> {
+    db.collection::<CategoryRule>("category_rules")
+        .find_one(doc! { "_id": id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Rule not found".to_string()))
+}
+
+pub async fn list_rules(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+) -> Result<Json<ApiResponse<Vec<CategoryRule>>>> {
+    let rules = find_rules(&state.db.mongo, &claims.user_id).await?;
+    Ok(Json(ApiResponse::success(rules)))
+}
+
+pub async fn create_rule(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Json(req): Json<CreateRuleRequest>,
+) -> Result<Json<ApiResponse<CategoryRule>>> {
+    let db = &state.db.mongo;
+    let name = validate_name(&req.name)?;
+    check_references(db, &claims.user_id, &req.conditions, &req.actions).await?;
+
+    let collection = db.collection::<CategoryRule>("category_rules");
+    let priority = match req.priority {
+        Some(priority) => priority,
+        None => collection.count_documents(doc! { "user_id": &claims.user_id }, None).await? as i32,
+    };
+
+    let now = Utc::now();
+    let rule = CategoryRule {
+        id: ObjectId::new().to_hex(),
+        user_id: claims.user_id,
+        name,
+        priority,
+        is_active: req.is_active.unwrap_or(true),
+        conditions: req.conditions,
+        actions: req.actions,
+        match_count: 0,
+        last_matched_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    collection.insert_one(&rule, None).await?;
+
+    Ok(Json(ApiResponse::success(rule)))
+}
+
+pub async fn update_rule(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateRuleRequest>,
+) -> Result<Json<ApiResponse<CategoryRule>>> {
+    let db = &state.db.mongo;
+    let mut rule = get_rule(db, &claims.user_id, &id).await?;
+
+    if let Some(name) = &req.name {
+        rule.name = validate_name(name)?;
+    }
+    if let Some(priority) = req.priority {
+        rule.priority = priority;
+    }
+    if let Some(is_active) = req.is_active {
+        rule.is_active = is_active;
+    }
+    if let Some(conditions) = req.conditions {
+        rule.conditions = conditions;
+    }
+    if let Some(actions) = req.actions {
+        rule.actions = actions;
+    }
+    check_references(db, &claims.user_id, &rule.conditions, &rule.actions).await?;
+    rule.updated_at = Utc::now();
+
+    db.collection::<CategoryRule>("category_rules")
+        .replace_one(doc! { "_id": &id, "user_id": &claims.user_id }, &rule, None)
+        .await?;
+
+    Ok(Json(ApiResponse::success(rule)))
+}
+
+pub async fn delete_rule(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>> {
+    let result = state
+        .db
+        .mongo
+        .collection::<CategoryRule>("category_rules")
+        .delete_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?;
+    if result.deleted_count == 0 {
+        return Err(Error::NotFound("Rule not found".to_string()));
+    }
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+/// 对日期区间内的历史交易重新应用规则：命中的规则覆盖原有分类和收款方，标签追加；
+/// 已归档账户的交易不做修改
+pub async fn apply_rules(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsWrite>,
+    Json(req): Json<ApplyRulesRequest>,
+) -> Result<Json<ApiResponse<ApplyRulesResult>>> {
+    let db = &state.db.mongo;
+    let user_id = &claims.user_id;
+
+    let calendar = Calendar::for_user(db, user_id).await?;
+    let start = calendar.parse_bound(&req.start_date, false)?;
+    let end = calendar.parse_bound(&req.end_date, true)?;
+    if start >= end {
+        return Err(Error::Validation("start_date must be before end_date".to_string()));
+    }
+
+    let mut rules = find_rules(db, user_id).await?;
+    if let Some(rule_ids) = &req.rule_ids {
+        rules.retain(|rule| rule_ids.contains(&rule.id));
+    }
+    let engine = build_engine(db, user_id, rules).await?;
+
+    let archived: Vec<String> = {
+        let mut cursor = db
+            .collection::<Account>("accounts")
+            .find(doc! { "user_id": user_id, "status": ACCOUNT_STATUS_ARCHIVED }, None)
+            .await?;
+        let mut ids = Vec::new();
+        while cursor.advance().await? {
+            ids.extend(cursor.deserialize_current()?.id);
+        }
+        ids
+    };
+
+    let mut scanned = 0;
+    let mut updated = 0;
+    let mut counts = HashMap::new();
+    if !engine.is_empty() {
+        let filter = doc! {
+            "user_id": user_id,
+            "account_id": { "$nin": &archived },
+            "transaction_date": {
+                "$gte": bson::to_bson(&start).unwrap(),
+                "$lt": bson::to_bson(&end).unwrap(),
+            },
+        };
+        let mut cursor = db.collection::<Transaction>("transactions").find(filter, None).await?;
+        while cursor.advance().await? {
+            let previous: Transaction = cursor.deserialize_current()?;
+            scanned += 1;
+
+            let mut transaction = previous.clone();
+            let touched = engine.apply(&mut transaction, true);
+            if touched.is_empty() {
+                continue;
+            }
+            if !req.dry_run {
+                transaction.dedup_hash = Some(DuplicateDetector::canonical_hash(&transaction));
+                transaction.updated_at = Utc::now();
+                service::recategorize_transaction(&state.db, &previous, &transaction).await?;
+            }
+            updated += 1;
+            count_matches(&mut counts, touched);
+        }
+    }
+
+    if !req.dry_run && updated > 0 {
+        record_matches(db, &counts).await;
+        suggest::invalidate(db, user_id).await;
+    }
+
+    let rules = engine
+        .rules()
+        .iter()
+        .map(|rule| RuleMatchCount {
+            rule_id: rule.id.clone(),
+            name: rule.name.clone(),
+            transactions: counts.get(&rule.id).copied().unwrap_or(0),
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::success(ApplyRulesResult {
+        dry_run: req.dry_run,
+        scanned,
+        updated,
+        rules,
+    })))
+}
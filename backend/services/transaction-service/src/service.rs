@@ -0,0 +1,471 @@
+// 服务层逻辑 - 交易写入及其对账户余额、预算的副作用
+use chrono::{DateTime, NaiveDate, Utc};
+use common::{
+    commit_or_abort, is_duplicate_key, Account, BalanceSnapshot, Budget, DatabaseConnection, DuplicateDetector, DuplicateMatch, Error,
+    Result, Transaction, ACCOUNT_STATUS_ARCHIVED,
+};
+use mongodb::{
+    bson::{self, doc},
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    ClientSession, Database,
+};
+use serde::Deserialize;
+
+/// 去重检查结果
+pub enum DuplicateCheck {
+    Unique,
+    /// 规范化哈希完全一致，附已有交易ID
+    Exact(String),
+    /// 模糊匹配命中，需要人工复核
+    Possible(DuplicateMatch),
+}
+
+#[derive(Deserialize)]
+struct QuoteEnvelope {
+    data: QuoteData,
+}
+
+#[derive(Deserialize)]
+struct QuoteData {
+    rate: f64,
+}
+
+/// 在同一个MongoDB事务中写入交易并更新账户余额和预算；违反唯一索引 (如重复的 external_id) 时返回 Conflict
+pub async fn insert_transaction(db: &DatabaseConnection, transaction: &Transaction) -> Result<()> {
+    let mut session = db.start_transaction().await?;
+    let mut snapshots = Vec::new();
+
+    let result = async {
+        db.mongo
+            .collection::<Transaction>("transactions")
+            .insert_one_with_session(transaction, None, &mut session)
+            .await
+            .map_err(|e| {
+                if is_duplicate_key(&e) {
+                    Error::Conflict("Transaction already exists".to_string())
+                } else {
+                    e.into()
+                }
+            })?;
+        apply_effects(&db.mongo, &mut session, transaction, &mut snapshots).await
+    }
+    .await;
+
+    commit_or_abort(&mut session, result).await?;
+    db.record_balance_history(&snapshots).await;
+    Ok(())
+}
+
+/// 用新文档替换交易：先冲销旧交易的副作用，再应用新交易的副作用
+pub async fn replace_transaction(
+    db: &DatabaseConnection,
+    previous: &Transaction,
+    updated: &Transaction,
+) -> Result<()> {
+    let mut session = db.start_transaction().await?;
+    let mut snapshots = Vec::new();
+
+    let result = async {
+        // 以 updated_at 做乐观锁，防止并发修改时按过期的旧文档冲销
+        let replaced = db
+            .mongo
+            .collection::<Transaction>("transactions")
+            .replace_one_with_session(
+                doc! {
+                    "_id": previous.id.as_ref().unwrap(),
+                    "user_id": &previous.user_id,
+                    "updated_at": bson::to_bson(&previous.updated_at).unwrap(),
+                },
+                updated,
+                None,
+                &mut session,
+            )
+            .await?;
+        if replaced.matched_count == 0 {
+            return Err(Error::Conflict("Transaction was modified concurrently".to_string()));
+        }
+
+        revert_effects(&db.mongo, &mut session, previous, &mut snapshots).await?;
+        apply_effects(&db.mongo, &mut session, updated, &mut snapshots).await
+    }
+    .await;
+
+    commit_or_abort(&mut session, result).await?;
+    db.record_balance_history(&snapshots).await;
+    Ok(())
+}
+
+/// 只修改分类、标签或收款方时替换交易：账户余额不变，支出改分类时把预算占用从旧分类转到新分类
+pub async fn recategorize_transaction(
+    db: &DatabaseConnection,
+    previous: &Transaction,
+    updated: &Transaction,
+) -> Result<()> {
+    let mut session = db.start_transaction().await?;
+
+    let result = async {
+        let replaced = db
+            .mongo
+            .collection::<Transaction>("transactions")
+            .replace_one_with_session(
+                doc! {
+                    "_id": previous.id.as_ref().unwrap(),
+                    "user_id": &previous.user_id,
+                    "updated_at": bson::to_bson(&previous.updated_at).unwrap(),
+                },
+                updated,
+                None,
+                &mut session,
+            )
+            .await?;
+        if replaced.matched_count == 0 {
+            return Err(Error::Conflict("Transaction was modified concurrently".to_string()));
+        }
+
+        if previous.transaction_type == "expense" && previous.category_amounts() != updated.category_amounts() {
+            adjust_budgets(&db.mongo, &mut session, previous, -1.0).await?;
+            adjust_budgets(&db.mongo, &mut session, updated, 1.0).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    commit_or_abort(&mut session, result).await
+}
+
+/// 删除交易并冲销其对账户余额和预算的影响，返回被删除的交易
+pub async fn remove_transaction(db: &DatabaseConnection, user_id: &str, id: &str) -> Result<Transaction> {
+    let mut session = db.start_transaction().await?;
+    let mut snapshots = Vec::new();
+
+    let result = async {
+        let removed = db
+            .mongo
+            .collection::<Transaction>("transactions")
+            .find_one_and_delete_with_session(doc! { "_id": id, "user_id": user_id }, None, &mut session)
+            .await?
+            .ok_or_else(|| Error::NotFound("Transaction not found".to_string()))?;
+
+        revert_effects(&db.mongo, &mut session, &removed, &mut snapshots).await?;
+        Ok(removed)
+    }
+    .await;
+
+    let removed = commit_or_abort(&mut session, result).await?;
+    db.record_balance_history(&snapshots).await;
+    Ok(removed)
+}
+
+/// 检查交易是否与已有交易重复：先按 dedup_hash 精确匹配，再在日期窗口内模糊匹配
+pub async fn check_duplicates(db: &DatabaseConnection, tx: &Transaction) -> Result<DuplicateCheck> {
+    let collection = db.mongo.collection::<Transaction>("transactions");
+
+    if let Some(hash) = &tx.dedup_hash {
+        let existing = collection
+            .find_one(doc! { "user_id": &tx.user_id, "dedup_hash": hash }, None)
+            .await?;
+        if let Some(existing) = existing {
+            return Ok(DuplicateCheck::Exact(existing.id.unwrap_or_default()));
+        }
+    }
+
+    let detector = DuplicateDetector::default();
+    let window = chrono::Duration::days(detector.date_window_days());
+    let filter = doc! {
+        "user_id": &tx.user_id,
+        "account_id": &tx.account_id,
+        "transaction_type": &tx.transaction_type,
+        "amount": {
+            "$gte": tx.amount - detector.amount_tolerance(),
+            "$lte": tx.amount + detector.amount_tolerance(),
+        },
+        "transaction_date": {
+            "$gte": bson::to_bson(&(tx.transaction_date - window)).unwrap(),
+            "$lte": bson::to_bson(&(tx.transaction_date + window)).unwrap(),
+        },
+    };
+
+    let mut cursor = collection.find(filter, None).await?;
+    let mut candidates = Vec::new();
+    while cursor.advance().await? {
+        candidates.push(cursor.deserialize_current()?);
+    }
+
+    Ok(detector
+        .find_matches(tx, &candidates)
+        .into_iter()
+        .next()
+        .map(DuplicateCheck::Possible)
+        .unwrap_or(DuplicateCheck::Unique))
+}
+
+/// 计算转账的汇率：同币种为1，否则优先使用手动汇率，再回退到报价服务 (补记的历史交易按交易日汇率)；
+/// 交易币种须与转出账户一致
+pub async fn resolve_transfer_rate(
+    db: &DatabaseConnection,
+    user_id: &str,
+    account_id: &str,
+    to_account_id: &str,
+    currency: &str,
+    manual_rate: Option<f64>,
+    transaction_date: DateTime<Utc>,
+) -> Result<f64> {
+    if account_id == to_account_id {
+        return Err(Error::InvalidInput("Cannot transfer to the same account".to_string()));
+    }
+
+    let accounts = db.mongo.collection::<Account>("accounts");
+    let from = accounts
+        .find_one(doc! { "_id": account_id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Account not found".to_string()))?;
+    let to = accounts
+        .find_one(doc! { "_id": to_account_id, "user_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("Destination account not found".to_string()))?;
+
+    match local_transfer_rate(&from.currency, &to.currency, currency, manual_rate)? {
+        Some(rate) => Ok(rate),
+        None => {
+            let date = (transaction_date.date_naive() < Utc::now().date_naive()).then(|| transaction_date.date_naive());
+            fetch_exchange_rate(&from.currency, &to.currency, date).await
+        }
+    }
+}
+
+/// 不查询报价服务即可确定的转账汇率：同币种为1，跨币种使用手动汇率；返回 None 表示需要查询报价
+fn local_transfer_rate(
+    from_currency: &str,
+    to_currency: &str,
+    currency: &str,
+    manual_rate: Option<f64>,
+) -> Result<Option<f64>> {
+    if currency != from_currency {
+        return Err(Error::InvalidInput(format!(
+            "Transfer currency {} does not match the source account currency {}",
+            currency, from_currency
+        )));
+    }
+    if from_currency == to_currency {
+        return Ok(Some(1.0));
+    }
+
+    match manual_rate {
+        Some(rate) if rate > 0.0 => Ok(Some(rate)),
+        Some(_) => Err(Error::InvalidInput("Exchange rate must be positive".to_string())),
+        None => Ok(None),
+    }
+}
+
+/// 按汇率换算转入金额，保留到分
+pub fn converted_amount(amount: f64, rate: f64) -> f64 {
+    (amount * rate * 100.0).round() / 100.0
+}
+
+/// 从报价服务获取融合后的汇率，指定日期时使用该日的历史汇率
+async fn fetch_exchange_rate(from: &str, to: &str, date: Option<NaiveDate>) -> Result<f64> {
+    let base_url = std::env::var("QUOTE_SERVICE_URL").unwrap_or_else(|_| "http://localhost:3005".to_string());
+    let mut url = format!("{}/quotes/convert?from={}&to={}&amount=1", base_url, from, to);
+    if let Some(date) = date {
+        url.push_str(&format!("&date={}", date.format("%Y-%m-%d")));
+    }
+
+    let envelope: QuoteEnvelope = reqwest::get(&url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| Error::InternalServer(format!("Failed to fetch exchange rate: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| Error::InternalServer(format!("Invalid exchange rate response: {}", e)))?;
+
+    Ok(envelope.data.rate)
+}
+
+async fn apply_effects(
+    db: &Database,
+    session: &mut ClientSession,
+    tx: &Transaction,
+    snapshots: &mut Vec<BalanceSnapshot>,
+) -> Result<()> {
+    apply_signed_effects(db, session, tx, 1.0, snapshots).await
+}
+
+async fn revert_effects(
+    db: &Database,
+    session: &mut ClientSession,
+    tx: &Transaction,
+    snapshots: &mut Vec<BalanceSnapshot>,
+) -> Result<()> {
+    apply_signed_effects(db, session, tx, -1.0, snapshots).await
+}
+
+/// sign 为 1.0 时应用交易的副作用，为 -1.0 时冲销；变动后的账户余额追加到 snapshots
+async fn apply_signed_effects(
+    db: &Database,
+    session: &mut ClientSession,
+    tx: &Transaction,
+    sign: f64,
+    snapshots: &mut Vec<BalanceSnapshot>,
+) -> Result<()> {
+    let reverting = sign < 0.0;
+    if tx.transaction_type == "transfer" && tx.to_account_id.is_none() {
+        return Err(Error::InvalidInput("to_account_id is required for transfers".to_string()));
+    }
+    for (account_id, delta) in signed_balance_effects(tx, sign) {
+        snapshots.extend(adjust_balance(db, session, &tx.user_id, account_id, delta, reverting).await?);
+    }
+    if tx.transaction_type == "expense" {
+        adjust_budgets(db, session, tx, sign).await?;
+    }
+    Ok(())
+}
+
+/// 交易对各账户余额的变动，sign 为 -1.0 时取反用于冲销
+fn signed_balance_effects(tx: &Transaction, sign: f64) -> Vec<(&str, f64)> {
+    tx.balance_effects()
+        .into_iter()
+        .map(|(account_id, delta)| (account_id, sign * delta))
+        .collect()
+}
+
+/// 调整账户余额并返回变动后的快照；冲销时账户已被删除则跳过，返回 None
+async fn adjust_balance(
+    db: &Database,
+    session: &mut ClientSession,
+    user_id: &str,
+    account_id: &str,
+    delta: f64,
+    reverting: bool,
+) -> Result<Option<BalanceSnapshot>> {
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+
+    // 归档账户只允许冲销已有交易，不再接受新的记账
+    let mut filter = doc! { "_id": account_id, "user_id": user_id };
+    if !reverting {
+        filter.insert("status", doc! { "$ne": ACCOUNT_STATUS_ARCHIVED });
+    }
+
+    let account = db
+        .collection::<Account>("accounts")
+        .find_one_and_update_with_session(
+            filter,
+            doc! {
+                "$inc": { "current_balance": delta },
+                "$set": { "updated_at": bson::to_bson(&Utc::now()).unwrap() },
+            },
+            options,
+            session,
+        )
+        .await?;
+
+    match account {
+        Some(account) => Ok(Some(BalanceSnapshot::of(&account, common::SNAPSHOT_AUTO))),
+        None => missing_account(account_id, reverting),
+    }
+}
+
+/// 找不到账户时：冲销可以跳过 (账户已删除，余额无需回滚)，新记账则报错
+fn missing_account(account_id: &str, reverting: bool) -> Result<Option<BalanceSnapshot>> {
+    if reverting {
+        tracing::warn!("Skipping balance reversal for deleted account {}", account_id);
+        Ok(None)
+    } else {
+        Err(Error::NotFound(format!("Account {} not found or archived", account_id)))
+    }
+}
+
+/// 按交易计入各预算分类的金额调整预算占用，sign 为 1.0 时计入，为 -1.0 时冲销；
+/// 拆分交易的各行分别计入对应分类的预算，同一预算只累计属于它的拆分行
+async fn adjust_budgets(
+    db: &Database,
+    session: &mut ClientSession,
+    tx: &Transaction,
+    sign: f64,
+) -> Result<()> {
+    let budgets_collection = db.collection::<Budget>("budgets");
+    let category_ids: Vec<&str> = tx.category_amounts().into_iter().map(|(category_id, _)| category_id).collect();
+    let filter = doc! {
+        "user_id": &tx.user_id,
+        "category_ids": { "$in": category_ids },
+        "start_date": { "$lte": bson::to_bson(&tx.transaction_date).unwrap() },
+        "end_date": { "$gte": bson::to_bson(&tx.transaction_date).unwrap() },
+        "status": "active"
+    };
+
+    let mut cursor = budgets_collection.find_with_session(filter, None, session).await?;
+    let mut budgets = Vec::new();
+    while cursor.advance(session).await? {
+        budgets.push(cursor.deserialize_current()?);
+    }
+
+    for mut budget in budgets {
+        budget.spent += sign * tx.amount_in_categories(&budget.category_ids);
+        budget.remaining = budget.amount - budget.spent;
+        budget.progress = (budget.spent / budget.amount) * 100.0;
+        budget.updated_at = chrono::Utc::now();
+
+        budgets_collection
+            .replace_one_with_session(doc! { "_id": budget.id.as_ref().unwrap() }, &budget, None, session)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_util::TransactionBuilder;
+
+    #[test]
+    fn test_local_transfer_rate() {
+        assert_eq!(local_transfer_rate("CNY", "CNY", "CNY", None).unwrap(), Some(1.0));
+        // 同币种忽略手动汇率
+        assert_eq!(local_transfer_rate("CNY", "CNY", "CNY", Some(7.2)).unwrap(), Some(1.0));
+        assert_eq!(local_transfer_rate("USD", "CNY", "USD", Some(7.2)).unwrap(), Some(7.2));
+        assert_eq!(local_transfer_rate("USD", "CNY", "USD", None).unwrap(), None);
+        assert!(local_transfer_rate("USD", "CNY", "USD", Some(0.0)).is_err());
+        assert!(local_transfer_rate("USD", "CNY", "CNY", Some(7.2)).is_err());
+    }
+
+    #[test]
+    fn test_converted_amount_rounds_to_cents() {
+        assert_eq!(converted_amount(100.0, 7.1234), 712.34);
+        assert_eq!(converted_amount(33.33, 0.1389), 4.63);
+        assert_eq!(converted_amount(50.0, 1.0), 50.0);
+    }
+
+    #[test]
+    fn test_transfer_balance_effects() {
+        let transfer = TransactionBuilder::new("transfer", 100.0)
+            .account("usd")
+            .transfer_to("cny", Some(712.34))
+            .build();
+        assert_eq!(signed_balance_effects(&transfer, 1.0), vec![("usd", -100.0), ("cny", 712.34)]);
+        // 冲销时两端反向
+        assert_eq!(signed_balance_effects(&transfer, -1.0), vec![("usd", 100.0), ("cny", -712.34)]);
+
+        // 同币种转账没有 to_amount，转入金额等于转出金额
+        let same_currency = TransactionBuilder::new("transfer", 80.0)
+            .account("cash")
+            .transfer_to("bank", None)
+            .build();
+        assert_eq!(signed_balance_effects(&same_currency, 1.0), vec![("cash", -80.0), ("bank", 80.0)]);
+    }
+
+    #[test]
+    fn test_missing_account_only_skipped_when_reverting() {
+        assert!(matches!(missing_account("gone", true), Ok(None)));
+        assert!(matches!(missing_account("gone", false), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_income_and_expense_balance_effects() {
+        let income = TransactionBuilder::new("income", 50.0).build();
+        let expense = TransactionBuilder::new("expense", 20.0).build();
+        assert_eq!(signed_balance_effects(&income, 1.0), vec![("acc", 50.0)]);
+        assert_eq!(signed_balance_effects(&expense, 1.0), vec![("acc", -20.0)]);
+        assert_eq!(signed_balance_effects(&expense, -1.0), vec![("acc", 20.0)]);
+    }
+}
@@ -0,0 +1,227 @@
+// 分类建议 - 按用户历史交易训练朴素贝叶斯模型，交易增删改时增量更新
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use common::middleware::{scope, Authorized};
+use common::{
+    category_matches, seed_system_categories, ApiResponse, Category, CategoryClassifier, Error, Result, Transaction,
+    ADJUSTMENT_CATEGORY_KEY, CATEGORY_TYPE_ADJUSTMENT,
+};
+use mongodb::{
+    bson::doc,
+    options::{FindOptions, ReplaceOptions},
+    Database,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// 首次训练时最多读取的历史交易数 (按日期从新到旧)
+const TRAINING_LIMIT: i64 = 5000;
+/// 增量更新遇到并发写入时的重试次数
+const MAX_UPDATE_RETRIES: usize = 3;
+
+/// 每个用户一个模型文档，_id 与 user_id 相同；version 用于乐观锁
+#[derive(Serialize, Deserialize)]
+struct CategoryModel {
+    #[serde(rename = "_id")]
+    id: String,
+    user_id: String,
+    version: i64,
+    classifier: CategoryClassifier,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SuggestQuery {
+    pub description: Option<String>,
+    pub payee: Option<String>,
+    pub amount: Option<f64>,
+    /// expense 或 income，默认 expense
+    pub transaction_type: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SuggestedCategory {
+    pub category_id: String,
+    pub name: String,
+    pub confidence: f64,
+}
+
+#[derive(Serialize)]
+pub struct CategorySuggestions {
+    pub suggestions: Vec<SuggestedCategory>,
+    /// 模型已学习的交易数，过少时建议仅供参考
+    pub trained_transactions: u32,
+}
+
+/// 只用收支交易训练；余额调整、拆分交易和待复核的疑似重复交易不代表用户的分类习惯
+fn training_sample(tx: &Transaction) -> Option<(String, Vec<String>)> {
+    let trainable = matches!(tx.transaction_type.as_str(), "expense" | "income")
+        && tx.category_id != ADJUSTMENT_CATEGORY_KEY
+        && tx.splits.is_none()
+        && tx.status != "possible_duplicate";
+    trainable.then(|| {
+        (
+            tx.category_id.clone(),
+            CategoryClassifier::features(&tx.description, tx.payee.as_deref(), tx.amount),
+        )
+    })
+}
+
+async fn train_from_history(db: &Database, user_id: &str) -> Result<CategoryModel> {
+    let options = FindOptions::builder()
+        .sort(doc! { "transaction_date": -1 })
+        .limit(TRAINING_LIMIT)
+        .build();
+    let mut cursor = db
+        .collection::<Transaction>("transactions")
+        .find(
+            doc! { "user_id": user_id, "transaction_type": { "$in": ["expense", "income"] } },
+            options,
+        )
+        .await?;
+
+    let mut classifier = CategoryClassifier::new();
+    while cursor.advance().await? {
+        let tx: Transaction = cursor.deserialize_current()?;
+        if let Some((category_id, features)) = training_sample(&tx) {
+            classifier.learn(&category_id, &features);
+        }
+    }
+
+    let model = CategoryModel {
+        id: user_id.to_string(),
+        user_id: user_id.to_string(),
+        version: 0,
+        classifier,
+        updated_at: Utc::now(),
+    };
+    db.collection::<CategoryModel>("category_models")
+        .replace_one(
+            doc! { "_id": user_id },
+            &model,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(model)
+}
+
+async fn load_model(db: &Database, user_id: &str) -> Result<CategoryModel> {
+    let model = db
+        .collection::<CategoryModel>("category_models")
+        .find_one(doc! { "_id": user_id }, None)
+        .await?;
+    match model {
+        Some(model) => Ok(model),
+        None => train_from_history(db, user_id).await,
+    }
+}
+
+async fn update_model(db: &Database, user_id: &str, previous: Option<&Transaction>, updated: Option<&Transaction>) -> Result<()> {
+    let forget = previous.and_then(training_sample);
+    let learn = updated.and_then(training_sample);
+    if forget == learn {
+        return Ok(());
+    }
+
+    let collection = db.collection::<CategoryModel>("category_models");
+    for _ in 0..MAX_UPDATE_RETRIES {
+        // 还没有模型时不必更新，首次请求建议时会从历史交易训练
+        let Some(mut model) = collection.find_one(doc! { "_id": user_id }, None).await? else {
+            return Ok(());
+        };
+        if let Some((category_id, features)) = &forget {
+            model.classifier.unlearn(category_id, features);
+        }
+        if let Some((category_id, features)) = &learn {
+            model.classifier.learn(category_id, features);
+        }
+
+        let version = model.version;
+        model.version += 1;
+        model.updated_at = Utc::now();
+        let result = collection
+            .replace_one(doc! { "_id": user_id, "version": version }, &model, None)
+            .await?;
+        if result.matched_count == 1 {
+            return Ok(());
+        }
+    }
+    Err(Error::Conflict("Category model was modified concurrently".to_string()))
+}
+
+/// 交易创建、修改或删除后增量更新模型：撤销 previous，学习 updated；
+/// 用户把交易改到别的分类即是对建议的纠正。交易已经写入，更新失败只记录日志
+pub async fn observe(db: &Database, user_id: &str, previous: Option<&Transaction>, updated: Option<&Transaction>) {
+    if let Err(e) = update_model(db, user_id, previous, updated).await {
+        tracing::warn!("Failed to update category model for user {}: {}", user_id, e);
+    }
+}
+
+/// 导入、批量应用规则、合并分类等批量修改后丢弃模型，下次请求建议时重新训练
+pub async fn invalidate(db: &Database, user_id: &str) {
+    let result = db
+        .collection::<CategoryModel>("category_models")
+        .delete_one(doc! { "_id": user_id }, None)
+        .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to reset category model for user {}: {}", user_id, e);
+    }
+}
+
+/// 按描述、收款方和金额推荐分类，只返回与交易类型相符且未归档的分类
+pub async fn suggest_category(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::TransactionsRead>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<ApiResponse<CategorySuggestions>>> {
+    let db = &state.db.mongo;
+    let transaction_type = query.transaction_type.as_deref().unwrap_or("expense");
+    if transaction_type != "expense" && transaction_type != "income" {
+        return Err(Error::Validation("transaction_type must be expense or income".to_string()));
+    }
+    let description = query.description.unwrap_or_default();
+    if description.trim().is_empty() && query.payee.as_deref().is_none_or(|p| p.trim().is_empty()) {
+        return Err(Error::Validation("description or payee is required".to_string()));
+    }
+    let limit = query.limit.unwrap_or(3).clamp(1, 10);
+
+    seed_system_categories(db, &claims.user_id).await?;
+    let mut cursor = db
+        .collection::<Category>("categories")
+        .find(doc! { "user_id": &claims.user_id, "is_archived": false }, None)
+        .await?;
+    let mut categories = HashMap::new();
+    while cursor.advance().await? {
+        let category: Category = cursor.deserialize_current()?;
+        if category.category_type != CATEGORY_TYPE_ADJUSTMENT
+            && category_matches(&category.category_type, transaction_type)
+        {
+            categories.insert(category.reference_id().to_string(), category.name);
+        }
+    }
+
+    let model = load_model(db, &claims.user_id).await?;
+    let features = CategoryClassifier::features(&description, query.payee.as_deref(), query.amount.unwrap_or(0.0));
+    let suggestions = model
+        .classifier
+        .suggest(&features, |category_id| categories.contains_key(category_id), limit)
+        .into_iter()
+        .map(|suggestion| SuggestedCategory {
+            name: categories.get(&suggestion.category_id).cloned().unwrap_or_default(),
+            category_id: suggestion.category_id,
+            confidence: (suggestion.confidence * 10000.0).round() / 10000.0,
+        })
+        .collect();
+
+    Ok(Json(ApiResponse::success(CategorySuggestions {
+        suggestions,
+        trained_transactions: model.classifier.documents(),
+    })))
+}
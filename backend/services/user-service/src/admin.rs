@@ -0,0 +1,173 @@
+// 管理员接口 - 用户查询、角色与状态管理
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use common::middleware::{revoke_issued_tokens, scope, Authorized};
+use common::{
+    ApiResponse, Error, PaginationMeta, PaginationResponse, Result, User, ROLE_ADMIN, ROLE_USER,
+    USER_STATUS_ACTIVE, USER_STATUS_DISABLED,
+};
+use mongodb::{
+    bson::{self, doc},
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{api_tokens, sessions, AppState};
+
+#[derive(Deserialize)]
+pub struct UserListQuery {
+    #[serde(default = "default_page")]
+    page: u64,
+    #[serde(default = "default_page_size")]
+    page_size: u64,
+    pub status: Option<String>,
+    pub role: Option<String>,
+    /// 按用户名或邮箱模糊匹配
+    pub search: Option<String>,
+}
+
+fn default_page() -> u64 { 1 }
+fn default_page_size() -> u64 { 20 }
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    pub role: Option<String>,
+    pub status: Option<String>,
+}
+
+fn escape_regex(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| {
+            let escape = "\\.+*?()|[]{}^$".contains(c);
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect()
+}
+
+pub async fn list_users(
+    State(state): State<Arc<AppState>>,
+    _: Authorized<scope::Admin>,
+    Query(query): Query<UserListQuery>,
+) -> Result<Json<ApiResponse<PaginationResponse<User>>>> {
+    if query.page == 0 || query.page_size == 0 || query.page_size > 100 {
+        return Err(Error::InvalidInput("page must be >= 1 and page_size between 1 and 100".to_string()));
+    }
+
+    let collection = state.db.mongo.collection::<User>("users");
+
+    let mut filter = doc! {};
+    if let Some(status) = query.status {
+        filter.insert("status", status);
+    }
+    if let Some(role) = query.role {
+        filter.insert("role", role);
+    }
+    if let Some(search) = query.search.filter(|s| !s.trim().is_empty()) {
+        let pattern = escape_regex(search.trim());
+        filter.insert(
+            "$or",
+            vec![
+                doc! { "username": { "$regex": &pattern, "$options": "i" } },
+                doc! { "email": { "$regex": &pattern, "$options": "i" } },
+            ],
+        );
+    }
+
+    let total = collection.count_documents(filter.clone(), None).await?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at": -1 })
+        .skip((query.page - 1) * query.page_size)
+        .limit(query.page_size as i64)
+        .build();
+    let mut cursor = collection.find(filter, options).await?;
+
+    let mut users = Vec::new();
+    while cursor.advance().await? {
+        let mut user: User = cursor.deserialize_current()?;
+        user.password_hash = String::new();
+        users.push(user);
+    }
+
+    Ok(Json(ApiResponse::success(PaginationResponse {
+        items: users,
+        pagination: PaginationMeta::new(total, query.page, query.page_size),
+    })))
+}
+
+pub async fn get_user(
+    State(state): State<Arc<AppState>>,
+    _: Authorized<scope::Admin>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<User>>> {
+    let mut user = state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+
+    user.password_hash = String::new();
+    Ok(Json(ApiResponse::success(user)))
+}
+
+/// 修改用户角色或状态；禁用用户时同时作废其所有会话和 API 令牌
+pub async fn update_user(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::Admin>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<Json<ApiResponse<User>>> {
+    if id == claims.user_id {
+        return Err(Error::BadRequest("Administrators cannot change their own role or status".to_string()));
+    }
+
+    let mut update = doc! { "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap() };
+    if let Some(role) = &req.role {
+        if role != ROLE_USER && role != ROLE_ADMIN {
+            return Err(Error::Validation(format!("role must be {} or {}", ROLE_USER, ROLE_ADMIN)));
+        }
+        update.insert("role", role);
+    }
+    if let Some(status) = &req.status {
+        if status != USER_STATUS_ACTIVE && status != USER_STATUS_DISABLED {
+            return Err(Error::Validation(format!(
+                "status must be {} or {}",
+                USER_STATUS_ACTIVE, USER_STATUS_DISABLED
+            )));
+        }
+        update.insert("status", status);
+    }
+    if req.role.is_none() && req.status.is_none() {
+        return Err(Error::InvalidInput("Nothing to update".to_string()));
+    }
+
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    let mut user = state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one_and_update(doc! { "_id": &id }, doc! { "$set": update }, options)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+
+    // 禁用或降为普通用户后，已签发的访问令牌立即失效，降级的用户刷新后拿到新角色的令牌
+    if user.status == USER_STATUS_DISABLED || req.role.as_deref() == Some(ROLE_USER) {
+        revoke_issued_tokens(&state.db.redis, &id, state.jwt.access_token_expiry()).await?;
+    }
+    if user.status == USER_STATUS_DISABLED {
+        sessions::revoke_all(&state.db.redis, &id).await?;
+        api_tokens::revoke_all(&state.db, &id).await?;
+        tracing::info!("User {} disabled by {}", id, claims.user_id);
+    }
+
+    user.password_hash = String::new();
+    Ok(Json(ApiResponse::success(user)))
+}
@@ -0,0 +1,160 @@
+// API 令牌 - 用户为脚本签发的只拥有部分权限的长期令牌
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use common::middleware::{scope, Authorized};
+use common::{ApiResponse, ApiToken, DatabaseConnection, Error, Result, API_TOKEN_SCOPES};
+use mongodb::{bson::{doc, oid::ObjectId}, options::FindOptions};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::AppState;
+
+/// 每个用户最多持有的 API 令牌数
+const MAX_TOKENS_PER_USER: u64 = 20;
+const DEFAULT_EXPIRY_DAYS: i64 = 90;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiToken {
+    /// 令牌明文，只在创建时返回
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+}
+
+fn validate_scopes(scopes: &[String]) -> Result<Vec<String>> {
+    let mut validated: Vec<String> = Vec::new();
+    for scope in scopes {
+        if !API_TOKEN_SCOPES.contains(&scope.as_str()) {
+            return Err(Error::Validation(format!(
+                "Unknown scope {}, allowed scopes: {}",
+                scope,
+                API_TOKEN_SCOPES.join(", ")
+            )));
+        }
+        if !validated.contains(scope) {
+            validated.push(scope.clone());
+        }
+    }
+    if validated.is_empty() {
+        return Err(Error::Validation("At least one scope is required".to_string()));
+    }
+    Ok(validated)
+}
+
+/// 删除用户的全部 API 令牌
+pub async fn revoke_all(db: &DatabaseConnection, user_id: &str) -> Result<u64> {
+    let result = db
+        .mongo
+        .collection::<ApiToken>("api_tokens")
+        .delete_many(doc! { "user_id": user_id }, None)
+        .await?;
+    Ok(result.deleted_count)
+}
+
+pub async fn create_api_token(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<CreateApiTokenRequest>,
+) -> Result<Json<ApiResponse<CreatedApiToken>>> {
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(Error::Validation("name must be 1-64 characters".to_string()));
+    }
+    let scopes = validate_scopes(&req.scopes)?;
+    let days = req.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS);
+    if !(1..=MAX_EXPIRY_DAYS).contains(&days) {
+        return Err(Error::Validation(format!("expires_in_days must be between 1 and {}", MAX_EXPIRY_DAYS)));
+    }
+
+    let collection = state.db.mongo.collection::<ApiToken>("api_tokens");
+    if collection.count_documents(doc! { "user_id": &claims.user_id }, None).await? >= MAX_TOKENS_PER_USER {
+        return Err(Error::Conflict(format!("At most {} API tokens per user", MAX_TOKENS_PER_USER)));
+    }
+
+    let now = Utc::now();
+    let api_token = ApiToken {
+        id: ObjectId::new().to_hex(),
+        user_id: claims.user_id.clone(),
+        name: name.to_string(),
+        scopes,
+        created_at: now,
+        expires_at: now + Duration::days(days),
+        last_used_at: None,
+    };
+    let token = state.jwt.generate_api_token(
+        &claims.user_id,
+        &claims.username,
+        &api_token.id,
+        &api_token.scopes,
+        api_token.expires_at,
+    )?;
+
+    collection.insert_one(&api_token, None).await?;
+
+    Ok(Json(ApiResponse::success(CreatedApiToken { token, api_token })))
+}
+
+pub async fn list_api_tokens(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+) -> Result<Json<ApiResponse<Vec<ApiToken>>>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let mut cursor = state
+        .db
+        .mongo
+        .collection::<ApiToken>("api_tokens")
+        .find(doc! { "user_id": &claims.user_id }, options)
+        .await?;
+
+    let mut tokens = Vec::new();
+    while cursor.advance().await? {
+        tokens.push(cursor.deserialize_current()?);
+    }
+
+    Ok(Json(ApiResponse::success(tokens)))
+}
+
+pub async fn delete_api_token(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>> {
+    let result = state
+        .db
+        .mongo
+        .collection::<ApiToken>("api_tokens")
+        .delete_one(doc! { "_id": &id, "user_id": &claims.user_id }, None)
+        .await?;
+
+    if result.deleted_count == 0 {
+        return Err(Error::NotFound("API token not found".to_string()));
+    }
+
+    Ok(Json(ApiResponse::success(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_scopes() {
+        let scopes = validate_scopes(&["reports:read".to_string(), "reports:read".to_string()]).unwrap();
+        assert_eq!(scopes, vec!["reports:read"]);
+
+        assert!(validate_scopes(&[]).is_err());
+        assert!(validate_scopes(&["admin".to_string()]).is_err());
+        assert!(validate_scopes(&["profile:write".to_string()]).is_err());
+    }
+}
@@ -1,15 +1,19 @@
 use axum::{
-    extract::{State, Extension},
+    extract::State,
     Json,
 };
-use common::{User, ApiResponse, Claims, Error, Result, JwtManager};
-use mongodb::bson::{doc, oid::ObjectId};
+use common::{is_duplicate_key, User, ApiResponse, Error, Result, ROLE_ADMIN, ROLE_USER, USER_STATUS_ACTIVE, USER_STATUS_PENDING};
+use common::middleware::{scope, Authorized, Lockout};
+use jsonwebtoken::jwk::JwkSet;
+use mongodb::{bson::{self, doc}, options::IndexOptions, Database, IndexModel};
 use std::sync::Arc;
 use serde::{Deserialize, Serialize};
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use argon2::password_hash::SaltString;
-use rand::rngs::OsRng;
 
+use crate::mailer::Email;
+use crate::mfa::{self, MfaChallenge};
+use crate::password::{self, hash_password, verify_dummy_password, verify_password};
+use crate::sessions;
+use crate::tokens::{self, TokenPurpose};
 use crate::AppState;
 
 #[derive(Deserialize)]
@@ -32,37 +36,243 @@ pub struct AuthResponse {
     pub refresh_token: String,
 }
 
+/// 登录结果：未开启二次验证时直接返回令牌，否则返回二次验证挑战
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(Box<AuthResponse>),
+    MfaRequired(MfaChallenge),
+}
+
 #[derive(Deserialize)]
 pub struct RefreshRequest {
     pub refresh_token: String,
 }
 
+#[derive(Serialize)]
+pub struct RegisterResponse {
+    pub user: User,
+    /// 为 true 时需先完成邮箱验证才能登录，此时不返回令牌
+    pub verification_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct EmailRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Serialize)]
+pub struct LogoutAllResponse {
+    pub revoked_sessions: usize,
+}
+
+/// 被禁用或尚未验证邮箱的用户不能登录或刷新令牌
+pub(crate) fn ensure_active(user: &User) -> Result<()> {
+    match user.status.as_str() {
+        USER_STATUS_ACTIVE => Ok(()),
+        USER_STATUS_PENDING => Err(Error::Forbidden("Email address has not been verified".to_string())),
+        _ => Err(Error::Forbidden("Account is disabled".to_string())),
+    }
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// 旧版本按注册时的原样保存邮箱，启动时统一改为小写 (查找时按小写匹配)，并确保邮箱唯一索引存在；
+/// 与已有小写邮箱冲突的账号保持原样并记录警告，需要人工合并
+pub async fn normalize_stored_emails(db: &Database) -> Result<u64> {
+    let users = db.collection::<User>("users");
+    users
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "email": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    let mut cursor = users
+        .find(doc! { "$expr": { "$ne": ["$email", { "$toLower": { "$trim": { "input": "$email" } } }] } }, None)
+        .await?;
+    let mut pending = Vec::new();
+    while cursor.advance().await? {
+        let user: User = cursor.deserialize_current()?;
+        pending.push(user);
+    }
+
+    let mut updated = 0;
+    for user in pending {
+        let id = user.id.unwrap_or_default();
+        let result = users
+            .update_one(doc! { "_id": &id }, doc! { "$set": { "email": normalize_email(&user.email) } }, None)
+            .await;
+        match result {
+            Ok(result) => updated += result.modified_count,
+            Err(e) if is_duplicate_key(&e) => {
+                tracing::warn!("User {} has an email that differs only in case from another account", id)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(updated)
+}
+
+async fn find_user_by_email(state: &AppState, email: &str) -> Result<Option<User>> {
+    Ok(state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "email": normalize_email(email) }, None)
+        .await?)
+}
+
+async fn set_password(state: &AppState, user_id: &str, new_password: &str) -> Result<()> {
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .update_one(
+            doc! { "_id": user_id },
+            doc! { "$set": {
+                "password_hash": hash_password(new_password)?,
+                "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// 发送带一次性令牌的链接；发送失败只记录日志，用户可以重新申请
+async fn send_token_email(state: &AppState, user: &User, purpose: TokenPurpose) -> Result<()> {
+    let user_id = user.id.as_deref().unwrap_or_default();
+    // 冷却期内静默跳过，响应与发送成功相同，不暴露邮箱是否存在
+    if !tokens::claim_mail_cooldown(&state.db.redis, purpose, user_id).await? {
+        return Ok(());
+    }
+    let token = tokens::issue(&state.db.redis, purpose, user_id).await?;
+    let hours = purpose.ttl_secs() / 3600;
+
+    let email = match purpose {
+        TokenPurpose::EmailVerification => Email {
+            to: user.email.clone(),
+            subject: "验证您的邮箱".to_string(),
+            body: format!(
+                "您好 {}，\n\n请在 {} 小时内打开以下链接完成邮箱验证：\n{}/verify-email?token={}\n",
+                user.username, hours, state.app_base_url, token
+            ),
+        },
+        TokenPurpose::MfaChallenge => {
+            return Err(Error::InternalServer("MFA challenges are not sent by email".to_string()))
+        }
+        TokenPurpose::PasswordReset => Email {
+            to: user.email.clone(),
+            subject: "重置您的密码".to_string(),
+            body: format!(
+                "您好 {}，\n\n请在 {} 小时内打开以下链接重置密码，如非本人操作请忽略：\n{}/reset-password?token={}\n",
+                user.username, hours, state.app_base_url, token
+            ),
+        },
+    };
+
+    if let Err(e) = state.mailer.send(&email).await {
+        tracing::warn!("Failed to send mail to user {}: {}", user_id, e);
+    }
+    Ok(())
+}
+
+/// 新用户的角色：邮箱在 ADMIN_EMAILS 中的用户注册为管理员，用于初始化第一个管理员
+fn initial_role(email: &str) -> &'static str {
+    let admins = std::env::var("ADMIN_EMAILS").unwrap_or_default();
+    if admins.split(',').any(|admin| admin.trim().eq_ignore_ascii_case(email)) {
+        ROLE_ADMIN
+    } else {
+        ROLE_USER
+    }
+}
+
+/// 为用户签发访问令牌和刷新令牌，并在 Redis 中登记刷新令牌；family 为 None 时开启新会话
+pub(crate) async fn issue_tokens(state: &AppState, user: &User, family: Option<&str>) -> Result<TokenResponse> {
+    let jwt_manager = &state.jwt;
+    let user_id = user
+        .id
+        .clone()
+        .ok_or_else(|| Error::InternalServer("User has no id".to_string()))?;
+
+    let access_token = jwt_manager.generate_access_token(&user_id, &user.username, &user.role)?;
+    let refresh_token = jwt_manager.generate_refresh_token(&user_id, family)?;
+    sessions::register(&state.db.redis, &user_id, &refresh_token).await?;
+
+    Ok(TokenResponse {
+        access_token,
+        refresh_token: refresh_token.token,
+        expires_in: jwt_manager.access_token_expiry().num_seconds(),
+    })
+}
+
 pub async fn register(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>> {
+) -> Result<Json<ApiResponse<RegisterResponse>>> {
     let collection = state.db.mongo.collection::<User>("users");
+
+    let username = req.username.trim().to_string();
+    if username.is_empty() || username.chars().count() > 32 {
+        return Err(Error::Validation("username must be 1-32 characters".to_string()));
+    }
+    let email = normalize_email(&req.email);
+    if !password::is_valid_email(&email) {
+        return Err(Error::Validation("Invalid email address".to_string()));
+    }
+    state.password_policy.validate(&req.password, &[&username, &email])?;
     
     // 检查邮箱是否已存在
-    if collection.find_one(doc! { "email": &req.email }, None).await?.is_some() {
+    if collection.find_one(doc! { "email": &email }, None).await?.is_some() {
         return Err(Error::Conflict("Email already exists".to_string()));
     }
     
     // 哈希密码
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|_| Error::InternalServer("Failed to hash password".to_string()))?
-        .to_string();
+    let password_hash = hash_password(&req.password)?;
     
     let now = chrono::Utc::now();
+    let role = initial_role(&email).to_string();
+    let verification_required = state.require_email_verification;
     let mut user = User {
         id: Some(mongodb::bson::oid::ObjectId::new().to_hex()),
-        username: req.username.clone(),
-        email: req.email,
+        username: username.clone(),
+        email,
         password_hash,
-        full_name: req.username,
+        full_name: username,
         avatar_url: None,
         phone: None,
         settings: common::UserSettings {
@@ -76,83 +286,264 @@ pub async fn register(
                 budget_alert: false,
             },
         },
-        status: "active".to_string(),
+        status: if verification_required { USER_STATUS_PENDING } else { USER_STATUS_ACTIVE }.to_string(),
+        role,
+        email_verified_at: None,
+        mfa_enabled: false,
         created_at: now,
         updated_at: now,
         last_login_at: None,
     };
     
     collection.insert_one(&user, None).await?;
+    common::seed_system_categories(&state.db.mongo, user.id.as_deref().unwrap_or_default()).await?;
     
-    // 生成令牌
-    let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "default_secret_key".to_string());
-    let jwt_manager = JwtManager::new(jwt_secret);
-    
-    let user_id = user.id.clone().unwrap();
-    let access_token = jwt_manager.generate_access_token(&user_id, &user.username, "user")?;
-    let refresh_token = jwt_manager.generate_refresh_token(&user_id, &user.username, "user")?;
+    // 需要验证邮箱时不签发令牌
+    let tokens = if verification_required {
+        send_token_email(&state, &user, TokenPurpose::EmailVerification).await?;
+        None
+    } else {
+        Some(issue_tokens(&state, &user, None).await?)
+    };
     
     user.password_hash = String::new(); // 不返回密码哈希
     
-    Ok(Json(ApiResponse::success(AuthResponse {
+    Ok(Json(ApiResponse::success(RegisterResponse {
         user,
-        access_token,
-        refresh_token,
+        verification_required,
+        access_token: tokens.as_ref().map(|t| t.access_token.clone()),
+        refresh_token: tokens.map(|t| t.refresh_token),
     })))
 }
 
 pub async fn login(
     State(state): State<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>> {
-    let collection = state.db.mongo.collection::<User>("users");
-    
-    let mut user = collection
-        .find_one(doc! { "email": &req.email }, None)
-        .await?
-        .ok_or_else(|| Error::Unauthorized("Invalid credentials".to_string()))?;
-    
+) -> Result<Json<ApiResponse<LoginResponse>>> {
+    // 同一邮箱连续输错密码后渐进式锁定，防止分布式暴力破解
+    let lockout = Lockout::default();
+    let lockout_key = format!("ratelimit:login_failures:{}", normalize_email(&req.email));
+    lockout.check(&state.db.redis, &lockout_key).await?;
+
     // 验证密码
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|_| Error::InternalServer("Invalid password hash".to_string()))?;
-    
-    Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .map_err(|_| Error::Unauthorized("Invalid credentials".to_string()))?;
-    
-    // 生成令牌
-    let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "default_secret_key".to_string());
-    let jwt_manager = JwtManager::new(jwt_secret);
-    
-    let user_id = user.id.clone().unwrap();
-    let access_token = jwt_manager.generate_access_token(&user_id, &user.username, "user")?;
-    let refresh_token = jwt_manager.generate_refresh_token(&user_id, &user.username, "user")?;
-    
+    let user = find_user_by_email(&state, &req.email).await?;
+    let valid = match &user {
+        Some(user) => verify_password(&req.password, &user.password_hash)?,
+        None => {
+            verify_dummy_password(&req.password);
+            false
+        }
+    };
+    let user = match user {
+        Some(user) if valid => user,
+        _ => {
+            if let Some(secs) = lockout.record_failure(&state.db.redis, &lockout_key).await? {
+                return Err(Error::TooManyRequests(secs));
+            }
+            return Err(Error::Unauthorized("Invalid credentials".to_string()));
+        }
+    };
+    lockout.reset(&state.db.redis, &lockout_key).await?;
+    ensure_active(&user)?;
+
+    // 开启二次验证的用户需再调用 /login/mfa 才能拿到令牌
+    if user.mfa_enabled {
+        let challenge = mfa::challenge(&state, &user).await?;
+        return Ok(Json(ApiResponse::success(LoginResponse::MfaRequired(challenge))));
+    }
+
+    Ok(Json(ApiResponse::success(LoginResponse::Authenticated(Box::new(
+        authenticate(&state, user).await?,
+    )))))
+}
+
+/// 完成登录：签发新会话的令牌，返回时去掉密码哈希
+pub(crate) async fn authenticate(state: &AppState, mut user: User) -> Result<AuthResponse> {
+    let tokens = issue_tokens(state, &user, None).await?;
     user.password_hash = String::new();
-    
-    Ok(Json(ApiResponse::success(AuthResponse {
+
+    Ok(AuthResponse {
         user,
-        access_token,
-        refresh_token,
-    })))
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+    })
 }
 
+/// 用刷新令牌换取新的令牌对；旧刷新令牌随即失效 (轮换)
 pub async fn refresh_token(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     Json(req): Json<RefreshRequest>,
-) -> Result<Json<ApiResponse<String>>> {
-    let jwt_secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "default_secret_key".to_string());
-    let jwt_manager = JwtManager::new(jwt_secret);
-    
-    let claims = jwt_manager.verify_token(&req.refresh_token)?;
-    let new_access_token = jwt_manager.generate_access_token(&claims.user_id, &claims.username, &claims.role)?;
-    
-    Ok(Json(ApiResponse::success(new_access_token)))
+) -> Result<Json<ApiResponse<TokenResponse>>> {
+    let claims = state.jwt.verify_refresh_token(&req.refresh_token)?;
+    sessions::consume(&state.db.redis, &claims).await?;
+
+    // 重新读取用户，保证新的访问令牌携带最新的用户信息
+    let user = state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::Unauthorized("User no longer exists".to_string()))?;
+    ensure_active(&user)?;
+
+    let tokens = issue_tokens(&state, &user, claims.family.as_deref()).await?;
+
+    Ok(Json(ApiResponse::success(tokens)))
+}
+
+/// 登出当前设备：作废该刷新令牌所属的令牌族
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    let claims = state.jwt.verify_refresh_token(&req.refresh_token)?;
+    if let Some(family) = claims.family.as_deref() {
+        sessions::revoke_family(&state.db.redis, &claims.user_id, family).await?;
+    }
+
+    Ok(Json(ApiResponse::success_with_message((), "登出成功".to_string())))
+}
+
+/// 登出所有设备：作废用户的全部刷新令牌
+pub async fn logout_all(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+) -> Result<Json<ApiResponse<LogoutAllResponse>>> {
+    let revoked_sessions = sessions::revoke_all(&state.db.redis, &claims.user_id).await?;
+
+    Ok(Json(ApiResponse::success(LogoutAllResponse { revoked_sessions })))
+}
+
+/// 修改密码：校验当前密码，成功后作废所有会话并为当前设备签发新令牌
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<Json<ApiResponse<TokenResponse>>> {
+    let user = state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &claims.user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))?;
+
+    if !verify_password(&req.current_password, &user.password_hash)? {
+        return Err(Error::Unauthorized("Current password is incorrect".to_string()));
+    }
+    if req.new_password == req.current_password {
+        return Err(Error::Validation("New password must differ from the current one".to_string()));
+    }
+    state
+        .password_policy
+        .validate(&req.new_password, &[&user.username, &user.email])?;
+
+    set_password(&state, &claims.user_id, &req.new_password).await?;
+    sessions::revoke_all(&state.db.redis, &claims.user_id).await?;
+    let tokens = issue_tokens(&state, &user, None).await?;
+
+    Ok(Json(ApiResponse::success(tokens)))
+}
+
+/// 申请重置密码；无论邮箱是否存在都返回成功，避免泄露注册信息
+pub async fn forgot_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EmailRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    if let Some(user) = find_user_by_email(&state, &req.email).await? {
+        if user.status == USER_STATUS_ACTIVE || user.status == USER_STATUS_PENDING {
+            send_token_email(&state, &user, TokenPurpose::PasswordReset).await?;
+        }
+    }
+
+    Ok(Json(ApiResponse::success_with_message(
+        (),
+        "如果该邮箱已注册，重置链接已发送".to_string(),
+    )))
+}
+
+/// 用一次性令牌重置密码，并作废该用户的所有会话
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    let user_id = tokens::consume(&state.db.redis, TokenPurpose::PasswordReset, &req.token)
+        .await?
+        .ok_or_else(|| Error::BadRequest("Reset link is invalid or has expired".to_string()))?;
+
+    let user = state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &user_id }, None)
+        .await?
+        .ok_or_else(|| Error::BadRequest("Reset link is invalid or has expired".to_string()))?;
+    state
+        .password_policy
+        .validate(&req.new_password, &[&user.username, &user.email])?;
+
+    set_password(&state, &user_id, &req.new_password).await?;
+    sessions::revoke_all(&state.db.redis, &user_id).await?;
+
+    Ok(Json(ApiResponse::success_with_message((), "密码已重置，请重新登录".to_string())))
+}
+
+/// 验证邮箱，待验证的用户随即激活
+pub async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    let user_id = tokens::consume(&state.db.redis, TokenPurpose::EmailVerification, &req.token)
+        .await?
+        .ok_or_else(|| Error::BadRequest("Verification link is invalid or has expired".to_string()))?;
+
+    let now = bson::to_bson(&chrono::Utc::now()).unwrap();
+    let collection = state.db.mongo.collection::<User>("users");
+    collection
+        .update_one(
+            doc! { "_id": &user_id },
+            doc! { "$set": { "email_verified_at": &now, "updated_at": &now } },
+            None,
+        )
+        .await?;
+    // 只激活待验证用户，不改变被禁用用户的状态
+    collection
+        .update_one(
+            doc! { "_id": &user_id, "status": USER_STATUS_PENDING },
+            doc! { "$set": { "status": USER_STATUS_ACTIVE } },
+            None,
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success_with_message((), "邮箱验证成功".to_string())))
+}
+
+/// 重新发送验证邮件；同样不透露邮箱是否存在
+pub async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<EmailRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    if let Some(user) = find_user_by_email(&state, &req.email).await? {
+        if user.email_verified_at.is_none() && user.status == USER_STATUS_PENDING {
+            send_token_email(&state, &user, TokenPurpose::EmailVerification).await?;
+        }
+    }
+
+    Ok(Json(ApiResponse::success_with_message(
+        (),
+        "如果该邮箱待验证，验证邮件已发送".to_string(),
+    )))
+}
+
+/// 公开的验签公钥 (JWKS)，供其他服务或网关校验令牌
+pub async fn jwks(State(state): State<Arc<AppState>>) -> Json<JwkSet> {
+    Json(state.jwt.keys().jwks())
 }
 
 pub async fn get_profile(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::ProfileRead>,
 ) -> Result<Json<ApiResponse<User>>> {
     let collection = state.db.mongo.collection::<User>("users");
     let mut user = collection
@@ -167,7 +558,7 @@ pub async fn get_profile(
 
 pub async fn update_profile(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
     Json(mut user): Json<User>,
 ) -> Result<Json<ApiResponse<User>>> {
     let collection = state.db.mongo.collection::<User>("users");
@@ -0,0 +1,106 @@
+// 邮件发送 - 通过 Mailer trait 接入具体的发送通道，本地开发使用日志或文件
+use async_trait::async_trait;
+use common::{Error, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<()>;
+}
+
+/// 把邮件内容写入日志
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        tracing::info!("Mail to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// 每封邮件写为目录下的一个 .eml 文件
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>, from: String) -> Self {
+        Self { dir: dir.into(), from }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| Error::InternalServer(format!("Failed to create mail directory: {}", e)))?;
+
+        let now = chrono::Utc::now();
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            now.format("%Y%m%dT%H%M%S%.6f"),
+            email.to.replace(['/', '\\'], "_")
+        ));
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nDate: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            now.to_rfc2822(),
+            email.subject,
+            email.body
+        );
+
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| Error::InternalServer(format!("Failed to write mail: {}", e)))
+    }
+}
+
+/// 按 MAILER 选择发送通道：log (默认) 或 file (写入 MAILER_DIR)
+pub fn from_env() -> Arc<dyn Mailer> {
+    match std::env::var("MAILER").unwrap_or_default().as_str() {
+        "file" => {
+            let dir = std::env::var("MAILER_DIR").unwrap_or_else(|_| "./mail".to_string());
+            let from = std::env::var("MAILER_FROM").unwrap_or_else(|_| "noreply@abook.local".to_string());
+            Arc::new(FileMailer::new(dir, from))
+        }
+        "" | "log" => Arc::new(LogMailer),
+        other => panic!("Unsupported MAILER: {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_mailer_writes_eml() {
+        let dir = std::env::temp_dir().join(format!("abook-mail-{}", mongodb::bson::oid::ObjectId::new().to_hex()));
+        let mailer = FileMailer::new(&dir, "noreply@test".to_string());
+        mailer
+            .send(&Email {
+                to: "user@example.com".to_string(),
+                subject: "Hello".to_string(),
+                body: "token=abc".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let content = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+        assert!(content.contains("To: user@example.com"));
+        assert!(content.contains("token=abc"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
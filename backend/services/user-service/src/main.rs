@@ -1,30 +1,124 @@
+mod admin;
+mod api_tokens;
 mod handlers;
+mod mailer;
+mod mfa;
+mod password;
+mod privacy;
+mod sessions;
+mod settings;
+mod tokens;
+mod totp;
 
 use axum::{
     middleware,
-    routing::{get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use common::{middleware::auth_middleware, DatabaseConnection};
+use common::{
+    middleware::{auth_middleware, AuthState, Lockout, RateLimit, RateLimitLayer},
+    DatabaseConnection, JwtManager, KeyRing,
+};
+use mailer::Mailer;
+use password::PasswordPolicy;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub struct AppState {
     pub db: DatabaseConnection,
+    pub jwt: Arc<JwtManager>,
+    pub mailer: Arc<dyn Mailer>,
+    pub password_policy: PasswordPolicy,
+    /// 新用户需验证邮箱后才能登录
+    pub require_email_verification: bool,
+    /// 邮件中链接指向的前端地址
+    pub app_base_url: String,
+    /// 认证器 App 中显示的发行方名称
+    pub mfa_issuer: String,
 }
 
-pub fn create_router(db: DatabaseConnection) -> Router {
-    let state = Arc::new(AppState { db: db.clone() });
+pub fn create_router(db: DatabaseConnection, jwt: Arc<JwtManager>) -> Router {
+    let state = Arc::new(AppState {
+        db: db.clone(),
+        jwt: jwt.clone(),
+        mailer: mailer::from_env(),
+        password_policy: PasswordPolicy::from_env(),
+        require_email_verification: std::env::var("EMAIL_VERIFICATION_REQUIRED")
+            .map(|v| v != "false" && v != "0")
+            .unwrap_or(true),
+        app_base_url: std::env::var("APP_BASE_URL")
+            .unwrap_or_else(|_| "http://localhost:5173".to_string())
+            .trim_end_matches('/')
+            .to_string(),
+        mfa_issuer: std::env::var("MFA_ISSUER").unwrap_or_else(|_| "Brollo".to_string()),
+    });
     
     let protected_routes = Router::new()
         .route("/profile", get(handlers::get_profile))
         .route("/profile", put(handlers::update_profile))
-        .layer(middleware::from_fn_with_state(db.clone(), auth_middleware));
+        .route("/profile", delete(privacy::erase_account))
+        .route("/profile/export", get(privacy::export_data))
+        .route("/settings", get(settings::get_settings))
+        .route("/settings", patch(settings::update_settings))
+        .route("/logout-all", post(handlers::logout_all))
+        .route("/password/change", post(handlers::change_password))
+        .route("/mfa", get(mfa::get_status))
+        .route("/mfa/totp/enroll", post(mfa::enroll))
+        .route("/mfa/totp/confirm", post(mfa::confirm))
+        .route("/mfa/totp/disable", post(mfa::disable))
+        .route("/mfa/recovery-codes", post(mfa::regenerate_recovery_codes))
+        .route("/api-tokens", get(api_tokens::list_api_tokens))
+        .route("/api-tokens", post(api_tokens::create_api_token))
+        .route("/api-tokens/:id", delete(api_tokens::delete_api_token))
+        .route("/admin/users", get(admin::list_users))
+        .route("/admin/users/:id", get(admin::get_user))
+        .route("/admin/users/:id", patch(admin::update_user))
+        .layer(RateLimitLayer::per_user(&db.redis, RateLimit::global_user()))
+        .layer(middleware::from_fn_with_state(AuthState::new(jwt.clone(), db.clone()), auth_middleware));
+
+    // 登录、注册单独限流，反复超限时锁定时长逐次翻倍
+    let login_limit = RateLimit::per_minute("login", 5)
+        .limit_from_env("RATE_LIMIT_LOGIN_PER_MINUTE")
+        .with_lockout(Lockout::default());
+    let mfa_limit = RateLimit::per_minute("login_mfa", 5)
+        .limit_from_env("RATE_LIMIT_LOGIN_PER_MINUTE")
+        .with_lockout(Lockout::default());
+    let register_limit = RateLimit::per_minute("register", 3)
+        .limit_from_env("RATE_LIMIT_REGISTER_PER_MINUTE")
+        .with_lockout(Lockout::default());
+    // 发送重置/验证邮件与提交一次性令牌的接口同样单独限流，防止轰炸邮箱和暴力猜测令牌
+    let mail_limit = RateLimit::per_minute("account_mail", 3)
+        .limit_from_env("RATE_LIMIT_ACCOUNT_MAIL_PER_MINUTE")
+        .with_lockout(Lockout::default());
+    let token_limit = RateLimit::per_minute("account_token", 5)
+        .limit_from_env("RATE_LIMIT_ACCOUNT_TOKEN_PER_MINUTE")
+        .with_lockout(Lockout::default());
     
     Router::new()
-        .route("/register", post(handlers::register))
-        .route("/login", post(handlers::login))
+        .route("/register", post(handlers::register).layer(RateLimitLayer::per_ip(&db.redis, register_limit)))
+        .route("/login", post(handlers::login).layer(RateLimitLayer::per_ip(&db.redis, login_limit)))
+        .route("/login/mfa", post(mfa::login_mfa).layer(RateLimitLayer::per_ip(&db.redis, mfa_limit)))
         .route("/refresh", post(handlers::refresh_token))
+        .route("/logout", post(handlers::logout))
+        .route(
+            "/password/forgot",
+            post(handlers::forgot_password).layer(RateLimitLayer::per_ip(&db.redis, mail_limit.clone())),
+        )
+        .route(
+            "/password/reset",
+            post(handlers::reset_password).layer(RateLimitLayer::per_ip(&db.redis, token_limit.clone())),
+        )
+        .route(
+            "/email/verify",
+            post(handlers::verify_email).layer(RateLimitLayer::per_ip(&db.redis, token_limit)),
+        )
+        .route(
+            "/email/resend",
+            post(handlers::resend_verification).layer(RateLimitLayer::per_ip(&db.redis, mail_limit)),
+        )
+        .route("/.well-known/jwks.json", get(handlers::jwks))
         .merge(protected_routes)
+        .layer(RateLimitLayer::per_ip(&db.redis, RateLimit::global_ip()))
         .with_state(state)
 }
 
@@ -32,6 +126,10 @@ pub fn create_router(db: DatabaseConnection) -> Router {
 async fn main() {
     tracing_subscriber::fmt::init();
     
+    let keys = KeyRing::from_env().expect("Failed to load JWT keys");
+    assert!(keys.can_sign(), "user-service requires JWT_SIGNING_KEY or JWT_SECRET to issue tokens");
+    let jwt = Arc::new(JwtManager::new(keys));
+
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
     let redis_uri = std::env::var("REDIS_URI").unwrap_or_else(|_| "redis://localhost:6379".to_string());
     let pg_uri = std::env::var("POSTGRES_URI").ok();
@@ -39,8 +137,14 @@ async fn main() {
     let db = DatabaseConnection::new(&mongo_uri, &redis_uri, pg_uri.as_deref(), "abook")
         .await
         .expect("Failed to connect to database");
+
+    match handlers::normalize_stored_emails(&db.mongo).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Lowercased {} stored email addresses", count),
+        Err(e) => tracing::warn!("Failed to normalize stored email addresses: {}", e),
+    }
     
-    let app = create_router(db);
+    let app = create_router(db, jwt);
     
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
         .await
@@ -48,7 +152,7 @@ async fn main() {
     
     tracing::info!("User service listening on port 3000");
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Failed to start server");
 }
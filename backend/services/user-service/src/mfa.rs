@@ -0,0 +1,298 @@
+// 二次验证 - TOTP 绑定与解绑、恢复码管理，以及登录的第二步
+use axum::{extract::State, Json};
+use common::middleware::{scope, Authorized, Lockout};
+use common::{ApiResponse, Error, Result, User, UserMfa};
+use mongodb::{
+    bson::{self, doc},
+    options::ReplaceOptions,
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::handlers::{authenticate, ensure_active, AuthResponse};
+use crate::password::verify_password;
+use crate::tokens::{self, TokenPurpose};
+use crate::{totp, AppState};
+
+#[derive(Deserialize)]
+pub struct CodeRequest {
+    /// TOTP 口令或恢复码
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableMfaRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Serialize)]
+pub struct MfaEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    pub recovery_codes_remaining: usize,
+}
+
+fn collection(state: &AppState) -> Collection<UserMfa> {
+    state.db.mongo.collection::<UserMfa>("user_mfa")
+}
+
+async fn find_user(state: &AppState, user_id: &str) -> Result<User> {
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))
+}
+
+pub(crate) async fn find_enabled(state: &AppState, user_id: &str) -> Result<UserMfa> {
+    collection(state)
+        .find_one(doc! { "_id": user_id, "enabled": true }, None)
+        .await?
+        .ok_or_else(|| Error::BadRequest("Two-factor authentication is not enabled".to_string()))
+}
+
+async fn set_mfa_enabled(state: &AppState, user_id: &str, enabled: bool) -> Result<()> {
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .update_one(
+            doc! { "_id": user_id },
+            doc! { "$set": {
+                "mfa_enabled": enabled,
+                "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// 校验第二因素：TOTP 口令只接受比上次更新的时间步，恢复码用后即删除
+pub(crate) async fn verify_factor(state: &AppState, mfa: &UserMfa, code: &str) -> Result<bool> {
+    let now = chrono::Utc::now().timestamp() as u64;
+    if let Some(step) = totp::verify(&mfa.secret, code, now) {
+        let result = collection(state)
+            .update_one(
+                doc! { "_id": &mfa.user_id, "last_used_step": { "$lt": step as i64 } },
+                doc! { "$set": { "last_used_step": step as i64 } },
+                None,
+            )
+            .await?;
+        return Ok(result.modified_count == 1);
+    }
+
+    let hash = totp::hash_recovery_code(code);
+    let result = collection(state)
+        .update_one(
+            doc! { "_id": &mfa.user_id, "enabled": true, "recovery_codes": &hash },
+            doc! { "$pull": { "recovery_codes": &hash } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count == 1)
+}
+
+/// 生成新的恢复码并替换已保存的摘要，返回明文
+async fn replace_recovery_codes(state: &AppState, user_id: &str, enable: bool) -> Result<Vec<String>> {
+    let codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|code| totp::hash_recovery_code(code)).collect();
+
+    let mut set = doc! { "recovery_codes": hashes };
+    if enable {
+        set.insert("enabled", true);
+        set.insert("enabled_at", bson::to_bson(&chrono::Utc::now()).unwrap());
+    }
+    collection(state)
+        .update_one(doc! { "_id": user_id }, doc! { "$set": set }, None)
+        .await?;
+    Ok(codes)
+}
+
+/// 为已通过密码校验的用户创建二次验证挑战
+pub async fn challenge(state: &AppState, user: &User) -> Result<MfaChallenge> {
+    let user_id = user.id.as_deref().unwrap_or_default();
+    let mfa_token = tokens::issue(&state.db.redis, TokenPurpose::MfaChallenge, user_id).await?;
+
+    Ok(MfaChallenge {
+        mfa_required: true,
+        mfa_token,
+        expires_in: TokenPurpose::MfaChallenge.ttl_secs(),
+    })
+}
+
+pub async fn get_status(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileRead>,
+) -> Result<Json<ApiResponse<MfaStatus>>> {
+    let mfa = collection(&state)
+        .find_one(doc! { "_id": &claims.user_id, "enabled": true }, None)
+        .await?;
+
+    Ok(Json(ApiResponse::success(MfaStatus {
+        enabled: mfa.is_some(),
+        recovery_codes_remaining: mfa.map(|mfa| mfa.recovery_codes.len()).unwrap_or(0),
+    })))
+}
+
+/// 开始绑定：生成新密钥，确认前不生效；重复调用会替换未确认的密钥
+pub async fn enroll(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+) -> Result<Json<ApiResponse<MfaEnrollment>>> {
+    let user = find_user(&state, &claims.user_id).await?;
+    if user.mfa_enabled {
+        return Err(Error::Conflict("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    let mfa = UserMfa {
+        user_id: claims.user_id.clone(),
+        secret: secret.clone(),
+        enabled: false,
+        recovery_codes: Vec::new(),
+        last_used_step: 0,
+        created_at: chrono::Utc::now(),
+        enabled_at: None,
+    };
+    collection(&state)
+        .replace_one(
+            doc! { "_id": &claims.user_id },
+            &mfa,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(MfaEnrollment {
+        otpauth_uri: totp::provisioning_uri(&state.mfa_issuer, &user.email, &secret),
+        secret,
+    })))
+}
+
+/// 用认证器中的口令确认绑定，返回恢复码 (只显示这一次)
+pub async fn confirm(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<CodeRequest>,
+) -> Result<Json<ApiResponse<RecoveryCodesResponse>>> {
+    let mfa = collection(&state)
+        .find_one(doc! { "_id": &claims.user_id, "enabled": false }, None)
+        .await?
+        .ok_or_else(|| Error::BadRequest("No pending two-factor enrollment".to_string()))?;
+
+    if !verify_factor(&state, &mfa, &req.code).await? {
+        return Err(Error::Validation("Invalid verification code".to_string()));
+    }
+
+    let recovery_codes = replace_recovery_codes(&state, &claims.user_id, true).await?;
+    set_mfa_enabled(&state, &claims.user_id, true).await?;
+
+    Ok(Json(ApiResponse::success_with_message(
+        RecoveryCodesResponse { recovery_codes },
+        "二次验证已开启，请妥善保存恢复码".to_string(),
+    )))
+}
+
+/// 关闭二次验证，需同时提供密码和口令 (或恢复码)
+pub async fn disable(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<DisableMfaRequest>,
+) -> Result<Json<ApiResponse<()>>> {
+    let user = find_user(&state, &claims.user_id).await?;
+    if !verify_password(&req.password, &user.password_hash)? {
+        return Err(Error::Unauthorized("Password is incorrect".to_string()));
+    }
+    let mfa = find_enabled(&state, &claims.user_id).await?;
+    if !verify_factor(&state, &mfa, &req.code).await? {
+        return Err(Error::Unauthorized("Invalid verification code".to_string()));
+    }
+
+    collection(&state)
+        .delete_one(doc! { "_id": &claims.user_id }, None)
+        .await?;
+    set_mfa_enabled(&state, &claims.user_id, false).await?;
+
+    Ok(Json(ApiResponse::success_with_message((), "二次验证已关闭".to_string())))
+}
+
+/// 重新生成恢复码，旧恢复码全部作废
+pub async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<CodeRequest>,
+) -> Result<Json<ApiResponse<RecoveryCodesResponse>>> {
+    let mfa = find_enabled(&state, &claims.user_id).await?;
+    if !verify_factor(&state, &mfa, &req.code).await? {
+        return Err(Error::Unauthorized("Invalid verification code".to_string()));
+    }
+
+    let recovery_codes = replace_recovery_codes(&state, &claims.user_id, false).await?;
+
+    Ok(Json(ApiResponse::success(RecoveryCodesResponse { recovery_codes })))
+}
+
+/// 登录第二步：用挑战令牌和口令 (或恢复码) 换取令牌对
+pub async fn login_mfa(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MfaLoginRequest>,
+) -> Result<Json<ApiResponse<AuthResponse>>> {
+    let invalid = || Error::Unauthorized("MFA challenge is invalid or has expired".to_string());
+    let user_id = tokens::peek(&state.db.redis, TokenPurpose::MfaChallenge, &req.mfa_token)
+        .await?
+        .ok_or_else(invalid)?;
+
+    // 挑战令牌在有效期内可重试，连续输错按用户渐进式锁定
+    let lockout = Lockout::default();
+    let lockout_key = format!("ratelimit:mfa_failures:{}", user_id);
+    lockout.check(&state.db.redis, &lockout_key).await?;
+
+    let mfa = find_enabled(&state, &user_id).await.map_err(|_| invalid())?;
+    if !verify_factor(&state, &mfa, &req.code).await? {
+        if let Some(secs) = lockout.record_failure(&state.db.redis, &lockout_key).await? {
+            return Err(Error::TooManyRequests(secs));
+        }
+        return Err(Error::Unauthorized("Invalid verification code".to_string()));
+    }
+    lockout.reset(&state.db.redis, &lockout_key).await?;
+
+    // 并发请求中只有一个能消费挑战令牌
+    if tokens::consume(&state.db.redis, TokenPurpose::MfaChallenge, &req.mfa_token)
+        .await?
+        .is_none()
+    {
+        return Err(invalid());
+    }
+
+    let user = find_user(&state, &user_id).await?;
+    ensure_active(&user)?;
+
+    Ok(Json(ApiResponse::success(authenticate(&state, user).await?)))
+}
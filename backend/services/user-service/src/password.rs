@@ -0,0 +1,180 @@
+// 密码策略与哈希 - 策略在启动时从环境变量读取
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use common::{Error, Result};
+use rand::rngs::OsRng;
+use std::sync::OnceLock;
+
+/// 密码长度上限，避免超长输入拖慢 Argon2
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_letter: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_letter: true,
+            require_uppercase: false,
+            require_digit: true,
+            require_symbol: false,
+        }
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+        Err(_) => default,
+    }
+}
+
+impl PasswordPolicy {
+    /// 从 PASSWORD_MIN_LENGTH、PASSWORD_REQUIRE_{LETTER,UPPERCASE,DIGIT,SYMBOL} 读取，缺省为默认策略
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            min_length: std::env::var("PASSWORD_MIN_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_length)
+                .clamp(1, MAX_PASSWORD_LENGTH),
+            require_letter: env_flag("PASSWORD_REQUIRE_LETTER", default.require_letter),
+            require_uppercase: env_flag("PASSWORD_REQUIRE_UPPERCASE", default.require_uppercase),
+            require_digit: env_flag("PASSWORD_REQUIRE_DIGIT", default.require_digit),
+            require_symbol: env_flag("PASSWORD_REQUIRE_SYMBOL", default.require_symbol),
+        }
+    }
+
+    /// 校验密码，一次返回所有不满足的要求；identities 为用户名、邮箱等不允许用作密码的值
+    pub fn validate(&self, password: &str, identities: &[&str]) -> Result<()> {
+        let length = password.chars().count();
+        let mut problems = Vec::new();
+
+        if length < self.min_length {
+            problems.push(format!("at least {} characters", self.min_length));
+        }
+        if length > MAX_PASSWORD_LENGTH {
+            problems.push(format!("at most {} characters", MAX_PASSWORD_LENGTH));
+        }
+        if self.require_letter && !password.chars().any(char::is_alphabetic) {
+            problems.push("a letter".to_string());
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            problems.push("an uppercase letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            problems.push("a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            problems.push("a symbol".to_string());
+        }
+
+        if !problems.is_empty() {
+            return Err(Error::Validation(format!("Password must contain {}", problems.join(", "))));
+        }
+        if identities
+            .iter()
+            .any(|identity| !identity.is_empty() && password.eq_ignore_ascii_case(identity))
+        {
+            return Err(Error::Validation("Password must not be your username or email".to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| Error::InternalServer("Failed to hash password".to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+    let parsed_hash = PasswordHash::new(password_hash)
+        .map_err(|_| Error::InternalServer("Invalid password hash".to_string()))?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+/// 用户不存在时对一个固定哈希做同样的校验，使登录的响应时间不暴露邮箱是否已注册
+pub fn verify_dummy_password(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash = DUMMY_HASH.get_or_init(|| hash_password("dummy-password-for-timing").unwrap_or_default());
+    let _ = verify_password(password, hash);
+}
+
+/// 基本的邮箱格式校验：local@domain.tld，不含空白
+pub fn is_valid_email(email: &str) -> bool {
+    if email.len() > 254 || email.chars().any(char::is_whitespace) {
+        return false;
+    }
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && !local.contains('@')
+        && labels.len() >= 2
+        && labels
+            .iter()
+            .all(|label| !label.is_empty() && !label.starts_with('-') && !label.ends_with('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.validate("correct horse 1", &[]).is_ok());
+        assert!(policy.validate("short1", &[]).is_err());
+        assert!(policy.validate("no digits here", &[]).is_err());
+        assert!(policy.validate("12345678", &[]).is_err());
+        assert!(policy.validate("alice2024", &["Alice2024", "alice@example.com"]).is_err());
+    }
+
+    #[test]
+    fn test_strict_policy_lists_all_problems() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            require_uppercase: true,
+            require_symbol: true,
+            ..Default::default()
+        };
+        let Err(Error::Validation(message)) = policy.validate("abc123", &[]) else {
+            panic!("expected validation error");
+        };
+        assert!(message.contains("12 characters"));
+        assert!(message.contains("uppercase"));
+        assert!(message.contains("symbol"));
+        assert!(policy.validate("Abcdefgh123!", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("s3cret-pass").unwrap();
+        assert!(verify_password("s3cret-pass", &hash).unwrap());
+        assert!(!verify_password("wrong-pass1", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_email_format() {
+        assert!(is_valid_email("user.name+tag@example.co.uk"));
+        assert!(!is_valid_email("user@localhost"));
+        assert!(!is_valid_email("user@@example.com"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("user @example.com"));
+        assert!(!is_valid_email("user@example..com"));
+    }
+}
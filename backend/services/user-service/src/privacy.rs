@@ -0,0 +1,189 @@
+// 个人数据 - 导出用户的全部数据，以及注销时彻底删除 (GDPR 数据可携带权与删除权)
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use common::middleware::{revoke_issued_tokens, scope, Authorized};
+use common::{
+    delete_balance_snapshots, load_balance_snapshots, Account, ApiResponse, ApiToken, BalanceSnapshot, Budget,
+    Category, CategoryRule, Error, ImportJob, Reconciliation, RecurringTransaction, Result, Transaction, User, UserMfa,
+};
+use mongodb::{
+    bson::{doc, Document},
+    Database,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::password::verify_password;
+use crate::{mfa, sessions, AppState};
+
+/// 以 user_id 字段关联用户的集合，注销时按此顺序删除；users 文档最后删除，中途失败时用户仍可登录重试
+const USER_COLLECTIONS: [&str; 10] = [
+    "transactions",
+    "recurring_transactions",
+    "budgets",
+    "categories",
+    "category_rules",
+    "category_models",
+    "import_jobs",
+    "reconciliations",
+    "api_tokens",
+    "accounts",
+];
+
+#[derive(Serialize)]
+pub struct UserDataExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+    pub budgets: Vec<Budget>,
+    pub categories: Vec<Category>,
+    pub category_rules: Vec<CategoryRule>,
+    pub recurring_transactions: Vec<RecurringTransaction>,
+    pub import_jobs: Vec<ImportJob>,
+    pub reconciliations: Vec<Reconciliation>,
+    pub api_tokens: Vec<ApiToken>,
+    pub balance_history: Vec<BalanceSnapshot>,
+}
+
+#[derive(Deserialize)]
+pub struct EraseAccountRequest {
+    pub password: String,
+    /// 开启二次验证时必填：TOTP 口令或恢复码
+    pub code: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ErasureResponse {
+    pub deleted_documents: u64,
+    pub deleted_balance_snapshots: u64,
+    pub revoked_sessions: usize,
+}
+
+async fn find_user(state: &AppState, user_id: &str) -> Result<User> {
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))
+}
+
+async fn find_all<T>(db: &Database, collection: &str, user_id: &str) -> Result<Vec<T>>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    let mut cursor = db
+        .collection::<T>(collection)
+        .find(doc! { "user_id": user_id }, None)
+        .await?;
+
+    let mut items = Vec::new();
+    while cursor.advance().await? {
+        items.push(cursor.deserialize_current()?);
+    }
+    Ok(items)
+}
+
+/// 导出当前用户的全部数据；不含密码哈希、二次验证密钥等凭据
+pub async fn export_data(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+) -> Result<Json<ApiResponse<UserDataExport>>> {
+    let db = &state.db.mongo;
+    let user_id = &claims.user_id;
+
+    let mut user = find_user(&state, user_id).await?;
+    user.password_hash = String::new();
+
+    let accounts: Vec<Account> = find_all(db, "accounts", user_id).await?;
+    let balance_history = match &state.db.pg {
+        Some(pool) => {
+            let account_ids: Vec<String> = accounts.iter().filter_map(|a| a.id.clone()).collect();
+            load_balance_snapshots(pool, &account_ids).await?
+        }
+        None => Vec::new(),
+    };
+
+    Ok(Json(ApiResponse::success(UserDataExport {
+        exported_at: Utc::now(),
+        user,
+        transactions: find_all(db, "transactions", user_id).await?,
+        budgets: find_all(db, "budgets", user_id).await?,
+        categories: find_all(db, "categories", user_id).await?,
+        category_rules: find_all(db, "category_rules", user_id).await?,
+        recurring_transactions: find_all(db, "recurring_transactions", user_id).await?,
+        import_jobs: find_all(db, "import_jobs", user_id).await?,
+        reconciliations: find_all(db, "reconciliations", user_id).await?,
+        api_tokens: find_all(db, "api_tokens", user_id).await?,
+        accounts,
+        balance_history,
+    })))
+}
+
+/// 注销账号并删除全部数据，需提供密码，开启二次验证时还需口令；操作不可恢复
+pub async fn erase_account(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<EraseAccountRequest>,
+) -> Result<Json<ApiResponse<ErasureResponse>>> {
+    let user_id = &claims.user_id;
+    let user = find_user(&state, user_id).await?;
+    if !verify_password(&req.password, &user.password_hash)? {
+        return Err(Error::Unauthorized("Password is incorrect".to_string()));
+    }
+    if user.mfa_enabled {
+        let code = req
+            .code
+            .as_deref()
+            .ok_or_else(|| Error::Validation("Verification code is required".to_string()))?;
+        let factor = mfa::find_enabled(&state, user_id).await?;
+        if !mfa::verify_factor(&state, &factor, code).await? {
+            return Err(Error::Unauthorized("Invalid verification code".to_string()));
+        }
+    }
+
+    // 先吊销会话和已签发的访问令牌，避免删除期间及之后继续写入
+    let revoked_sessions = sessions::revoke_all(&state.db.redis, user_id).await?;
+    revoke_issued_tokens(&state.db.redis, user_id, state.jwt.access_token_expiry()).await?;
+
+    let db = &state.db.mongo;
+    let accounts: Vec<Account> = find_all(db, "accounts", user_id).await?;
+    let account_ids: Vec<String> = accounts.into_iter().filter_map(|a| a.id).collect();
+    let deleted_balance_snapshots = match &state.db.pg {
+        Some(pool) => delete_balance_snapshots(pool, &account_ids).await?,
+        None => 0,
+    };
+
+    let mut deleted_documents = 0;
+    for collection in USER_COLLECTIONS {
+        deleted_documents += db
+            .collection::<Document>(collection)
+            .delete_many(doc! { "user_id": user_id }, None)
+            .await?
+            .deleted_count;
+    }
+    deleted_documents += db
+        .collection::<UserMfa>("user_mfa")
+        .delete_one(doc! { "_id": user_id }, None)
+        .await?
+        .deleted_count;
+
+    deleted_documents += db
+        .collection::<User>("users")
+        .delete_one(doc! { "_id": user_id }, None)
+        .await?
+        .deleted_count;
+
+    tracing::info!("Erased user {} ({} documents)", user_id, deleted_documents);
+
+    Ok(Json(ApiResponse::success_with_message(
+        ErasureResponse {
+            deleted_documents,
+            deleted_balance_snapshots,
+            revoked_sessions,
+        },
+        "账号及全部数据已删除".to_string(),
+    )))
+}
@@ -0,0 +1,109 @@
+// 刷新令牌会话 - 在 Redis 中跟踪每个刷新令牌 (jti) 及其令牌族，实现轮换、重放检测与登出
+use common::{Claims, Error, RefreshToken, Result};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use tracing::warn;
+
+const STATE_ACTIVE: &str = "active";
+const STATE_ROTATED: &str = "rotated";
+
+fn token_key(jti: &str) -> String {
+    format!("auth:refresh:{}", jti)
+}
+
+fn family_key(family: &str) -> String {
+    format!("auth:family:{}", family)
+}
+
+fn user_families_key(user_id: &str) -> String {
+    format!("auth:user_families:{}", user_id)
+}
+
+fn ttl_secs(expires_at: i64) -> u64 {
+    (expires_at - chrono::Utc::now().timestamp()).max(1) as u64
+}
+
+/// 登记新签发的刷新令牌，并延长其令牌族的有效期
+pub async fn register(redis: &ConnectionManager, user_id: &str, token: &RefreshToken) -> Result<()> {
+    let ttl = ttl_secs(token.expires_at);
+    let mut conn = redis.clone();
+    redis::pipe()
+        .atomic()
+        .set_ex(token_key(&token.jti), STATE_ACTIVE, ttl)
+        .ignore()
+        .set_ex(family_key(&token.family), user_id, ttl)
+        .ignore()
+        .sadd(user_families_key(user_id), &token.family)
+        .ignore()
+        .expire(user_families_key(user_id), ttl as i64)
+        .ignore()
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// 消费一个刷新令牌：每个 jti 只能使用一次。
+/// 已轮换过的令牌再次出现说明令牌可能被窃取，整个令牌族随即作废
+pub async fn consume(redis: &ConnectionManager, claims: &Claims) -> Result<()> {
+    let (Some(jti), Some(family)) = (claims.jti.as_deref(), claims.family.as_deref()) else {
+        return Err(Error::Unauthorized("Not a refresh token".to_string()));
+    };
+
+    let mut conn = redis.clone();
+    let owner: Option<String> = conn.get(family_key(family)).await?;
+    if owner.as_deref() != Some(claims.user_id.as_str()) {
+        return Err(Error::Unauthorized("Session has been revoked".to_string()));
+    }
+
+    // 原子地标记为已轮换并取回旧状态，保证并发请求中只有一个能成功
+    let previous: Option<String> = redis::cmd("SET")
+        .arg(token_key(jti))
+        .arg(STATE_ROTATED)
+        .arg("XX")
+        .arg("KEEPTTL")
+        .arg("GET")
+        .query_async(&mut conn)
+        .await?;
+
+    match previous.as_deref() {
+        Some(STATE_ACTIVE) => Ok(()),
+        Some(_) => {
+            warn!(
+                "Refresh token reuse detected for user {}, revoking family {}",
+                claims.user_id, family
+            );
+            revoke_family(redis, &claims.user_id, family).await?;
+            Err(Error::Unauthorized("Refresh token has already been used".to_string()))
+        }
+        None => Err(Error::Unauthorized("Unknown refresh token".to_string())),
+    }
+}
+
+/// 作废一个令牌族 (单设备登出)
+pub async fn revoke_family(redis: &ConnectionManager, user_id: &str, family: &str) -> Result<()> {
+    let mut conn = redis.clone();
+    redis::pipe()
+        .atomic()
+        .del(family_key(family))
+        .ignore()
+        .srem(user_families_key(user_id), family)
+        .ignore()
+        .query_async::<_, ()>(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// 作废用户的所有令牌族 (所有设备登出)，返回作废的会话数
+pub async fn revoke_all(redis: &ConnectionManager, user_id: &str) -> Result<usize> {
+    let mut conn = redis.clone();
+    let families: Vec<String> = conn.smembers(user_families_key(user_id)).await?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for family in &families {
+        pipe.del(family_key(family)).ignore();
+    }
+    pipe.del(user_families_key(user_id)).ignore();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+
+    Ok(families.len())
+}
@@ -0,0 +1,148 @@
+// 用户设置 - 默认币种、时区、语言、主题与通知偏好
+use axum::{extract::State, Json};
+use common::middleware::{scope, Authorized};
+use common::{
+    is_currency_code, is_valid_timezone, ApiResponse, Error, Result, User, UserSettings, SUPPORTED_LANGUAGES,
+    THEMES,
+};
+use mongodb::bson::{self, doc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::AppState;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateNotificationsRequest {
+    pub email: Option<bool>,
+    pub push: Option<bool>,
+    pub budget_alert: Option<bool>,
+}
+
+/// 只更新请求中出现的字段
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub default_currency: Option<String>,
+    pub timezone: Option<String>,
+    pub language: Option<String>,
+    pub theme: Option<String>,
+    pub notifications: Option<UpdateNotificationsRequest>,
+}
+
+impl UpdateSettingsRequest {
+    /// 校验并合并到当前设置
+    fn apply(self, mut settings: UserSettings) -> Result<UserSettings> {
+        if let Some(currency) = self.default_currency {
+            let currency = currency.trim().to_ascii_uppercase();
+            if !is_currency_code(&currency) {
+                return Err(Error::Validation(format!("Unsupported currency: {}", currency)));
+            }
+            settings.default_currency = currency;
+        }
+        if let Some(timezone) = self.timezone {
+            let timezone = timezone.trim().to_string();
+            if !is_valid_timezone(&timezone) {
+                return Err(Error::Validation(format!("Unknown timezone: {}", timezone)));
+            }
+            settings.timezone = timezone;
+        }
+        if let Some(language) = self.language {
+            if !SUPPORTED_LANGUAGES.contains(&language.as_str()) {
+                return Err(Error::Validation(format!(
+                    "language must be one of: {}",
+                    SUPPORTED_LANGUAGES.join(", ")
+                )));
+            }
+            settings.language = language;
+        }
+        if let Some(theme) = self.theme {
+            if !THEMES.contains(&theme.as_str()) {
+                return Err(Error::Validation(format!("theme must be one of: {}", THEMES.join(", "))));
+            }
+            settings.theme = theme;
+        }
+        if let Some(notifications) = self.notifications {
+            let current = &mut settings.notifications;
+            current.email = notifications.email.unwrap_or(current.email);
+            current.push = notifications.push.unwrap_or(current.push);
+            current.budget_alert = notifications.budget_alert.unwrap_or(current.budget_alert);
+        }
+        Ok(settings)
+    }
+}
+
+async fn find_user(state: &AppState, user_id: &str) -> Result<User> {
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id }, None)
+        .await?
+        .ok_or_else(|| Error::NotFound("User not found".to_string()))
+}
+
+pub async fn get_settings(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileRead>,
+) -> Result<Json<ApiResponse<UserSettings>>> {
+    let user = find_user(&state, &claims.user_id).await?;
+    Ok(Json(ApiResponse::success(user.settings)))
+}
+
+pub async fn update_settings(
+    State(state): State<Arc<AppState>>,
+    Authorized { claims, .. }: Authorized<scope::ProfileWrite>,
+    Json(req): Json<UpdateSettingsRequest>,
+) -> Result<Json<ApiResponse<UserSettings>>> {
+    let user = find_user(&state, &claims.user_id).await?;
+    let settings = req.apply(user.settings)?;
+
+    state
+        .db
+        .mongo
+        .collection::<User>("users")
+        .update_one(
+            doc! { "_id": &claims.user_id },
+            doc! { "$set": {
+                "settings": bson::to_bson(&settings).map_err(|e| Error::InternalServer(e.to_string()))?,
+                "updated_at": bson::to_bson(&chrono::Utc::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+
+    Ok(Json(ApiResponse::success(settings)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_validates_and_merges() {
+        let request = UpdateSettingsRequest {
+            default_currency: Some(" usd ".to_string()),
+            timezone: Some("UTC".to_string()),
+            notifications: Some(UpdateNotificationsRequest {
+                budget_alert: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let settings = request.apply(UserSettings::default()).unwrap();
+        assert_eq!(settings.default_currency, "USD");
+        assert_eq!(settings.timezone, "UTC");
+        assert_eq!(settings.language, "zh-CN");
+        assert!(settings.notifications.email);
+        assert!(!settings.notifications.budget_alert);
+
+        let invalid = [
+            UpdateSettingsRequest { default_currency: Some("XXX".to_string()), ..Default::default() },
+            UpdateSettingsRequest { timezone: Some("Mars/Base".to_string()), ..Default::default() },
+            UpdateSettingsRequest { language: Some("fr-FR".to_string()), ..Default::default() },
+            UpdateSettingsRequest { theme: Some("blue".to_string()), ..Default::default() },
+        ];
+        for request in invalid {
+            assert!(request.apply(UserSettings::default()).is_err());
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// 一次性令牌 - 密码重置、邮箱验证链接及登录二次验证中的随机令牌，Redis 中只保存其 SHA-256 摘要
+use common::Result;
+use rand::RngCore;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Copy)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    /// 密码校验通过、等待二次验证的登录
+    MfaChallenge,
+}
+
+impl TokenPurpose {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Self::PasswordReset => "auth:password_reset",
+            Self::EmailVerification => "auth:email_verification",
+            Self::MfaChallenge => "auth:mfa_challenge",
+        }
+    }
+
+    /// 有效期 (秒)
+    pub fn ttl_secs(&self) -> u64 {
+        match self {
+            Self::PasswordReset => 3600,
+            Self::EmailVerification => 24 * 3600,
+            Self::MfaChallenge => 300,
+        }
+    }
+}
+
+fn digest(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// 为用户生成新令牌，同一用途下之前未使用的令牌随即失效
+pub async fn issue(redis: &ConnectionManager, purpose: TokenPurpose, user_id: &str) -> Result<String> {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let hash = digest(&token);
+
+    let user_key = format!("{}:user:{}", purpose.prefix(), user_id);
+    let mut conn = redis.clone();
+    let previous: Option<String> = conn.get(&user_key).await?;
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    if let Some(previous) = previous {
+        pipe.del(format!("{}:{}", purpose.prefix(), previous)).ignore();
+    }
+    pipe.set_ex(format!("{}:{}", purpose.prefix(), hash), user_id, purpose.ttl_secs())
+        .ignore()
+        .set_ex(&user_key, &hash, purpose.ttl_secs())
+        .ignore();
+    pipe.query_async::<_, ()>(&mut conn).await?;
+
+    Ok(token)
+}
+
+/// 同一用户同一用途的邮件最短发送间隔 (秒)，防止对同一邮箱反复发信
+const MAIL_COOLDOWN_SECS: u64 = 60;
+
+/// 占用该用户本用途的发信冷却期；冷却期内再次请求时返回 false，不应再发信
+pub async fn claim_mail_cooldown(redis: &ConnectionManager, purpose: TokenPurpose, user_id: &str) -> Result<bool> {
+    let mut conn = redis.clone();
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(format!("{}:cooldown:{}", purpose.prefix(), user_id))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(MAIL_COOLDOWN_SECS)
+        .query_async(&mut conn)
+        .await?;
+    Ok(claimed.is_some())
+}
+
+/// 查看令牌所属用户但不消费
+pub async fn peek(redis: &ConnectionManager, purpose: TokenPurpose, token: &str) -> Result<Option<String>> {
+    let mut conn = redis.clone();
+    Ok(conn.get(format!("{}:{}", purpose.prefix(), digest(token))).await?)
+}
+
+/// 消费令牌，返回其所属用户；令牌不存在、已过期或已使用时返回 None
+pub async fn consume(redis: &ConnectionManager, purpose: TokenPurpose, token: &str) -> Result<Option<String>> {
+    let mut conn = redis.clone();
+    let user_id: Option<String> = conn
+        .get_del(format!("{}:{}", purpose.prefix(), digest(token)))
+        .await?;
+
+    if let Some(user_id) = &user_id {
+        conn.del::<_, ()>(format!("{}:user:{}", purpose.prefix(), user_id)).await?;
+    }
+    Ok(user_id)
+}
@@ -0,0 +1,149 @@
+// TOTP (RFC 6238) - 基于 HMAC-SHA1 的 6 位动态口令，以及一次性恢复码
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// 时间步长 (秒)
+pub const PERIOD: u64 = 30;
+pub const DIGITS: u32 = 6;
+/// 允许前后各偏移的时间步数，容忍客户端时钟误差
+const SKEW: u64 = 1;
+const SECRET_LEN: usize = 20;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// 恢复码字符集，去掉易混淆的 0/O、1/I/L
+const RECOVERY_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+/// 生成新的 Base32 编码密钥
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    BASE32_NOPAD.decode(normalized.as_bytes()).ok()
+}
+
+/// 计算第 step 个时间步的口令 (RFC 4226 动态截断)
+fn code_at(key: &[u8], step: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    binary % 10u32.pow(digits)
+}
+
+/// 校验口令，成功时返回匹配的时间步，供调用方拒绝重放
+pub fn verify(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let expected: u32 = code.parse().ok()?;
+    let key = decode_secret(secret)?;
+
+    let current = unix_time / PERIOD;
+    (current.saturating_sub(SKEW)..=current + SKEW).find(|step| code_at(&key, *step, DIGITS) == expected)
+}
+
+/// 供认证器 App 扫码的 otpauth:// 地址
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        DIGITS,
+        PERIOD
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// 生成一组 XXXXX-XXXXX 格式的恢复码 (明文只返回给用户一次)
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rngs::OsRng;
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| RECOVERY_ALPHABET[rng.gen_range(0..RECOVERY_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// 恢复码的存储摘要；忽略大小写、空白和分隔符
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 附录 B 的 SHA1 测试密钥 "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        let key = decode_secret(RFC_SECRET).unwrap();
+        assert_eq!(code_at(&key, 59 / PERIOD, 8), 94287082);
+        assert_eq!(code_at(&key, 1111111109 / PERIOD, 8), 7081804);
+        assert_eq!(code_at(&key, 1234567890 / PERIOD, 8), 89005924);
+        assert_eq!(code_at(&key, 20000000000 / PERIOD, 8), 65353130);
+    }
+
+    #[test]
+    fn test_verify_allows_one_step_of_skew() {
+        let now = 1234567890;
+        assert_eq!(verify(RFC_SECRET, "005924", now), Some(now / PERIOD));
+        assert_eq!(verify(RFC_SECRET, "005924", now + PERIOD), Some(now / PERIOD));
+        assert_eq!(verify(RFC_SECRET, "005924", now + 2 * PERIOD), None);
+        assert_eq!(verify(RFC_SECRET, "5924", now), None);
+        assert_eq!(verify(RFC_SECRET, "abcdef", now), None);
+
+        let secret = generate_secret();
+        assert!(decode_secret(&secret.to_lowercase()).is_some());
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 11 && code.as_bytes()[5] == b'-'));
+        assert_eq!(hash_recovery_code("abcde-fghjk"), hash_recovery_code(" ABCDEFGHJK "));
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = provisioning_uri("ABook", "alice@example.com", "JBSWY3DPEHPK3PXP");
+        assert_eq!(
+            uri,
+            "otpauth://totp/ABook:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=ABook&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -40,7 +40,7 @@ echo -e "${YELLOW}[3/5] 编译项目...${NC}"
 echo -e "${YELLOW}[4/5] 配置环境变量...${NC}"
 cat > "$SCRIPT_DIR/.env" << EOF
 # 数据库配置
-MONGO_URI=mongodb://localhost:27017
+MONGO_URI=mongodb://localhost:27017/?replicaSet=rs0
 REDIS_URI=redis://localhost:6379
 
 # JWT配置
@@ -55,12 +55,7 @@ docker-compose ps || docker compose ps
 echo ""
 echo -e "${YELLOW}访问地址:${NC}"
 echo "  前端: http://localhost"
-echo "  用户服务: http://localhost:3000"
-echo "  账户服务: http://localhost:3001"
-echo "  交易服务: http://localhost:3002"
-echo "  预算服务: http://localhost:3003"
-echo "  报表服务: http://localhost:3004"
-echo "  汇率服务: http://localhost:3005"
+echo "  后端 API: http://localhost/api (各服务端口不对宿主机开放，只经前端 nginx 转发)"
 echo ""
 echo -e "${YELLOW}管理命令:${NC}"
 echo "  查看日志: docker-compose logs -f"
@@ -3,6 +3,7 @@ import { Layout as AntLayout, Menu, Avatar, Button, Space, Typography } from 'an
 import { PieChartOutlined, WalletOutlined, TransactionOutlined, BarChartOutlined, SettingOutlined, LogoutOutlined } from '@ant-design/icons'
 import { NavLink, Outlet, useNavigate } from 'react-router-dom'
 import { useAuthStore } from '../stores/authStore'
+import api from '../utils/api'
 
 const { Header, Sider, Content } = AntLayout
 const { Text } = Typography
@@ -13,7 +14,12 @@ export default function Layout() {
   const logout = useAuthStore((s) => s.logout)
   const navigate = useNavigate()
 
-  const handleLogout = () => {
+  const handleLogout = async () => {
+    const refreshToken = useAuthStore.getState().refreshToken
+    if (refreshToken) {
+      // 服务端作废刷新令牌；失败时仍然清除本地登录状态
+      await api.post('/logout', { refresh_token: refreshToken }).catch(() => undefined)
+    }
     logout()
     navigate('/login')
   }
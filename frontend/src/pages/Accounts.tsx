@@ -245,6 +245,7 @@ export default function Accounts() {
                 <Select.Option value="active">活跃</Select.Option>
                 <Select.Option value="inactive">停用</Select.Option>
                 <Select.Option value="closed">关闭</Select.Option>
+                <Select.Option value="archived">归档</Select.Option>
               </Select>
             </Form.Item>
           )}
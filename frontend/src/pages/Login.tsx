@@ -1,3 +1,4 @@
+import { useState } from 'react'
 import { Form, Input, Button, Card, message, ConfigProvider, theme } from 'antd'
 import { useNavigate, Link } from 'react-router-dom'
 import { useAuthStore } from '../stores/authStore'
@@ -8,15 +9,50 @@ export default function Login() {
   const navigate = useNavigate()
   const login = useAuthStore((state) => state.login)
 
+  // 开启二次验证的账户在密码校验通过后需再提交口令
+  const [mfaToken, setMfaToken] = useState<string | null>(null)
+
+  const finishLogin = (data: any) => {
+    const { user, access_token, refresh_token } = data
+    login(user, access_token, refresh_token)
+    message.success('登录成功')
+    navigate('/')
+  }
+
+  const onVerify = async (values: { code: string }) => {
+    try {
+      const response = await api.post('/login/mfa', { mfa_token: mfaToken, code: values.code })
+      finishLogin(response.data)
+    } catch (error: any) {
+      if (error.response?.status === 429) {
+        message.error('尝试次数过多，请稍后再试')
+      } else if (error.response?.data?.error?.message === 'Invalid verification code') {
+        message.error('验证码错误')
+      } else {
+        setMfaToken(null)
+        message.error('验证已过期，请重新登录')
+      }
+    }
+  }
+
   const onFinish = async (values: { email: string; password: string }) => {
     try {
       const response = await api.post('/login', values)
-      const { user, access_token, refresh_token } = response.data
-      login(user, access_token, refresh_token)
-      message.success('登录成功')
-      navigate('/')
-    } catch (error) {
-      message.error('登录失败，请检查邮箱和密码')
+      if (response.data.mfa_required) {
+        setMfaToken(response.data.mfa_token)
+        return
+      }
+      finishLogin(response.data)
+    } catch (error: any) {
+      if (error.response?.status === 429) {
+        message.error('尝试次数过多，请稍后再试')
+      } else if (error.response?.status === 403) {
+        message.error(error.response.data?.error?.message === 'Email address has not been verified'
+          ? '邮箱尚未验证，请查收验证邮件'
+          : '账户已被禁用')
+      } else {
+        message.error('登录失败，请检查邮箱和密码')
+      }
     }
   }
 
@@ -65,6 +101,26 @@ export default function Login() {
             bodyStyle={{ background: 'transparent' }}
             bordered={false}
           >
+            {mfaToken ? (
+            <Form onFinish={onVerify} layout="vertical" size="large">
+              <Form.Item 
+                label={<span style={{ color: 'rgba(255,255,255,0.9)' }}>验证码</span>} 
+                name="code" 
+                extra={<span style={{ color: 'rgba(255,255,255,0.45)' }}>输入认证器中的 6 位验证码，或一个恢复码</span>}
+                rules={[{ required: true, message: '请输入验证码' }]}
+              >
+                <Input placeholder="123456" autoComplete="one-time-code" autoFocus />
+              </Form.Item>
+              <Form.Item>
+                <Button type="primary" htmlType="submit" block style={{ height: 44, fontSize: 16 }}>
+                  验证
+                </Button>
+              </Form.Item>
+              <div style={{ textAlign: 'center' }}>
+                <a onClick={() => setMfaToken(null)} style={{ color: '#40a9ff' }}>返回</a>
+              </div>
+            </Form>
+            ) : (
             <Form onFinish={onFinish} layout="vertical" size="large">
               <Form.Item 
                 label={<span style={{ color: 'rgba(255,255,255,0.9)' }}>邮箱</span>} 
@@ -100,8 +156,11 @@ export default function Login() {
                 <span style={{ color: 'rgba(255,255,255,0.65)' }}>还没有账户？</span>
                 {' '}
                 <Link to="/register" style={{ color: '#40a9ff', fontWeight: 500 }}>立即注册</Link>
+                <span style={{ color: 'rgba(255,255,255,0.35)', margin: '0 8px' }}>|</span>
+                <Link to="/reset-password" style={{ color: '#40a9ff' }}>忘记密码？</Link>
               </div>
             </Form>
+            )}
           </Card>
         </ConfigProvider>
       </div>
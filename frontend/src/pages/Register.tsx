@@ -8,11 +8,11 @@ export default function Register() {
 
   const onFinish = async (values: { username: string; email: string; password: string }) => {
     try {
-      await api.post('/register', values)
-      message.success('注册成功，请登录')
+      const response = await api.post('/register', values)
+      message.success(response.data.verification_required ? '注册成功，请查收验证邮件后登录' : '注册成功，请登录')
       navigate('/login')
-    } catch (error) {
-      message.error('注册失败，请重试')
+    } catch (error: any) {
+      message.error(error.response?.data?.error?.message || '注册失败，请重试')
     }
   }
 
@@ -38,8 +38,8 @@ export default function Reports() {
     queryKey: ['monthly-report', dateRange],
     queryFn: () => api.get('/reports/monthly', {
       params: {
-        start_date: dateRange[0].toISOString(),
-        end_date: dateRange[1].toISOString(),
+        start_date: dateRange[0].format('YYYY-MM-DD'),
+        end_date: dateRange[1].format('YYYY-MM-DD'),
       }
     }),
   })
@@ -48,8 +48,8 @@ export default function Reports() {
     queryKey: ['category-report', dateRange],
     queryFn: () => api.get('/reports/category', {
       params: {
-        start_date: dateRange[0].toISOString(),
-        end_date: dateRange[1].toISOString(),
+        start_date: dateRange[0].format('YYYY-MM-DD'),
+        end_date: dateRange[1].format('YYYY-MM-DD'),
       }
     }),
   })
@@ -58,8 +58,8 @@ export default function Reports() {
     queryKey: ['trend-report', dateRange],
     queryFn: () => api.get('/reports/trend', {
       params: {
-        start_date: dateRange[0].toISOString(),
-        end_date: dateRange[1].toISOString(),
+        start_date: dateRange[0].format('YYYY-MM-DD'),
+        end_date: dateRange[1].format('YYYY-MM-DD'),
       }
     }),
   })
@@ -0,0 +1,78 @@
+import { useState } from 'react'
+import { Form, Input, Button, Card, Result, message } from 'antd'
+import { Link, useNavigate, useSearchParams } from 'react-router-dom'
+import api from '../utils/api'
+
+// 无 token 时申请重置邮件，带 token (来自邮件链接) 时设置新密码
+export default function ResetPassword() {
+  const [searchParams] = useSearchParams()
+  const token = searchParams.get('token')
+  const navigate = useNavigate()
+  const [sent, setSent] = useState(false)
+
+  const requestReset = async (values: { email: string }) => {
+    try {
+      await api.post('/password/forgot', values)
+      setSent(true)
+    } catch {
+      message.error('发送失败，请稍后重试')
+    }
+  }
+
+  const resetPassword = async (values: { new_password: string }) => {
+    try {
+      await api.post('/password/reset', { token, new_password: values.new_password })
+      message.success('密码已重置，请重新登录')
+      navigate('/login')
+    } catch (error: any) {
+      message.error(error.response?.data?.error?.message || '重置失败，链接可能已失效')
+    }
+  }
+
+  return (
+    <div style={{ display: 'flex', justifyContent: 'center', alignItems: 'center', minHeight: '100vh', background: '#f0f2f5' }}>
+      <Card title="重置密码" style={{ width: 400 }}>
+        {token ? (
+          <Form onFinish={resetPassword} layout="vertical" size="large">
+            <Form.Item label="新密码" name="new_password" rules={[{ required: true, message: '请输入新密码' }]}>
+              <Input.Password placeholder="请输入新密码" />
+            </Form.Item>
+            <Form.Item
+              label="确认新密码"
+              name="confirm"
+              dependencies={['new_password']}
+              rules={[
+                { required: true, message: '请再次输入新密码' },
+                ({ getFieldValue }) => ({
+                  validator: (_, value) =>
+                    !value || getFieldValue('new_password') === value
+                      ? Promise.resolve()
+                      : Promise.reject(new Error('两次输入的密码不一致')),
+                }),
+              ]}
+            >
+              <Input.Password placeholder="请再次输入新密码" />
+            </Form.Item>
+            <Button type="primary" htmlType="submit" block>
+              重置密码
+            </Button>
+          </Form>
+        ) : sent ? (
+          <Result status="success" title="如果该邮箱已注册，重置链接已发送" extra={<Link to="/login">返回登录</Link>} />
+        ) : (
+          <Form onFinish={requestReset} layout="vertical" size="large">
+            <Form.Item label="邮箱" name="email" rules={[{ required: true, type: 'email', message: '请输入有效的邮箱地址' }]}>
+              <Input placeholder="请输入注册邮箱" />
+            </Form.Item>
+            <Button type="primary" htmlType="submit" block>
+              发送重置链接
+            </Button>
+            <div style={{ textAlign: 'center', marginTop: 16 }}>
+              <Link to="/login">返回登录</Link>
+            </div>
+          </Form>
+        )}
+      </Card>
+    </div>
+  )
+}
@@ -0,0 +1,35 @@
+import { useEffect, useRef, useState } from 'react'
+import { Card, Result, Spin } from 'antd'
+import { Link, useSearchParams } from 'react-router-dom'
+import api from '../utils/api'
+
+export default function VerifyEmail() {
+  const [searchParams] = useSearchParams()
+  const token = searchParams.get('token')
+  const [status, setStatus] = useState<'pending' | 'success' | 'error'>(token ? 'pending' : 'error')
+  // 令牌只能使用一次，避免开发模式下重复提交
+  const submitted = useRef(false)
+
+  useEffect(() => {
+    if (!token || submitted.current) return
+    submitted.current = true
+    api
+      .post('/email/verify', { token })
+      .then(() => setStatus('success'))
+      .catch(() => setStatus('error'))
+  }, [token])
+
+  return (
+    <div style={{ display: 'flex', justifyContent: 'center', alignItems: 'center', minHeight: '100vh', background: '#f0f2f5' }}>
+      <Card style={{ width: 400 }}>
+        {status === 'pending' && <Spin tip="正在验证..." />}
+        {status === 'success' && (
+          <Result status="success" title="邮箱验证成功" extra={<Link to="/login">前往登录</Link>} />
+        )}
+        {status === 'error' && (
+          <Result status="error" title="验证链接无效或已过期" extra={<Link to="/login">返回登录</Link>} />
+        )}
+      </Card>
+    </div>
+  )
+}
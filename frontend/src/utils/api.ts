@@ -25,15 +25,16 @@ api.interceptors.response.use(
       if (refreshToken) {
         try {
           const response = await axios.post('/api/refresh', { refresh_token: refreshToken })
-          // Backend returns {success: true, data: "new_access_token"}
-          const access_token = response.data.data || response.data.access_token
-          if (!access_token) {
-            throw new Error('No access token in refresh response')
+          // Backend returns {success: true, data: {access_token, refresh_token, expires_in}}
+          // 刷新令牌每次使用后都会轮换，必须保存新的刷新令牌
+          const { access_token, refresh_token } = response.data.data || {}
+          if (!access_token || !refresh_token) {
+            throw new Error('No tokens in refresh response')
           }
           useAuthStore.getState().login(
             useAuthStore.getState().user!,
             access_token,
-            refreshToken
+            refresh_token
           )
           error.config.headers.Authorization = `Bearer ${access_token}`
           return axios.request(error.config)
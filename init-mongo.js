@@ -8,7 +8,14 @@ db.createCollection('users');
 db.createCollection('accounts');
 db.createCollection('transactions');
 db.createCollection('categories');
+db.createCollection('category_rules');
+db.createCollection('category_models');
+db.createCollection('recurring_transactions');
 db.createCollection('budgets');
+db.createCollection('import_jobs');
+db.createCollection('reconciliations');
+db.createCollection('api_tokens');
+db.createCollection('user_mfa');
 
 // 创建索引
 print('Creating indexes...');
@@ -16,6 +23,7 @@ print('Creating indexes...');
 // 用户索引
 db.users.createIndex({ email: 1 }, { unique: true });
 db.users.createIndex({ username: 1 });
+db.users.createIndex({ role: 1, status: 1 });
 
 // 账户索引
 db.accounts.createIndex({ user_id: 1 });
@@ -24,17 +32,39 @@ db.accounts.createIndex({ user_id: 1, status: 1 });
 // 交易索引
 db.transactions.createIndex({ user_id: 1 });
 db.transactions.createIndex({ user_id: 1, transaction_date: -1 });
+db.transactions.createIndex({ user_id: 1, category_id: 1, transaction_date: 1 });
 db.transactions.createIndex({ account_id: 1 });
 db.transactions.createIndex({ category_id: 1 });
+db.transactions.createIndex({ user_id: 1, account_id: 1, external_id: 1 }, { sparse: true });
+db.transactions.createIndex({ user_id: 1, external_id: 1 }, { name: 'user_recurring_external_id', unique: true, partialFilterExpression: { external_id: { $gte: 'recurring:', $lt: 'recurring;' } } });
+db.transactions.createIndex({ user_id: 1, dedup_hash: 1 });
 
 // 分类索引
 db.categories.createIndex({ user_id: 1 });
 db.categories.createIndex({ user_id: 1, category_type: 1 });
+db.categories.createIndex({ user_id: 1, key: 1 }, { unique: true, partialFilterExpression: { key: { $exists: true } } });
+db.categories.createIndex({ user_id: 1, parent_id: 1 });
+
+// 自动分类规则索引
+db.category_rules.createIndex({ user_id: 1, priority: 1 });
+
+// 周期性交易索引
+db.recurring_transactions.createIndex({ user_id: 1 });
+db.recurring_transactions.createIndex({ status: 1, next_occurrence: 1 });
 
 // 预算索引
 db.budgets.createIndex({ user_id: 1 });
 db.budgets.createIndex({ user_id: 1, period: 1 });
 
+// 导入任务索引
+db.import_jobs.createIndex({ user_id: 1, created_at: -1 });
+
+// 对账结果索引
+db.reconciliations.createIndex({ user_id: 1, status: 1 });
+
+// API 令牌索引
+db.api_tokens.createIndex({ user_id: 1, created_at: -1 });
+
 print('Indexes created successfully!');
 
 // 创建应用用户（可选）
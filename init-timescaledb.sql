@@ -1,3 +1,5 @@
+-- 初始表结构；之后的结构变更是 backend/common/migrations 下的版本化迁移，服务连接 TimescaleDB 时自动执行
+
 -- 启用 TimescaleDB 扩展
 CREATE EXTENSION IF NOT EXISTS timescaledb;
 